@@ -17,10 +17,10 @@ pub fn create_account(address: String) -> String {
     STATE.with(|s| {
         let mut state = s.borrow_mut();
         if state.accounts.contains(&address) {
-            return "Already exists".into();
+            "Already exists".into()
         } else {
             state.accounts.insert(address);
-            return "Account created".into();
+            "Account created".into()
         }
     })
 }
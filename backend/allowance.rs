@@ -0,0 +1,114 @@
+use candid::{Nat, Principal};
+use futures::future::LocalBoxFuture;
+use icrc_ledger_types::icrc1::account::Account as IcrcAccount;
+use icrc_ledger_types::icrc2::allowance::Allowance;
+use icrc_ledger_types::icrc2::approve::ApproveArgs;
+use serde_json::{json, Value};
+
+use crate::{icrc1_fee, icrc2_allowance, lookup_pending, LAST_PLAN_BY_CALLER};
+use crate::tools::{parse_args, Param, Permission, Tool, ToolArgs, ToolCtx};
+use crate::types::{ApprovalPrepArgs, PreparedApproval};
+use crate::utils::{format_amount, format_date_ns, is_placeholder, resolve_token, scale_amount, token_symbols, NANOS_PER_DAY};
+
+/// Default validity of an allowance prepared for a budget.
+pub const BUDGET_VALID_DAYS: u32 = 7;
+
+/// Allowance needed to execute transfers of `amounts`, each paying `fee`.
+pub fn needed_for(amounts: &[Nat], fee: &Nat) -> Nat {
+    amounts.iter().fold(Nat::from(0u32), |acc, a| acc + a.clone() + fee.clone())
+}
+
+/// Build the approve call granting `spender` an allowance of `needed` until `expires_at`.
+///
+/// `token` is the `(ledger, decimals, symbol)` triple from [`resolve_token`]; `expected_allowance`
+/// pins the live value (zero once expired) so a concurrent approve makes the call fail.
+pub fn build(
+    spender: Principal,
+    token: &(Principal, u8, String),
+    needed: Nat,
+    fee: Nat,
+    current: &Allowance,
+    expires_at: u64,
+    now: u64,
+) -> PreparedApproval {
+    let (ledger, decimals, symbol) = (token.0, token.1, token.2.as_str());
+    let live = current.expires_at.is_none_or(|e| e > now);
+    let current_allowance = if live { current.allowance.clone() } else { Nat::from(0u32) };
+    let sufficient = current_allowance >= needed && current.expires_at.is_none_or(|e| e >= expires_at);
+    let human_readable = format!(
+        "Approve {} to spend up to {} {} (incl. fee {}) until {}. Current allowance {}.",
+        spender.to_text(), format_amount(&needed, decimals), symbol, format_amount(&fee, decimals),
+        format_date_ns(expires_at), format_amount(&current_allowance, decimals),
+    );
+    PreparedApproval {
+        ledger,
+        symbol: symbol.to_string(),
+        args: ApproveArgs {
+            from_subaccount: None,
+            spender: IcrcAccount { owner: spender, subaccount: None },
+            amount: needed,
+            expected_allowance: Some(current_allowance.clone()),
+            expires_at: Some(expires_at),
+            fee: Some(fee),
+            memo: None,
+            created_at_time: None,
+        },
+        current_allowance,
+        sufficient,
+        human_readable,
+    }
+}
+
+/// Prepare the approve call `owner` has to sign for a plan or budget.
+pub async fn prepare(owner: Principal, spender: Principal, a: &ApprovalPrepArgs, now: u64) -> Result<PreparedApproval, Value> {
+    let budget = a.budget_dec.as_deref().filter(|b| !is_placeholder(b));
+    let (token, amounts) = match budget {
+        Some(b) => {
+            let token = resolve_token(a.symbol.as_deref(), None)
+                .map_err(|_| json!({"status":"err","code":"BadToken","field":"symbol","options": token_symbols()}))?;
+            let amount = scale_amount(b, token.1)
+                .map_err(|e| json!({"status":"err","code":"BadAmount","field":"budget_dec","error":e}))?;
+            (token, vec![amount])
+        }
+        None => {
+            let pending = match a.checksum.as_deref().filter(|cs| !is_placeholder(cs)) {
+                Some(cs) => lookup_pending(cs),
+                None => LAST_PLAN_BY_CALLER.with(|m| m.borrow().get(&owner).cloned()),
+            };
+            let Some(pending) = pending.filter(|p| p.owner() == Some(owner)) else {
+                return Err(json!({"status":"err","code":"UnknownPlan","error":"give a plan checksum or a budget_dec"}));
+            };
+            let legs = pending.legs();
+            let token = resolve_token(Some(&legs[0].symbol), None)
+                .map_err(|_| json!({"status":"err","code":"BadToken","options": token_symbols()}))?;
+            (token, legs.iter().map(|l| l.amount.clone()).collect())
+        }
+    };
+    let ledger = token.0;
+    let fee = icrc1_fee(ledger).await.map_err(|e| json!({"status":"err","code":"LedgerUnavailable","error":e}))?;
+    let current = icrc2_allowance(ledger, IcrcAccount { owner, subaccount: None }, spender).await
+        .map_err(|e| json!({"status":"err","code":"LedgerUnavailable","error":e}))?;
+    let needed = needed_for(&amounts, &fee);
+    let days = a.valid_days.filter(|d| *d > 0).unwrap_or(if budget.is_some() { BUDGET_VALID_DAYS } else { 1 });
+    let expires_at = now + days as u64 * NANOS_PER_DAY;
+    Ok(build(spender, &token, needed, fee, &current, expires_at, now))
+}
+
+/// `prepare_approval`: the approve call the user signs in the wallet.
+pub struct PrepareApprovalTool;
+
+impl Tool for PrepareApprovalTool {
+    fn name(&self) -> &'static str { "prepare_approval" }
+    fn description(&self) -> Option<&'static str> {
+        Some("Prepare the ICRC-2 approve the user signs in the wallet, for a plan (checksum) or a budget.")
+    }
+    fn params(&self) -> Vec<Param> { ApprovalPrepArgs::params() }
+    fn permission(&self) -> Permission { Permission::Read }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let a: ApprovalPrepArgs = parse_args(args)?;
+            let p = prepare(ctx.caller, ic_cdk::api::id(), &a, ctx.now).await?;
+            Ok(json!({"status":"ok","sufficient":p.sufficient,"summary":p.human_readable}))
+        })
+    }
+}
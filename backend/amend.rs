@@ -0,0 +1,158 @@
+use candid::Principal;
+use futures::future::LocalBoxFuture;
+use serde_json::{json, Value};
+
+use crate::{annotate_fiat, build_plan, cosign, fiat_to_token_amount, remember_plan, CANCELLED_CHECKSUMS, EXECUTED_CHECKSUMS, LAST_PLAN_BY_CALLER, PLAN_BY_CHECKSUM, SUPERSEDED_BY};
+use crate::tools::{parse_args, Param, Permission, Tool, ToolArgs, ToolCtx};
+use crate::types::{AmendArgs, ExecError, PendingPlan, PlanArgs, TransferPlan};
+use crate::utils::{format_amount, is_placeholder, plan_checksum, plan_memo_text, resolve_token, PLAN_TTL_NS};
+
+/// Build a new version of `old` for `caller` with the fields of `a` changed.
+pub fn amend(caller: Principal, old: &TransferPlan, a: &AmendArgs, now: u64) -> Result<TransferPlan, Value> {
+    if old.from_owner != Some(caller) {
+        return Err(json!({"status":"err","code":"NotOwner"}));
+    }
+    if old.invoice_id.is_some() || old.escrow_id.is_some() {
+        return Err(json!({"status":"err","code":"NotAmendable","error":"invoice and escrow plans cannot be amended"}));
+    }
+    if let Some(by) = superseded_by(&old.checksum) {
+        return Err(json!({"status":"err","code":"Superseded","error":format!("amend the newer plan {}", by)}));
+    }
+    if EXECUTED_CHECKSUMS.with(|s| s.borrow().contains(&old.checksum)) {
+        return Err(json!({"status":"err","code":"Duplicate","error":"plan already executed"}));
+    }
+    let changed = |v: &Option<String>| v.clone().filter(|s| !is_placeholder(s));
+    let new_to = changed(&a.to);
+    let new_symbol = changed(&a.symbol);
+    let amount_dec = match changed(&a.amount_dec) {
+        Some(v) => v,
+        None => {
+            let (_, decimals, _) = resolve_token(Some(&old.symbol), None)
+                .map_err(|_| json!({"status":"err","code":"BadToken","field":"symbol"}))?;
+            format_amount(&old.amount, decimals)
+        }
+    };
+    let args = PlanArgs {
+        to: new_to.clone().unwrap_or_else(|| old.to_principal.to_text()),
+        amount_dec,
+        symbol: Some(new_symbol.unwrap_or_else(|| old.symbol.clone())),
+        ledger: None,
+        decimals: None,
+        memo: changed(&a.memo).or_else(|| plan_memo_text(old)),
+    };
+    let mut plan = build_plan(&args, caller, now)?;
+    if new_to.is_none() {
+        plan.to_sub = old.to_sub;
+    }
+    // links and the exact memo bytes stay with the payment
+    plan.invoice_id = old.invoice_id;
+    plan.escrow_id = old.escrow_id;
+    if changed(&a.memo).is_none() {
+        plan.memo = old.memo.clone();
+    }
+    // co-signer setup changes since the original plan must not weaken its quorum
+    if old.approvals_required > plan.approvals_required {
+        let note = |n: u8| format!(" Needs {} co-signer approval(s).", n);
+        plan.human_readable = plan.human_readable.replace(&note(plan.approvals_required), "") + &note(old.approvals_required);
+        plan.approvals_required = old.approvals_required;
+    }
+    plan.supersedes = Some(old.checksum.clone());
+    plan.checksum = plan_checksum(&plan);
+    if plan.checksum == old.checksum {
+        return Err(json!({"status":"err","code":"NoChange"}));
+    }
+    Ok(plan)
+}
+
+/// Mark `old` as replaced by `new`.
+pub fn supersede(old: &str, new: &str) {
+    SUPERSEDED_BY.with(|m| { m.borrow_mut().insert(old.to_string(), new.to_string()); });
+}
+
+/// Checksum of the plan that replaced `checksum`, if any.
+pub fn superseded_by(checksum: &str) -> Option<String> {
+    SUPERSEDED_BY.with(|m| m.borrow().get(checksum).cloned())
+}
+
+/// Cancel `plan`: it, its legs and their co-signer requests can no longer be executed or approved.
+pub fn cancel(plan: &PendingPlan) {
+    CANCELLED_CHECKSUMS.with(|m| {
+        let mut m = m.borrow_mut();
+        m.insert(plan.checksum().to_string());
+        m.extend(plan.legs().iter().map(|l| l.checksum.clone()));
+    });
+    for leg in plan.legs() {
+        cosign::close(&leg.checksum);
+    }
+}
+
+/// Fail if the plan `checksum` was amended or cancelled.
+pub fn check_current(checksum: &str) -> Result<(), ExecError> {
+    if CANCELLED_CHECKSUMS.with(|m| m.borrow().contains(checksum)) {
+        return Err(ExecError::Cancelled);
+    }
+    match superseded_by(checksum) {
+        Some(by) => Err(ExecError::Superseded { by }),
+        None => Ok(()),
+    }
+}
+
+/// All versions of the plan `checksum`, oldest first.
+pub fn history(checksum: &str) -> Vec<TransferPlan> {
+    let mut out = Vec::new();
+    let mut next = Some(checksum.to_string());
+    while let Some(cs) = next {
+        let Some(p) = PLAN_BY_CHECKSUM.with(|m| m.borrow().get(&cs).cloned()) else { break; };
+        next = p.supersedes.clone();
+        out.push(p);
+    }
+    out.reverse();
+    let mut newer = superseded_by(checksum);
+    while let Some(cs) = newer {
+        let Some(p) = PLAN_BY_CHECKSUM.with(|m| m.borrow().get(&cs).cloned()) else { break; };
+        newer = superseded_by(&cs);
+        out.push(p);
+    }
+    out
+}
+
+/// `amend_plan`: change fields of the pending plan.
+pub struct AmendPlanTool;
+
+impl Tool for AmendPlanTool {
+    fn name(&self) -> &'static str { "amend_plan" }
+    fn description(&self) -> Option<&'static str> {
+        Some("Change fields of the pending plan. Returns a new TransferPlan+checksum; the old one can no longer be confirmed.")
+    }
+    fn params(&self) -> Vec<Param> { AmendArgs::params() }
+    fn permission(&self) -> Permission { Permission::Plan }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let mut a: AmendArgs = parse_args(args)?;
+            let old = match a.checksum.as_deref().filter(|cs| !is_placeholder(cs)) {
+                Some(cs) => PLAN_BY_CHECKSUM.with(|m| m.borrow().get(cs).cloned()),
+                None => match LAST_PLAN_BY_CALLER.with(|m| m.borrow().get(&ctx.caller).cloned()) {
+                    Some(PendingPlan::Transfer(p)) => Some(p),
+                    _ => None,
+                },
+            };
+            let Some(old) = old else {
+                return Err(json!({"status":"err","code":"UnknownPlan","error":"no single-transfer plan to amend"}));
+            };
+            // "Rp 50.000" or "$5" becomes a token amount at the current price, as in plan_transfer
+            if let Some(amount_dec) = a.amount_dec.clone().filter(|v| !is_placeholder(v)) {
+                let symbol = a.symbol.clone().filter(|s| !is_placeholder(s)).unwrap_or_else(|| old.symbol.clone());
+                let mut p = PlanArgs { to: String::new(), amount_dec, symbol: Some(symbol), ledger: None, decimals: None, memo: None };
+                fiat_to_token_amount(&mut p, ctx.now).await?;
+                a.amount_dec = Some(p.amount_dec);
+            }
+            let mut plan = amend(ctx.caller, &old, &a, ctx.now)?;
+            annotate_fiat(&mut plan).await;
+            crate::log!("[amend_plan] {} -> {}", old.checksum, plan.checksum);
+            supersede(&old.checksum, &plan.checksum);
+            cosign::open_amended(&old.checksum, &plan, Some(plan.created_at_time + PLAN_TTL_NS));
+            remember_plan(ctx.caller, &plan);
+            Ok(serde_json::to_value(&plan).unwrap())
+        })
+    }
+}
@@ -0,0 +1,205 @@
+use candid::{Nat, Principal};
+use futures::future::LocalBoxFuture;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::{ACCOUNTS, ACCOUNT_TAGS, RECEIPTS};
+use crate::tools::{parse_args, Param, Permission, Tool, ToolArgs, ToolCtx};
+use crate::types::{Receipt, SavedAccount, SpendingSummary, SummaryArgs, SummaryGroup, TOKENS};
+use crate::utils::{
+    civil_from_days, days_from_civil, days_in_month, format_amount, format_date_ns, is_placeholder, parse_date_ns,
+    resolve_to, NANOS_PER_DAY,
+};
+
+/// Top recipients returned when the user gives no count.
+pub const DEFAULT_TOP: u32 = 5;
+
+/// Bucket label of `ts`: "2026-09-14" per day, "week of 2026-09-14" (Monday) per week, "2026-09" per month.
+pub fn period_key(ts: u64, period: &str) -> String {
+    let days = (ts / NANOS_PER_DAY) as i64;
+    match period {
+        "day" => format_date_ns(ts),
+        // 1970-01-01 was a Thursday
+        "week" => format!("week of {}", format_date_ns((days - (days + 3).rem_euclid(7)) as u64 * NANOS_PER_DAY)),
+        _ => {
+            let (y, m, _) = civil_from_days(days);
+            format!("{:04}-{:02}", y, m)
+        }
+    }
+}
+
+/// Parse a range bound; a bare `YYYY-MM` covers the whole month. Returns `[start, end)` of the bound.
+pub fn parse_bound(raw: &str) -> Result<(u64, u64), String> {
+    let t = raw.trim();
+    if t.len() == 7 {
+        let start = parse_date_ns(&format!("{}-01", t))?;
+        let (y, m, _) = civil_from_days((start / NANOS_PER_DAY) as i64);
+        return Ok((start, (days_from_civil(y, m, 1) + days_in_month(y, m) as i64) as u64 * NANOS_PER_DAY));
+    }
+    let start = parse_date_ns(t)?;
+    Ok((start, start + NANOS_PER_DAY))
+}
+
+fn decimals_of(symbol: &str) -> u8 {
+    TOKENS.iter().find(|t| t.symbol == symbol).map(|t| t.decimals).unwrap_or(0)
+}
+
+/// Alias of the receipt's recipient, else its principal text.
+fn recipient_label(r: &Receipt, accounts: &[SavedAccount]) -> String {
+    accounts.iter().find(|a| a.owner == r.to_principal && a.subaccount == r.to_sub)
+        .map(|a| a.alias.clone())
+        .unwrap_or_else(|| r.to_principal.to_text())
+}
+
+/// Tags of the receipt's recipient, each once even if several aliases share it.
+fn recipient_tags<'a>(r: &Receipt, accounts: &'a [SavedAccount]) -> Vec<&'a str> {
+    let mut tags: Vec<&str> = accounts.iter().filter(|a| a.owner == r.to_principal && a.subaccount == r.to_sub)
+        .flat_map(|a| a.tags.iter().map(String::as_str))
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+fn groups(map: BTreeMap<(String, String), (Nat, u32)>) -> Vec<SummaryGroup> {
+    map.into_iter().map(|((key, symbol), (total, count))| SummaryGroup {
+        total_dec: format_amount(&total, decimals_of(&symbol)),
+        key, symbol, total, count,
+    }).collect()
+}
+
+fn add(map: &mut BTreeMap<(String, String), (Nat, u32)>, key: String, r: &Receipt) {
+    let e = map.entry((key, r.symbol.clone())).or_insert((Nat::from(0u32), 0));
+    e.0 += r.amount.clone();
+    e.1 += 1;
+}
+
+/// Aggregate `receipts` matching `a`; `recipient` is the already resolved recipient filter.
+pub fn summarize(
+    receipts: &[Receipt],
+    accounts: &[SavedAccount],
+    a: &SummaryArgs,
+    recipient: Option<(Principal, Option<[u8; 32]>)>,
+) -> Result<SpendingSummary, Value> {
+    let opt = |v: &Option<String>| v.as_deref().map(str::trim).filter(|s| !is_placeholder(s)).map(str::to_string);
+    let symbol = opt(&a.symbol).map(|s| s.to_ascii_uppercase());
+    let tag = opt(&a.tag).map(|t| t.to_lowercase());
+    let period = match opt(&a.period).map(|p| p.to_lowercase()) {
+        None => "month".to_string(),
+        Some(p) if ["day", "week", "month"].contains(&p.as_str()) => p,
+        Some(_) => return Err(json!({"status":"err","code":"BadArgs","field":"period","options":["day","week","month"]})),
+    };
+    let from = opt(&a.from).map(|f| parse_bound(&f))
+        .transpose().map_err(|e| json!({"status":"err","code":"BadDate","field":"from","error":e,"example":"2026-09"}))?
+        .map(|(start, _)| start);
+    let to = opt(&a.to).map(|t| parse_bound(&t))
+        .transpose().map_err(|e| json!({"status":"err","code":"BadDate","field":"to","error":e,"example":"2026-09-30"}))?
+        .map(|(_, end)| end);
+
+    let (mut totals, mut by_period, mut by_recipient, mut by_tag) =
+        (BTreeMap::new(), BTreeMap::new(), BTreeMap::new(), BTreeMap::new());
+    for r in receipts {
+        let tags = recipient_tags(r, accounts);
+        let keep = symbol.as_ref().is_none_or(|s| *s == r.symbol)
+            && from.is_none_or(|f| r.executed_at >= f)
+            && to.is_none_or(|t| r.executed_at < t)
+            && recipient.is_none_or(|(p, sub)| p == r.to_principal && (sub.is_none() || sub == r.to_sub))
+            && tag.as_ref().is_none_or(|t| tags.contains(&t.as_str()));
+        if !keep { continue; }
+        add(&mut totals, r.symbol.clone(), r);
+        add(&mut by_period, period_key(r.executed_at, &period), r);
+        add(&mut by_recipient, recipient_label(r, accounts), r);
+        for t in tags {
+            add(&mut by_tag, t.to_string(), r);
+        }
+    }
+
+    let top = a.top.filter(|n| *n > 0).unwrap_or(DEFAULT_TOP) as usize;
+    let mut top_recipients = groups(by_recipient);
+    top_recipients.sort_by(|x, y| y.total.cmp(&x.total).then_with(|| x.key.cmp(&y.key)));
+    let mut kept: BTreeMap<String, usize> = BTreeMap::new();
+    top_recipients.retain(|g| {
+        let n = kept.entry(g.symbol.clone()).or_default();
+        *n += 1;
+        *n <= top
+    });
+
+    let totals = groups(totals);
+    let range = match (from, to) {
+        (Some(f), Some(t)) => format!(" from {} to {}", format_date_ns(f), format_date_ns(t - 1)),
+        (Some(f), None) => format!(" since {}", format_date_ns(f)),
+        (None, Some(t)) => format!(" until {}", format_date_ns(t - 1)),
+        (None, None) => String::new(),
+    };
+    let human_readable = if totals.is_empty() {
+        format!("No transfers found{}.", range)
+    } else {
+        let sent: Vec<String> = totals.iter()
+            .map(|g| format!("{} {} in {} transfer(s)", g.total_dec, g.symbol, g.count))
+            .collect();
+        let mut s = format!("Sent {}{}.", sent.join(" and "), range);
+        if let Some(t) = top_recipients.first() {
+            s.push_str(&format!(" Top recipient: {} ({} {}).", t.key, t.total_dec, t.symbol));
+        }
+        s
+    };
+    Ok(SpendingSummary { from, to, totals, by_period: groups(by_period), top_recipients, by_tag: groups(by_tag), human_readable })
+}
+
+/// Summarize `owner`'s receipts.
+pub fn summary(owner: Principal, a: &SummaryArgs) -> Result<SpendingSummary, Value> {
+    let recipient = match a.recipient.as_deref().filter(|r| !is_placeholder(r)) {
+        Some(r) => Some(resolve_to(r.trim()).map_err(|e| json!({"status":"err","code":"BadRecipient","field":"recipient","error":e}))?),
+        None => None,
+    };
+    let receipts = RECEIPTS.with(|m| m.borrow().get(&owner).cloned()).unwrap_or_default();
+    summarize(&receipts, &accounts_for(owner), a, recipient)
+}
+
+/// Replace the tags `caller` gave `alias`; an empty list removes them.
+pub fn set_tags(caller: Principal, alias: &str, tags: Vec<String>) {
+    ACCOUNT_TAGS.with(|m| {
+        let mut m = m.borrow_mut();
+        if tags.is_empty() {
+            m.remove(&(caller, alias.to_string()));
+        } else {
+            m.insert((caller, alias.to_string()), tags);
+        }
+    });
+}
+
+/// Saved accounts carrying the tags `caller` gave them.
+pub fn accounts_for(caller: Principal) -> Vec<SavedAccount> {
+    let mut accounts: Vec<SavedAccount> = ACCOUNTS.with(|m| m.borrow().values().cloned().collect());
+    ACCOUNT_TAGS.with(|m| {
+        let m = m.borrow();
+        for a in &mut accounts {
+            a.tags = m.get(&(caller, a.alias.clone())).cloned().unwrap_or_default();
+        }
+    });
+    accounts
+}
+
+/// Normalize comma separated tags.
+pub fn parse_tags(raw: &str) -> Vec<String> {
+    let mut tags: Vec<String> = raw.split(',').map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty()).collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// `spending_summary`.
+pub struct SpendingSummaryTool;
+
+impl Tool for SpendingSummaryTool {
+    fn name(&self) -> &'static str { "spending_summary" }
+    fn description(&self) -> Option<&'static str> { Some("Totals, counts and top recipients of the user's executed transfers.") }
+    fn params(&self) -> Vec<Param> { SummaryArgs::params() }
+    fn permission(&self) -> Permission { Permission::Read }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let s = summary(ctx.caller, &parse_args(args)?)?;
+            Ok(json!({"status":"ok","summary":s}))
+        })
+    }
+}
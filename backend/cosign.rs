@@ -0,0 +1,141 @@
+use candid::{Nat, Principal};
+
+use crate::{APPROVALS, COSIGN_CONFIGS};
+use crate::types::{ApprovalRequest, CosignConfig, ExecError, TransferPlan};
+
+/// Validate a co-signer setup of `owner` before storing it.
+pub fn validate(owner: Principal, c: &CosignConfig) -> Result<(), String> {
+    if c.cosigners.is_empty() {
+        return if c.thresholds.is_empty() { Ok(()) } else { Err("co-signer list is empty".into()) };
+    }
+    if c.cosigners.contains(&owner) { return Err("owner cannot co-sign own plans".into()); }
+    let mut uniq = c.cosigners.clone();
+    uniq.sort();
+    uniq.dedup();
+    if uniq.len() != c.cosigners.len() { return Err("duplicate co-signer".into()); }
+    if c.required == 0 || c.required as usize > c.cosigners.len() {
+        return Err(format!("required must be between 1 and {}", c.cosigners.len()));
+    }
+    Ok(())
+}
+
+/// Approvals `c` demands for sending `amount` of `symbol` (0 if none).
+pub fn required_for(c: &CosignConfig, symbol: &str, amount: &Nat) -> u8 {
+    let hit = c.thresholds.iter().any(|t| t.symbol.eq_ignore_ascii_case(symbol) && *amount >= t.amount);
+    if hit && !c.cosigners.is_empty() { c.required } else { 0 }
+}
+
+/// Approvals `owner` needs for sending `amount` of `symbol` under the stored setup.
+pub fn required_for_owner(owner: Principal, symbol: &str, amount: &Nat) -> u8 {
+    COSIGN_CONFIGS.with(|m| m.borrow().get(&owner).map(|c| required_for(c, symbol, amount)).unwrap_or(0))
+}
+
+/// Store the co-signer setup of `owner`; it applies to plans created afterwards.
+pub fn set_config(owner: Principal, c: CosignConfig) -> Result<(), String> {
+    validate(owner, &c)?;
+    COSIGN_CONFIGS.with(|m| m.borrow_mut().insert(owner, c));
+    Ok(())
+}
+
+/// All co-signer setups and approval requests, as saved across upgrades.
+pub fn snapshot() -> (Vec<(Principal, CosignConfig)>, Vec<ApprovalRequest>) {
+    let configs = COSIGN_CONFIGS.with(|m| m.borrow().iter().map(|(k, v)| (*k, v.clone())).collect());
+    (configs, APPROVALS.with(|m| m.borrow().values().cloned().collect()))
+}
+
+/// Replace the co-signer setups and approval requests with a [`snapshot`].
+pub fn restore((configs, approvals): (Vec<(Principal, CosignConfig)>, Vec<ApprovalRequest>)) {
+    COSIGN_CONFIGS.with(|m| *m.borrow_mut() = configs.into_iter().collect());
+    APPROVALS.with(|m| *m.borrow_mut() = approvals.into_iter().map(|r| (r.checksum.clone(), r)).collect());
+}
+
+/// Open an approval request for `plan` if it needs co-signers.
+///
+/// Co-signers and quorum are fixed once the request is open; reopening it changes nothing.
+pub fn open(plan: &TransferPlan, expires_at: Option<u64>) {
+    if plan.approvals_required == 0 { return; }
+    if APPROVALS.with(|m| m.borrow().contains_key(&plan.checksum)) { return; }
+    let Some(owner) = plan.from_owner else { return; };
+    let cosigners = COSIGN_CONFIGS.with(|m| m.borrow().get(&owner).map(|c| c.cosigners.clone()).unwrap_or_default());
+    let req = ApprovalRequest {
+        checksum: plan.checksum.clone(),
+        plan: plan.clone(),
+        cosigners,
+        required: plan.approvals_required,
+        approved_by: vec![],
+        rejected_by: None,
+        rejection_reason: None,
+        expires_at,
+    };
+    APPROVALS.with(|m| { m.borrow_mut().insert(plan.checksum.clone(), req); });
+}
+
+/// Open the request of `plan`, which amends `old`, with the co-signers of `old`'s request.
+pub fn open_amended(old: &str, plan: &TransferPlan, expires_at: Option<u64>) {
+    open(plan, expires_at);
+    let Some(cosigners) = APPROVALS.with(|m| m.borrow().get(old).map(|r| r.cosigners.clone())) else { return; };
+    APPROVALS.with(|m| {
+        if let Some(r) = m.borrow_mut().get_mut(&plan.checksum) { r.cosigners = cosigners; }
+    });
+}
+
+/// Withdraw the approval request of a cancelled plan.
+pub fn close(checksum: &str) {
+    APPROVALS.with(|m| { m.borrow_mut().remove(checksum); });
+}
+
+/// Outcome of the approval requirement for a request.
+pub fn status(req: Option<&ApprovalRequest>, required: u8) -> Result<(), ExecError> {
+    if required == 0 { return Ok(()); }
+    let Some(req) = req else { return Err(ExecError::AwaitingApproval { approved: 0, required }); };
+    if let Some(by) = req.rejected_by {
+        return Err(ExecError::Rejected { by, reason: req.rejection_reason.clone() });
+    }
+    let approved = req.approved_by.len() as u8;
+    if approved < required.max(req.required) {
+        return Err(ExecError::AwaitingApproval { approved, required: required.max(req.required) });
+    }
+    Ok(())
+}
+
+/// Check whether the approval request stored under `checksum` allows execution.
+pub fn check(checksum: &str, required: u8) -> Result<(), ExecError> {
+    APPROVALS.with(|m| status(m.borrow().get(checksum), required))
+}
+
+/// Record the decision of `cosigner` on a request.
+pub fn decide(req: &mut ApprovalRequest, cosigner: Principal, approve: bool, reason: Option<String>, now: u64) -> Result<(), String> {
+    if !req.cosigners.contains(&cosigner) { return Err("not a co-signer of this plan".into()); }
+    if req.expires_at.is_some_and(|e| now > e) { return Err("approval request expired".into()); }
+    if req.rejected_by.is_some() { return Err("plan already rejected".into()); }
+    if req.approved_by.contains(&cosigner) { return Err("already approved".into()); }
+    if approve {
+        req.approved_by.push(cosigner);
+    } else {
+        req.rejected_by = Some(cosigner);
+        req.rejection_reason = reason;
+    }
+    Ok(())
+}
+
+/// Apply a decision of the caller to the stored request `checksum`.
+pub fn decide_stored(checksum: &str, cosigner: Principal, approve: bool, reason: Option<String>, now: u64) -> Result<ApprovalRequest, String> {
+    APPROVALS.with(|m| {
+        let mut m = m.borrow_mut();
+        let req = m.get_mut(checksum).ok_or("unknown plan")?;
+        decide(req, cosigner, approve, reason, now)?;
+        Ok(req.clone())
+    })
+}
+
+/// Open requests `cosigner` still has to decide on.
+pub fn pending_for(cosigner: Principal, now: u64) -> Vec<ApprovalRequest> {
+    APPROVALS.with(|m| {
+        m.borrow().values()
+            .filter(|r| r.cosigners.contains(&cosigner) && !r.approved_by.contains(&cosigner))
+            .filter(|r| r.rejected_by.is_none() && r.approved_by.len() < r.required as usize)
+            .filter(|r| r.expires_at.is_none_or(|e| now <= e))
+            .cloned()
+            .collect()
+    })
+}
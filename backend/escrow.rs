@@ -0,0 +1,243 @@
+use candid::{Nat, Principal};
+use futures::future::LocalBoxFuture;
+use icrc_ledger_types::icrc1::account::Account as IcrcAccount;
+use serde_json::{json, Value};
+
+use crate::{annotate_fiat, build_plan, icrc1_fee, icrc1_transfer, log, remember_plan, ESCROWS, NEXT_ESCROW_ID};
+use crate::tools::{id_arg, parse_args, Param, Permission, Tool, ToolArgs, ToolCtx};
+use crate::types::{Escrow, EscrowArgs, EscrowStatus, ExecError, PlanArgs, TransferPlan};
+use crate::utils::{
+    escrow_subaccount, format_amount, format_date_ns, is_placeholder, parse_date_ns, plan_checksum,
+    resolve_to, resolve_token, NANOS_PER_DAY,
+};
+
+/// Summary line describing an escrow.
+pub fn describe(e: &Escrow) -> String {
+    let amount = resolve_token(Some(&e.symbol), None)
+        .map(|(_, d, _)| format_amount(&e.amount, d))
+        .unwrap_or_else(|_| e.amount.to_string());
+    let status = match e.status {
+        EscrowStatus::AwaitingFunds => "awaiting funds",
+        EscrowStatus::Funded => "funded",
+        EscrowStatus::Released => "released",
+        EscrowStatus::Refunded => "refunded",
+    };
+    format!("#{} {} {} from {} to {} refundable after {} ({})", e.id, amount, e.symbol,
+        e.payer.to_text(), e.recipient.to_text(), format_date_ns(e.deadline_at), status)
+}
+
+/// Register an escrow of `payer` and build the plan that funds it from `canister`.
+pub fn create(payer: Principal, canister: Principal, a: EscrowArgs, now: u64) -> Result<(Escrow, TransferPlan), Value> {
+    let deadline_at = parse_date_ns(&a.deadline)
+        .map_err(|e| json!({"status":"err","code":"BadDate","field":"deadline","error":e,"example":"2025-12-31"}))?
+        + NANOS_PER_DAY - 1;
+    if deadline_at < now {
+        return Err(json!({"status":"err","code":"BadDate","field":"deadline","error":"deadline is in the past"}));
+    }
+    let arbiter = match a.arbiter.as_deref().filter(|s| !is_placeholder(s)) {
+        Some(s) => {
+            let (p, _) = resolve_to(s).map_err(|e| json!({"status":"err","code":"BadRecipient","field":"arbiter","error":e}))?;
+            if p == payer {
+                return Err(json!({"status":"err","code":"BadRecipient","field":"arbiter","error":"payer cannot be the arbiter"}));
+            }
+            Some(p)
+        }
+        None => None,
+    };
+    let args = PlanArgs {
+        to: a.to, amount_dec: a.amount_dec, symbol: a.symbol,
+        ledger: None, decimals: None, memo: a.memo.clone(),
+    };
+    // validates recipient, token and amount the same way a direct transfer would
+    let mut plan = build_plan(&args, payer, now)?;
+    if plan.to_principal == payer {
+        return Err(json!({"status":"err","code":"BadRecipient","field":"to","error":"cannot escrow to yourself"}));
+    }
+
+    let id = NEXT_ESCROW_ID.with(|n| { let mut n = n.borrow_mut(); *n += 1; *n });
+    let escrow = Escrow {
+        id, payer, arbiter,
+        recipient: plan.to_principal,
+        recipient_sub: plan.to_sub,
+        amount: plan.amount.clone(),
+        symbol: plan.symbol.clone(),
+        ledger: plan.ledger,
+        subaccount: escrow_subaccount(id),
+        memo: a.memo.filter(|m| !is_placeholder(m)),
+        deadline_at,
+        created_at: now,
+        status: EscrowStatus::AwaitingFunds,
+        fund_block_index: None,
+        settle_block_index: None,
+    };
+    plan.to_principal = canister;
+    plan.to_sub = Some(escrow.subaccount);
+    plan.escrow_id = Some(id);
+    plan.human_readable = format!("Lock {} {} in escrow #{} for {} until {} (ledger={}).",
+        plan.amount, plan.symbol, id, escrow.recipient.to_text(), format_date_ns(deadline_at), plan.ledger.to_text());
+    if plan.approvals_required > 0 {
+        plan.human_readable.push_str(&format!(" Needs {} co-signer approval(s).", plan.approvals_required));
+    }
+    plan.checksum = plan_checksum(&plan);
+
+    ESCROWS.with(|m| { m.borrow_mut().insert(id, escrow.clone()); });
+    log!("[escrow] created {}", describe(&escrow));
+    Ok((escrow, plan))
+}
+
+/// Escrow by id.
+pub fn get(id: u64) -> Option<Escrow> {
+    ESCROWS.with(|m| m.borrow().get(&id).cloned())
+}
+
+/// Escrows where `who` is payer, recipient or arbiter.
+pub fn list(who: Principal) -> Vec<Escrow> {
+    ESCROWS.with(|m| {
+        m.borrow().values()
+            .filter(|e| e.payer == who || e.recipient == who || e.arbiter == Some(who))
+            .cloned()
+            .collect()
+    })
+}
+
+/// All escrows and the last issued id, as saved across upgrades.
+pub fn snapshot() -> (Vec<Escrow>, u64) {
+    let escrows = ESCROWS.with(|m| m.borrow().values().cloned().collect());
+    (escrows, NEXT_ESCROW_ID.with(|n| *n.borrow()))
+}
+
+/// Replace the escrows and id counter with a [`snapshot`].
+pub fn restore((escrows, next_id): (Vec<Escrow>, u64)) {
+    ESCROWS.with(|m| *m.borrow_mut() = escrows.into_iter().map(|e| (e.id, e)).collect());
+    NEXT_ESCROW_ID.with(|n| *n.borrow_mut() = next_id);
+}
+
+/// Fail unless escrow `id` still waits for its funding transfer.
+pub fn check_awaiting(id: u64) -> Result<(), ExecError> {
+    match get(id) {
+        Some(e) if e.status == EscrowStatus::AwaitingFunds => Ok(()),
+        _ => Err(ExecError::EscrowClosed { id }),
+    }
+}
+
+/// Record the funding transfer of escrow `id`.
+pub fn mark_funded(id: u64, block_index: Nat) {
+    ESCROWS.with(|m| {
+        if let Some(e) = m.borrow_mut().get_mut(&id) {
+            e.status = EscrowStatus::Funded;
+            e.fund_block_index = Some(block_index);
+        }
+    });
+}
+
+/// Check that `caller` may release (or refund) `e` at `now`.
+pub fn authorize(e: &Escrow, caller: Principal, refund: bool, now: u64) -> Result<(), String> {
+    if e.status != EscrowStatus::Funded { return Err("escrow is not funded".into()); }
+    let is_arbiter = e.arbiter == Some(caller);
+    if caller != e.payer && !is_arbiter { return Err("only the payer or arbiter can settle this escrow".into()); }
+    if refund && !is_arbiter && now <= e.deadline_at {
+        return Err(format!("refund possible after {}", format_date_ns(e.deadline_at)));
+    }
+    Ok(())
+}
+
+/// Release escrow `id` to its recipient, or refund it to the payer.
+///
+/// The ledger fee of the outgoing transfer is taken from the locked amount.
+pub async fn settle(caller: Principal, id: u64, refund: bool, now: u64) -> Result<Escrow, String> {
+    // flip the status before awaiting so a concurrent call cannot settle twice
+    let e = ESCROWS.with(|m| {
+        let mut m = m.borrow_mut();
+        let e = m.get_mut(&id).ok_or("escrow not found")?;
+        authorize(e, caller, refund, now)?;
+        e.status = if refund { EscrowStatus::Refunded } else { EscrowStatus::Released };
+        Ok::<_, String>(e.clone())
+    })?;
+    let res = async {
+        let fee = icrc1_fee(e.ledger).await?;
+        if e.amount <= fee { return Err("amount does not cover the ledger fee".to_string()); }
+        let to = if refund {
+            IcrcAccount { owner: e.payer, subaccount: None }
+        } else {
+            IcrcAccount { owner: e.recipient, subaccount: e.recipient_sub }
+        };
+        icrc1_transfer(e.ledger, Some(e.subaccount), to, e.amount.clone() - fee, now).await
+            .map_err(|err| format!("ICRC1::{:?}", err))
+    }.await;
+    log!("[escrow] settle #{} refund={} result={:?}", id, refund, res);
+    ESCROWS.with(|m| {
+        let mut m = m.borrow_mut();
+        let e = m.get_mut(&id).ok_or("escrow not found")?;
+        match res {
+            Ok(block_index) => {
+                e.settle_block_index = Some(block_index);
+                Ok(e.clone())
+            }
+            Err(err) => {
+                e.status = EscrowStatus::Funded;
+                Err(err)
+            }
+        }
+    })
+}
+
+/// `create_escrow`: plan locking funds in escrow.
+pub struct CreateEscrowTool;
+
+impl Tool for CreateEscrowTool {
+    fn name(&self) -> &'static str { "create_escrow" }
+    fn description(&self) -> Option<&'static str> {
+        Some("Plan locking funds in escrow for a recipient until released or refunded. Returns a TransferPlan+checksum.")
+    }
+    fn params(&self) -> Vec<Param> { EscrowArgs::params() }
+    fn permission(&self) -> Permission { Permission::Plan }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let (_, mut plan) = create(ctx.caller, ic_cdk::api::id(), parse_args(args)?, ctx.now)?;
+            annotate_fiat(&mut plan).await;
+            remember_plan(ctx.caller, &plan);
+            Ok(serde_json::to_value(&plan).unwrap())
+        })
+    }
+}
+
+/// `release_escrow` or, with `refund`, `refund_escrow`.
+pub struct SettleEscrowTool {
+    pub refund: bool,
+}
+
+impl Tool for SettleEscrowTool {
+    fn name(&self) -> &'static str { if self.refund { "refund_escrow" } else { "release_escrow" } }
+    fn description(&self) -> Option<&'static str> {
+        Some(if self.refund {
+            "Refund a funded escrow to the payer (after the deadline, or any time by the arbiter)."
+        } else {
+            "Release a funded escrow to its recipient (payer or arbiter only)."
+        })
+    }
+    fn params(&self) -> Vec<Param> { vec![Param::number("id").required()] }
+    fn permission(&self) -> Permission { Permission::Execute }
+    fn confirmation(&self, args: &Value) -> Option<String> {
+        let what = id_arg(args).ok().and_then(get).map_or_else(|| "escrow".to_string(), |e| describe(&e));
+        Some(format!("{} {}", if self.refund { "Refund to the payer:" } else { "Release to the recipient:" }, what))
+    }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let e = settle(ctx.caller, id_arg(&args)?, self.refund, ctx.now).await
+                .map_err(|e| json!({"status":"err","code":"EscrowError","error":e}))?;
+            Ok(json!({"status":"ok","summary":describe(&e),"block_index": e.settle_block_index.map(|b| b.to_string())}))
+        })
+    }
+}
+
+/// `list_escrows`.
+pub struct ListEscrowsTool;
+
+impl Tool for ListEscrowsTool {
+    fn name(&self) -> &'static str { "list_escrows" }
+    fn description(&self) -> Option<&'static str> { Some("List escrows the user pays, receives or arbitrates") }
+    fn permission(&self) -> Permission { Permission::Read }
+    fn call<'a>(&'a self, _args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move { Ok(json!(list(ctx.caller).iter().map(describe).collect::<Vec<_>>())) })
+    }
+}
@@ -0,0 +1,130 @@
+use candid::{Nat, Principal};
+use futures::future::LocalBoxFuture;
+use icrc_ledger_types::icrc1::account::Account as IcrcAccount;
+use serde_json::{json, Value};
+
+use crate::CONFIG;
+use crate::tools::{parse_args, Param, Permission, Tool, ToolArgs, ToolCtx};
+use crate::types::{
+    AccountTx, GetAccountTransactionsArgs, GetTransactions, GetTransactionsErr, HistoryArgs, TransactionWithId, TxPage, TOKENS,
+};
+use crate::utils::{describe_memo, format_amount, is_placeholder, resolve_token, token_symbols};
+
+/// Page size used when the user gives none.
+pub const DEFAULT_PAGE: u32 = 10;
+/// Largest page requested from an index canister.
+pub const MAX_PAGE: u32 = 50;
+
+/// Index canister of `symbol`: the configured override, else the token registry entry.
+pub fn index_canister(symbol: &str) -> Option<Principal> {
+    CONFIG.with(|c| c.borrow().index_canisters.iter().find(|(s, _)| s == symbol).map(|(_, p)| *p))
+        .or_else(|| TOKENS.iter().find(|t| t.symbol == symbol)?.index.and_then(|i| Principal::from_text(i).ok()))
+}
+
+/// View `tx` from `owner`'s side; `None` for kinds that do not move the owner's funds.
+pub fn account_tx(owner: &IcrcAccount, tx: &TransactionWithId, decimals: u8) -> Option<AccountTx> {
+    let t = &tx.transaction;
+    let (direction, counterparty, amount, memo) = if let Some(x) = &t.transfer {
+        let dir = match (&x.from == owner, &x.to == owner) {
+            (true, true) => "self",
+            (true, false) => "out",
+            _ => "in",
+        };
+        let other = if dir == "in" { x.from } else { x.to };
+        (dir, Some(other), x.amount.clone(), x.memo.clone())
+    } else if let Some(m) = &t.mint {
+        ("in", None, m.amount.clone(), m.memo.clone())
+    } else if let Some(b) = &t.burn {
+        ("out", None, b.amount.clone(), b.memo.clone())
+    } else if let Some(a) = &t.approve {
+        ("out", Some(a.spender), a.amount.clone(), None)
+    } else {
+        return None;
+    };
+    Some(AccountTx {
+        id: tx.id.clone(),
+        kind: t.kind.clone(),
+        direction: direction.into(),
+        counterparty,
+        amount_dec: format_amount(&amount, decimals),
+        amount,
+        memo_text: memo.as_deref().map(describe_memo),
+        timestamp: t.timestamp,
+    })
+}
+
+/// Turn an index reply into a page, keeping transactions in `direction` ("in", "out" or "all").
+pub fn page(owner: &IcrcAccount, symbol: &str, decimals: u8, res: &GetTransactions, direction: &str, max_results: u32) -> TxPage {
+    let transactions = res.transactions.iter()
+        .filter_map(|tx| account_tx(owner, tx, decimals))
+        .filter(|tx| direction == "all" || tx.direction == direction || tx.direction == "self")
+        .collect();
+    // the index returns newest first; a short page or reaching the oldest id means there is no more
+    let last = res.transactions.last().map(|tx| tx.id.clone());
+    let more = res.transactions.len() as u32 >= max_results && last.is_some() && last != res.oldest_tx_id;
+    TxPage {
+        symbol: symbol.to_string(),
+        balance_dec: format_amount(&res.balance, decimals),
+        transactions,
+        next_start: last.filter(|_| more).map(|id| id.to_string()),
+    }
+}
+
+/// Parse tool arguments into the token, index request and direction filter.
+pub fn request(owner: Principal, a: &HistoryArgs) -> Result<(String, u8, GetAccountTransactionsArgs, String), Value> {
+    let symbol = a.symbol.as_deref().filter(|s| !is_placeholder(s));
+    let (_, decimals, symbol) = resolve_token(symbol, None)
+        .map_err(|_| json!({"status":"err","code":"BadToken","field":"symbol","options": token_symbols()}))?;
+    let start = match a.start.as_deref().filter(|s| !is_placeholder(s)) {
+        Some(s) => Some(s.parse::<u64>().map(Nat::from)
+            .map_err(|_| json!({"status":"err","code":"BadArgs","field":"start","error":"use next_start from the previous page"}))?),
+        None => None,
+    };
+    let direction = match a.direction.as_deref().map(|d| d.trim().to_ascii_lowercase()) {
+        None => "all".to_string(),
+        Some(d) if is_placeholder(&d) => "all".to_string(),
+        Some(d) if ["in", "out", "all"].contains(&d.as_str()) => d,
+        Some(_) => return Err(json!({"status":"err","code":"BadArgs","field":"direction","options":["in","out","all"]})),
+    };
+    let max_results = a.max_results.filter(|n| *n > 0).unwrap_or(DEFAULT_PAGE).min(MAX_PAGE);
+    let args = GetAccountTransactionsArgs {
+        account: IcrcAccount { owner, subaccount: None },
+        start,
+        max_results: Nat::from(max_results),
+    };
+    Ok((symbol, decimals, args, direction))
+}
+
+/// Fetch one page of `owner`'s ledger history from the token's index canister.
+pub async fn fetch(owner: Principal, a: &HistoryArgs) -> Result<TxPage, Value> {
+    let (symbol, decimals, args, direction) = request(owner, a)?;
+    let index = index_canister(&symbol)
+        .ok_or_else(|| json!({"status":"err","code":"NoIndex","error":format!("no index canister for {}", symbol)}))?;
+    let max_results = u32::try_from(args.max_results.0.clone()).unwrap_or(MAX_PAGE);
+    let account = args.account;
+    let res = ic_cdk::call::<(GetAccountTransactionsArgs,), (Result<GetTransactions, GetTransactionsErr>,)>(
+        index, "get_account_transactions", (args,),
+    ).await
+        .map_err(|(code, msg)| format!("index call failed: {:?} {}", code, msg))
+        .and_then(|(r,)| r.map_err(|e| e.message))
+        .map_err(|e| json!({"status":"err","code":"IndexUnavailable","error":e}))?;
+    Ok(page(&account, &symbol, decimals, &res, &direction, max_results))
+}
+
+/// `get_account_transactions`.
+pub struct AccountTransactionsTool;
+
+impl Tool for AccountTransactionsTool {
+    fn name(&self) -> &'static str { "get_account_transactions" }
+    fn description(&self) -> Option<&'static str> {
+        Some("Page through the user's ledger history (incoming and outgoing, including transfers made outside the copilot).")
+    }
+    fn params(&self) -> Vec<Param> { HistoryArgs::params() }
+    fn permission(&self) -> Permission { Permission::Read }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let p = fetch(ctx.caller, &parse_args(args)?).await?;
+            Ok(json!({"status":"ok","page":p}))
+        })
+    }
+}
@@ -0,0 +1,247 @@
+use candid::Nat;
+use serde_json::Value;
+
+use crate::oracle;
+use crate::types::{PendingPlan, ToolTrace, TransferPlan, TOKENS};
+use crate::utils::{format_amount, LANGUAGES};
+
+/// Error messages keyed by code, one per entry of [`LANGUAGES`] (en, id, ms, jv).
+const ERRORS: &[(&str, [&str; 4])] = &[
+    ("BadArgs", ["Some details are missing or invalid.", "Ada data yang kurang atau tidak valid.",
+        "Ada maklumat yang tiada atau tidak sah.", "Ana data sing kurang utawa ora valid."]),
+    ("Conflict", ["That ledger or decimals value does not match the supported token.", "Ledger atau desimal itu tidak cocok dengan token yang didukung.",
+        "Ledger atau perpuluhan itu tidak sepadan dengan token yang disokong.", "Ledger utawa desimal kuwi ora cocog karo token sing didhukung."]),
+    ("NeedRecipient", ["Who should receive it?", "Mau dikirim ke siapa?", "Hendak dihantar kepada siapa?", "Arep dikirim menyang sapa?"]),
+    ("NeedRecipients", ["Who should share it?", "Dibagi ke siapa saja?", "Hendak dibahagi kepada siapa?", "Arep dibagi menyang sapa wae?"]),
+    ("NeedAmount", ["How much?", "Berapa jumlahnya?", "Berapa jumlahnya?", "Pinten jumlahe?"]),
+    ("BadAmount", ["That amount is not valid for this token.", "Jumlah itu tidak valid untuk token ini.",
+        "Jumlah itu tidak sah untuk token ini.", "Jumlah kuwi ora valid kanggo token iki."]),
+    ("BadToken", ["That token is not supported.", "Token itu tidak didukung.", "Token itu tidak disokong.", "Token kuwi ora didhukung."]),
+    ("BadRecipient", ["I can't find that recipient; use a saved alias or a principal.",
+        "Penerima tidak ditemukan; pakai alias tersimpan atau principal.",
+        "Penerima tidak dijumpai; guna alias yang disimpan atau principal.",
+        "Panampa ora ketemu; nganggo alias sing wis disimpen utawa principal."]),
+    ("BadPrincipal", ["That principal is not valid.", "Principal itu tidak valid.", "Principal itu tidak sah.", "Principal kuwi ora valid."]),
+    ("BadSub", ["A subaccount must be 32 bytes.", "Subaccount harus 32 byte.", "Subakaun mesti 32 bait.", "Subaccount kudu 32 byte."]),
+    ("BadMemo", ["That memo is too long or not valid.", "Memo terlalu panjang atau tidak valid.",
+        "Memo terlalu panjang atau tidak sah.", "Memo kedawan utawa ora valid."]),
+    ("BadDate", ["That date is not valid; use YYYY-MM-DD.", "Tanggal tidak valid; pakai format YYYY-MM-DD.",
+        "Tarikh tidak sah; guna format YYYY-MM-DD.", "Tanggal ora valid; nganggo format YYYY-MM-DD."]),
+    ("BadFrequency", ["How often should it repeat?", "Seberapa sering diulang?", "Berapa kerap hendak diulang?", "Sepira kerepe diulang?"]),
+    ("BadSeries", ["I can't find that recurring transfer.", "Transfer berulang itu tidak ditemukan.",
+        "Pindahan berulang itu tidak dijumpai.", "Transfer rutin kuwi ora ketemu."]),
+    ("EmptySchedule", ["That schedule has no payment dates.", "Jadwal itu tidak punya tanggal pembayaran.",
+        "Jadual itu tiada tarikh bayaran.", "Jadwal kuwi ora ana tanggal bayar."]),
+    ("BadMode", ["Split evenly or by weights?", "Dibagi rata atau berdasarkan bobot?", "Bahagi sama rata atau ikut pemberat?", "Dibagi rata apa miturut bobot?"]),
+    ("BadWeights", ["The weights are not valid.", "Bobotnya tidak valid.", "Pemberatnya tidak sah.", "Bobote ora valid."]),
+    ("NothingToSend", ["There is nothing to send.", "Tidak ada yang perlu dikirim.", "Tiada apa untuk dihantar.", "Ora ana sing kudu dikirim."]),
+    ("BadSlippage", ["That slippage is not valid.", "Slippage itu tidak valid.", "Slippage itu tidak sah.", "Slippage kuwi ora valid."]),
+    ("NoDex", ["Swaps are not available yet.", "Fitur tukar belum tersedia.", "Pertukaran belum tersedia.", "Ijol token durung kasedhiya."]),
+    ("NoLiquidity", ["There is not enough liquidity for that swap.", "Likuiditas tidak cukup untuk penukaran itu.",
+        "Kecairan tidak mencukupi untuk pertukaran itu.", "Likuiditas ora cukup kanggo ijolan kuwi."]),
+    ("QuoteFailed", ["I couldn't get a price quote; try again shortly.", "Gagal mengambil kuotasi harga; coba lagi sebentar.",
+        "Gagal mendapatkan sebut harga; cuba lagi sebentar.", "Gagal njupuk kuotasi rega; jajal maneh sedhela."]),
+    ("PriceUnavailable", ["No current price is available, so I can't convert that amount.",
+        "Harga terkini tidak tersedia, jadi jumlah itu tidak bisa dikonversi.",
+        "Harga semasa tiada, jadi jumlah itu tidak dapat ditukar.",
+        "Rega saiki ora kasedhiya, dadi jumlah kuwi ora bisa diowahi."]),
+    ("LedgerUnavailable", ["The ledger is not responding; try again shortly.", "Ledger tidak merespons; coba lagi sebentar.",
+        "Ledger tidak bertindak balas; cuba lagi sebentar.", "Ledger ora nanggapi; jajal maneh sedhela."]),
+    ("NoIndex", ["History is not available for that token.", "Riwayat tidak tersedia untuk token itu.",
+        "Sejarah tidak tersedia untuk token itu.", "Riwayat ora kasedhiya kanggo token kuwi."]),
+    ("IndexUnavailable", ["The history service is not responding; try again shortly.", "Layanan riwayat tidak merespons; coba lagi sebentar.",
+        "Perkhidmatan sejarah tidak bertindak balas; cuba lagi sebentar.", "Layanan riwayat ora nanggapi; jajal maneh sedhela."]),
+    ("MissingPlan", ["There is no plan to confirm; tell me what to send first.", "Belum ada rencana untuk dikonfirmasi; sebutkan dulu apa yang mau dikirim.",
+        "Tiada pelan untuk disahkan; beritahu dahulu apa yang hendak dihantar.", "Durung ana rencana sing kudu dikonfirmasi; sebutna dhisik apa sing arep dikirim."]),
+    ("ConfirmationRequired", ["Please review the plan and confirm it yourself first.", "Periksa rencananya dan konfirmasi sendiri dulu.",
+        "Sila semak pelan itu dan sahkan sendiri dahulu.", "Priksa rencanane lan konfirmasi dhewe dhisik."]),
+    ("UnknownPlan", ["I can't find that plan.", "Rencana itu tidak ditemukan.", "Pelan itu tidak dijumpai.", "Rencana kuwi ora ketemu."]),
+    ("NotOwner", ["That plan belongs to someone else.", "Rencana itu milik orang lain.", "Pelan itu milik orang lain.", "Rencana kuwi duweke wong liya."]),
+    ("NotAmendable", ["That plan can no longer be changed.", "Rencana itu tidak bisa diubah lagi.", "Pelan itu tidak boleh diubah lagi.", "Rencana kuwi ora bisa diowahi maneh."]),
+    ("NoChange", ["Nothing changed.", "Tidak ada yang berubah.", "Tiada perubahan.", "Ora ana sing owah."]),
+    ("Cancelled", ["That plan was cancelled.", "Rencana itu sudah dibatalkan.", "Pelan itu telah dibatalkan.", "Rencana kuwi wis dibatalake."]),
+    ("Superseded", ["That plan was changed; confirm the newer one.", "Rencana itu sudah diubah; konfirmasi yang terbaru.",
+        "Pelan itu telah diubah; sahkan yang terbaru.", "Rencana kuwi wis diowahi; konfirmasi sing paling anyar."]),
+    ("Duplicate", ["That transfer was already sent.", "Transfer itu sudah dikirim.", "Pindahan itu telah dihantar.", "Transfer kuwi wis dikirim."]),
+    ("ExecError", ["The ledger rejected the transfer.", "Ledger menolak transfer.", "Ledger menolak pindahan itu.", "Ledger nolak transfer kuwi."]),
+    ("PartialExec", ["Only some of the transfers went through.", "Hanya sebagian transfer yang berhasil.",
+        "Hanya sebahagian pindahan yang berjaya.", "Mung sebagian transfer sing kasil."]),
+    ("AwaitingApproval", ["Waiting for co-signer approval.", "Menunggu persetujuan co-signer.",
+        "Menunggu kelulusan penandatangan bersama.", "Ngenteni persetujuan co-signer."]),
+    ("Rejected", ["A co-signer rejected this transfer.", "Co-signer menolak transfer ini.",
+        "Penandatangan bersama menolak pindahan ini.", "Co-signer nolak transfer iki."]),
+    ("UnknownInvoice", ["I can't find that invoice.", "Tagihan itu tidak ditemukan.", "Invois itu tidak dijumpai.", "Tagihan kuwi ora ketemu."]),
+    ("OwnInvoice", ["You can't pay your own invoice.", "Kamu tidak bisa membayar tagihan sendiri.",
+        "Anda tidak boleh membayar invois sendiri.", "Kowe ora bisa mbayar tagihanmu dhewe."]),
+    ("NotPayer", ["That invoice is addressed to someone else.", "Tagihan itu ditujukan ke orang lain.",
+        "Invois itu ditujukan kepada orang lain.", "Tagihan kuwi kanggo wong liya."]),
+    ("InvoiceClosed", ["That invoice is already paid or cancelled.", "Tagihan itu sudah dibayar atau dibatalkan.",
+        "Invois itu telah dibayar atau dibatalkan.", "Tagihan kuwi wis dibayar utawa dibatalake."]),
+    ("EscrowError", ["That escrow can't be changed now.", "Escrow itu tidak bisa diubah sekarang.",
+        "Escrow itu tidak boleh diubah sekarang.", "Escrow kuwi ora bisa diowahi saiki."]),
+    ("EscrowClosed", ["That escrow is already funded or settled.", "Escrow itu sudah didanai atau diselesaikan.",
+        "Escrow itu telah dibiayai atau diselesaikan.", "Escrow kuwi wis diisi utawa rampung."]),
+    ("SwapFailed", ["The swap failed; your funds were not exchanged.", "Penukaran gagal; dana tidak ditukar.",
+        "Pertukaran gagal; dana anda tidak ditukar.", "Ijolan gagal; dana ora diijolake."]),
+    ("RoundLimit", ["That took too many steps; please try again with a simpler request.",
+        "Terlalu banyak langkah; coba lagi dengan permintaan yang lebih sederhana.",
+        "Terlalu banyak langkah; cuba lagi dengan permintaan yang lebih mudah.",
+        "Kakehan langkah; jajal maneh nganggo panjaluk sing luwih prasaja."]),
+    ("ModelUnavailable", ["Sorry, the assistant is unavailable right now. Please try again in a moment.",
+        "Maaf, asisten sedang tidak tersedia. Coba lagi sebentar.",
+        "Maaf, pembantu tidak tersedia buat masa ini. Cuba lagi sebentar.",
+        "Nyuwun pangapunten, asisten saiki ora kasedhiya. Jajal maneh sedhela."]),
+    ("PolicyPerTxMax", ["That is above your per-transfer limit.", "Jumlah itu melebihi batas per transfer.",
+        "Jumlah itu melebihi had setiap pindahan.", "Jumlah kuwi ngluwihi wates saben transfer."]),
+    ("PolicyDailyCap", ["That would exceed your daily limit.", "Itu akan melebihi batas harian.",
+        "Itu akan melebihi had harian.", "Kuwi bakal ngluwihi wates saben dina."]),
+    ("PolicyWeeklyCap", ["That would exceed your weekly limit.", "Itu akan melebihi batas mingguan.",
+        "Itu akan melebihi had mingguan.", "Kuwi bakal ngluwihi wates saben minggu."]),
+    ("PolicyRecipientCap", ["That would exceed your limit for this recipient.", "Itu akan melebihi batas untuk penerima ini.",
+        "Itu akan melebihi had untuk penerima ini.", "Kuwi bakal ngluwihi wates kanggo panampa iki."]),
+    ("PolicyRecipientNotAllowed", ["Your policy doesn't allow sending to this recipient.", "Kebijakanmu tidak mengizinkan kirim ke penerima ini.",
+        "Polisi anda tidak membenarkan penghantaran kepada penerima ini.", "Aturanmu ora ngidinake ngirim menyang panampa iki."]),
+    ("PolicyQuietHours", ["Transfers are paused during your quiet hours.", "Transfer dijeda selama jam tenang.",
+        "Pindahan dihentikan sementara semasa waktu senyap.", "Transfer dilereni nalika jam sepi."]),
+];
+
+/// Hint labels appended to error messages.
+const EXAMPLE: [&str; 4] = ["Example", "Contoh", "Contoh", "Tuladha"];
+const OPTIONS: [&str; 4] = ["Options", "Pilihan", "Pilihan", "Pilihan"];
+const FIELDS: [&str; 4] = ["Fields", "Kolom", "Medan", "Kolom"];
+
+fn index(lang: &str) -> usize {
+    LANGUAGES.iter().position(|l| *l == lang).unwrap_or(0)
+}
+
+fn text(v: &Value) -> Option<String> {
+    match v {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Array(a) => Some(a.iter().filter_map(text).collect::<Vec<_>>().join(", ")),
+        _ => None,
+    }
+}
+
+/// Message for error `code` in `lang`, with the summary, example, options and invalid fields of `err` when present.
+pub fn error_message(code: &str, lang: &str, err: &Value) -> Option<String> {
+    let i = index(lang);
+    let mut s = ERRORS.iter().find(|(c, _)| *c == code)?.1[i].to_string();
+    if let Some(summary) = err.get("summary").and_then(text) {
+        s.push_str(&format!(" {}", summary));
+    }
+    if let Some(e) = err.get("example").and_then(text) {
+        s.push_str(&format!(" {}: {}.", EXAMPLE[i], e));
+    }
+    if let Some(o) = err.get("options").and_then(text) {
+        s.push_str(&format!(" {}: {}.", OPTIONS[i], o));
+    }
+    if let Some(Value::Array(fields)) = err.get("fields") {
+        let names: Vec<String> = fields.iter().filter_map(|f| f.get("field").and_then(text)).collect();
+        s.push_str(&format!(" {}: {}.", FIELDS[i], names.join(", ")));
+    }
+    Some(s)
+}
+
+fn decimals_of(symbol: &str) -> u8 {
+    TOKENS.iter().find(|t| t.symbol == symbol).map(|t| t.decimals).unwrap_or(0)
+}
+
+fn amount(n: &Nat, symbol: &str) -> String {
+    format!("{} {}", format_amount(n, decimals_of(symbol)), symbol)
+}
+
+fn approvals(n: u8, i: usize) -> String {
+    if n == 0 { return String::new(); }
+    [
+        format!(" Needs {} co-signer approval(s).", n),
+        format!(" Perlu {} persetujuan co-signer.", n),
+        format!(" Perlu {} kelulusan penandatangan bersama.", n),
+        format!(" Butuh {} persetujuan co-signer.", n),
+    ][i].clone()
+}
+
+fn transfer_summary(p: &TransferPlan, i: usize, now: u64) -> String {
+    let (what, to) = (amount(&p.amount, &p.symbol), p.to_principal.to_text());
+    let mut s = match (p.invoice_id, p.escrow_id) {
+        (Some(id), _) => [
+            format!("Pay invoice #{}: send {} to {}.", id, what, to),
+            format!("Bayar tagihan #{}: kirim {} ke {}.", id, what, to),
+            format!("Bayar invois #{}: hantar {} kepada {}.", id, what, to),
+            format!("Mbayar tagihan #{}: ngirim {} menyang {}.", id, what, to),
+        ][i].clone(),
+        (_, Some(id)) => [
+            format!("Lock {} in escrow #{}.", what, id),
+            format!("Kunci {} di escrow #{}.", what, id),
+            format!("Kunci {} dalam escrow #{}.", what, id),
+            format!("Ngunci {} ing escrow #{}.", what, id),
+        ][i].clone(),
+        _ => [
+            format!("Send {} to {}.", what, to),
+            format!("Kirim {} ke {}.", what, to),
+            format!("Hantar {} kepada {}.", what, to),
+            format!("Ngirim {} menyang {}.", what, to),
+        ][i].clone(),
+    };
+    if let Some(fiat) = oracle::annotation_cached(&p.symbol, &p.amount, decimals_of(&p.symbol), now) {
+        s.push_str(&format!(" ({})", fiat));
+    }
+    s + &approvals(p.approvals_required, i)
+}
+
+/// One-line summary of `plan` in `lang`.
+pub fn plan_summary(plan: &PendingPlan, lang: &str, now: u64) -> String {
+    let i = index(lang);
+    match plan {
+        PendingPlan::Transfer(p) => transfer_summary(p, i, now),
+        PendingPlan::Batch(b) => {
+            let legs: Vec<String> = b.legs.iter()
+                .map(|l| format!("{} → {}", amount(&l.amount, &l.symbol), l.to_principal.to_text()))
+                .collect();
+            let total = amount(&b.total, &b.symbol);
+            let verb = ["Split", "Bagi", "Bahagi", "Dibagi"][i];
+            format!("{} {}: {}.", verb, total, legs.join(", "))
+                + &approvals(b.legs.iter().map(|l| l.approvals_required).max().unwrap_or(0), i)
+        }
+        PendingPlan::Swap(s) => {
+            let (sell, out, min) = (amount(&s.deposit.amount, &s.deposit.symbol),
+                amount(&s.expected_out, &s.symbol_out), amount(&s.min_out, &s.symbol_out));
+            [
+                format!("Swap {} for ~{} (min {}).", sell, out, min),
+                format!("Tukar {} menjadi ~{} (min {}).", sell, out, min),
+                format!("Tukar {} kepada ~{} (min {}).", sell, out, min),
+                format!("Ijol {} dadi ~{} (min {}).", sell, out, min),
+            ][i].clone() + &approvals(s.deposit.approvals_required, i)
+        }
+    }
+}
+
+/// Trace entry of a tool call from its raw result.
+pub fn tool_trace(name: &str, result: &str) -> ToolTrace {
+    let v: Value = serde_json::from_str(result).unwrap_or_default();
+    let failed = v.get("status").and_then(Value::as_str) == Some("err");
+    ToolTrace {
+        name: name.to_string(),
+        status: if failed { "err" } else { "ok" }.to_string(),
+        code: v.get("code").and_then(Value::as_str).filter(|_| failed).map(str::to_string),
+    }
+}
+
+/// Add a `message` in `lang` to a tool result: the catalog text of its error code, or the
+/// summary of `pending` when the result is that plan.
+pub fn localize(result: &str, lang: &str, pending: Option<&PendingPlan>, now: u64) -> String {
+    let Ok(mut v) = serde_json::from_str::<Value>(result) else { return result.to_string() };
+    let Some(obj) = v.as_object() else { return result.to_string() };
+    let message = match (obj.get("code").and_then(Value::as_str), obj.get("checksum").and_then(Value::as_str), pending) {
+        (Some(code), _, _) if obj.get("status").and_then(Value::as_str) == Some("err") => error_message(code, lang, &v),
+        (_, Some(cs), Some(p)) if p.checksum() == cs => Some(plan_summary(p, lang, now)),
+        _ => None,
+    };
+    match message {
+        Some(m) => {
+            v["message"] = Value::String(m);
+            v.to_string()
+        }
+        None => result.to_string(),
+    }
+}
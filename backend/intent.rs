@@ -0,0 +1,131 @@
+use ic_llm::ToolCall;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::sync::LazyLock;
+
+use crate::{handle_tool_call, i18n, log, LAST_PLAN_BY_CALLER};
+use crate::utils::LANGUAGES;
+use crate::types::{PlanArgs, TOKENS};
+
+/// "send 10 CFXN to alice", "kirim 0,5 ICP ke bob memo makan siang".
+static TRANSFER_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+    r"(?ix)^\s*(?:please\s+|tolong\s+|mohon\s+)?
+    (?:send|transfer|pay|kirim(?:kan)?|transfer(?:kan)?|bayar(?:kan)?)\s+
+    (?P<amount>\d+(?:[.,]\d+)?)\s*
+    (?P<symbol>[a-z]{2,10})?\s+
+    (?:to|ke|kepada)\s+
+    (?P<to>[a-z0-9_-]+)
+    (?:\s+(?:memo|note|catatan|untuk|for)\s+(?P<memo>.+?))?
+    \s*[.!]?\s*$",
+).expect("valid transfer regex"));
+
+/// Amounts like "1.000" or "2,500" where the separator may group thousands.
+static AMBIGUOUS_AMOUNT_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d{1,3}[.,]\d{3}$").expect("valid amount regex"));
+
+/// "confirm", "yes", "lanjut", "ya", "gas", optionally followed by a checksum prefix ("confirm 3fa9c2e1").
+static CONFIRM_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(
+    r"(?i)^\s*(?:confirm|confirmed|yes|yep|ok|okay|proceed|go\s+ahead|do\s+it|ya|iya|oke|lanjut|lanjutkan|gas|setuju|kirim\s+sekarang)(?:\s+(?P<checksum>[0-9a-f]{8,64}))?\s*[.!]*\s*$",
+).expect("valid confirm regex"));
+
+/// Command recognized without the model.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Intent {
+    /// Plan a single transfer.
+    Transfer(PlanArgs),
+    /// Confirm the pending plan.
+    Confirm,
+}
+
+/// Parse a simple English or Indonesian command; anything else is left to the model.
+pub fn parse(text: &str) -> Option<Intent> {
+    if CONFIRM_RE.is_match(text) {
+        return Some(Intent::Confirm);
+    }
+    let c = TRANSFER_RE.captures(text)?;
+    // "1.000" is one thousand in Indonesian but one in English; let the model ask
+    if AMBIGUOUS_AMOUNT_RE.is_match(&c["amount"]) {
+        return None;
+    }
+    // only allowlisted symbols; "send 10 bucks to bob" and "send 10 to bob" go to the model, which asks
+    let s = c.name("symbol")?.as_str();
+    let symbol = Some(TOKENS.iter().find(|t| t.symbol.eq_ignore_ascii_case(s))?.symbol.to_string());
+    Some(Intent::Transfer(PlanArgs {
+        to: c["to"].to_string(),
+        // Indonesian decimal comma
+        amount_dec: c["amount"].replace(',', "."),
+        symbol,
+        ledger: None,
+        decimals: None,
+        memo: c.name("memo").map(|m| m.as_str().trim().to_string()),
+    }))
+}
+
+/// Whether `text` explicitly confirms the plan with `checksum`; a checksum in the text must match it.
+///
+/// A bare "yes" only counts when the plan was `just_shown` in the previous assistant turn.
+pub fn confirms(text: &str, checksum: &str, just_shown: bool) -> bool {
+    CONFIRM_RE.captures(text).is_some_and(|c| match c.name("checksum") {
+        Some(m) => checksum.starts_with(&m.as_str().to_ascii_lowercase()),
+        None => just_shown,
+    })
+}
+
+fn tool_call(name: &str, args: &[(&str, String)]) -> ToolCall {
+    let arguments: Vec<Value> = args.iter().map(|(n, v)| json!({"name": n, "value": v})).collect();
+    serde_json::from_value(json!({"id": format!("intent-{}", name), "function": {"name": name, "arguments": arguments}}))
+        .expect("valid tool call")
+}
+
+/// Reply to a confirmation result.
+fn outcome(result: &Value, lang: &str) -> String {
+    if result["status"] == "ok" {
+        let detail = result["block_index"].as_str().map(|b| format!(" Block index {}.", b))
+            .or_else(|| result["amount_out"].as_str().map(|a| format!(" {} {}.", a, result["symbol_out"].as_str().unwrap_or(""))))
+            .unwrap_or_default();
+        let sent = match lang { "id" => "Terkirim.", "ms" => "Telah dihantar.", "jv" => "Wis dikirim.", _ => "Sent." };
+        return format!("{}{}", sent, detail);
+    }
+    let code = result["code"].as_str().unwrap_or("ExecError");
+    i18n::error_message(code, lang, result).unwrap_or_else(|| i18n::error_message("ExecError", lang, result).unwrap_or_default())
+}
+
+/// Prompt appended to a plan summary.
+fn confirm_prompt(lang: &str) -> &'static str {
+    const PROMPTS: [&str; 4] = [
+        "Reply \"confirm\" to send it.",
+        "Balas \"lanjut\" untuk mengirim.",
+        "Balas \"ya\" untuk menghantar.",
+        "Bales \"ya\" kanggo ngirim.",
+    ];
+    PROMPTS[LANGUAGES.iter().position(|l| *l == lang).unwrap_or(0)]
+}
+
+/// Handle `text` without the model when it is a simple command; `None` hands it to the model.
+///
+/// `confirmed` is the checksum this message confirms, if any.
+pub(crate) async fn handle(caller: candid::Principal, text: &str, lang: &str, confirmed: Option<&str>) -> Option<String> {
+    match parse(text)? {
+        Intent::Confirm => {
+            // a bare "yes" with nothing pending is ordinary conversation; pending tool calls are re-issued by the model
+            let pending = LAST_PLAN_BY_CALLER.with(|m| m.borrow().get(&caller).map(|p| p.checksum().to_string()))?;
+            if confirmed != Some(pending.as_str()) {
+                return None;
+            }
+            log!("[intent] confirm");
+            let (_, result) = handle_tool_call(&tool_call("confirm_transfer", &[]), Some(&pending)).await;
+            Some(outcome(&serde_json::from_str(&result).unwrap_or_default(), lang))
+        }
+        Intent::Transfer(a) => {
+            log!("[intent] transfer {} {:?} to {}", a.amount_dec, a.symbol, a.to);
+            let mut args = vec![("to", a.to), ("amount_dec", a.amount_dec)];
+            args.extend(a.symbol.map(|s| ("symbol", s)));
+            args.extend(a.memo.map(|m| ("memo", m)));
+            let (_, result) = handle_tool_call(&tool_call("plan_transfer", &args), None).await;
+            let plan: Value = serde_json::from_str(&result).unwrap_or_default();
+            // errors such as an unknown alias are better explained by the model
+            plan["checksum"].as_str()?;
+            let pending = LAST_PLAN_BY_CALLER.with(|m| m.borrow().get(&caller).cloned())?;
+            Some(format!("{} {}", i18n::plan_summary(&pending, lang, ic_cdk::api::time()), confirm_prompt(lang)))
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use candid::{Nat, Principal};
+use futures::future::LocalBoxFuture;
+use serde_json::{json, Value};
+
+use crate::{annotate_fiat, build_plan, log, remember_plan, INVOICES, NEXT_INVOICE_ID};
+use crate::tools::{id_arg, parse_args, Param, Permission, Tool, ToolArgs, ToolCtx};
+use crate::types::{ExecError, Invoice, InvoiceArgs, InvoiceStatus, PlanArgs, TransferPlan};
+use crate::utils::{
+    example_for_decimals, format_amount, format_date_ns, is_placeholder, parse_date_ns, plan_checksum, plan_memo,
+    resolve_to, resolve_token, scale_amount, token_symbols, NANOS_PER_DAY,
+};
+
+/// Summary line describing an invoice.
+pub fn describe(inv: &Invoice) -> String {
+    let amount = resolve_token(Some(&inv.symbol), None)
+        .map(|(_, d, _)| format_amount(&inv.amount, d))
+        .unwrap_or_else(|_| inv.amount.to_string());
+    let payer = inv.payer.map(|p| p.to_text()).unwrap_or_else(|| "anyone".into());
+    let status = match inv.status {
+        InvoiceStatus::Open => "open",
+        InvoiceStatus::Paid => "paid",
+        InvoiceStatus::Cancelled => "cancelled",
+    };
+    let mut s = format!("#{} {} {} to {} from {}", inv.id, amount, inv.symbol, inv.creator.to_text(), payer);
+    if let Some(m) = &inv.memo { s.push_str(&format!(" for \"{}\"", m)); }
+    if let Some(d) = inv.due_at { s.push_str(&format!(" due {}", format_date_ns(d))); }
+    s.push_str(&format!(" ({})", status));
+    s
+}
+
+/// Validate the arguments and register a new open invoice of `creator`.
+pub fn create(creator: Principal, a: InvoiceArgs, now: u64) -> Result<Invoice, Value> {
+    if is_placeholder(&a.amount_dec) {
+        return Err(json!({"status":"err","code":"NeedAmount","field":"amount_dec","example":"10"}));
+    }
+    let (ledger, decimals, symbol) = resolve_token(a.symbol.as_deref(), None)
+        .map_err(|_| json!({"status":"err","code":"BadToken","field":"symbol","options": token_symbols()}))?;
+    let amount = scale_amount(&a.amount_dec, decimals).map_err(|e| json!({
+        "status":"err","code":"BadAmount","field":"amount_dec","error":e,"example": example_for_decimals(decimals)
+    }))?;
+    if amount == 0u32 {
+        return Err(json!({"status":"err","code":"BadAmount","field":"amount_dec","error":"amount must be positive"}));
+    }
+    let due_at = match a.due_date.as_deref().filter(|d| !is_placeholder(d)) {
+        Some(d) => {
+            let due = parse_date_ns(d)
+                .map_err(|e| json!({"status":"err","code":"BadDate","field":"due_date","error":e,"example":"2025-12-31"}))?
+                + NANOS_PER_DAY - 1;
+            if due < now {
+                return Err(json!({"status":"err","code":"BadDate","field":"due_date","error":"due date is in the past"}));
+            }
+            Some(due)
+        }
+        None => None,
+    };
+    let payer = match a.payer.as_deref().filter(|p| !is_placeholder(p)) {
+        Some(p) => {
+            let (p, _) = resolve_to(p).map_err(|e| json!({"status":"err","code":"BadRecipient","field":"payer","error":e}))?;
+            if p == creator {
+                return Err(json!({"status":"err","code":"BadRecipient","field":"payer","error":"cannot invoice yourself"}));
+            }
+            Some(p)
+        }
+        None => None,
+    };
+
+    let memo = a.memo.filter(|m| !is_placeholder(m));
+    // the invoice id and payer tag share the ledger memo with this text
+    plan_memo(memo.as_deref(), Some(u64::MAX), creator, &symbol)?;
+
+    let id = NEXT_INVOICE_ID.with(|n| { let mut n = n.borrow_mut(); *n += 1; *n });
+    let inv = Invoice {
+        id, creator, payer, amount, symbol, ledger,
+        memo,
+        due_at,
+        created_at: now,
+        status: InvoiceStatus::Open,
+        paid_by: None,
+        paid_block_index: None,
+        paid_at: None,
+    };
+    INVOICES.with(|m| { m.borrow_mut().insert(id, inv.clone()); });
+    log!("[invoice] created {}", describe(&inv));
+    Ok(inv)
+}
+
+/// Invoice by id.
+pub fn get(id: u64) -> Option<Invoice> {
+    INVOICES.with(|m| m.borrow().get(&id).cloned())
+}
+
+/// All invoices and the last issued id, as saved across upgrades.
+pub fn snapshot() -> (Vec<Invoice>, u64) {
+    let invoices = INVOICES.with(|m| m.borrow().values().cloned().collect());
+    (invoices, NEXT_INVOICE_ID.with(|n| *n.borrow()))
+}
+
+/// Replace the invoices and id counter with a [`snapshot`].
+pub fn restore((invoices, next_id): (Vec<Invoice>, u64)) {
+    INVOICES.with(|m| *m.borrow_mut() = invoices.into_iter().map(|i| (i.id, i)).collect());
+    NEXT_INVOICE_ID.with(|n| *n.borrow_mut() = next_id);
+}
+
+/// Invoices `who` created, is asked to pay, or paid.
+pub fn list(who: Principal) -> Vec<Invoice> {
+    INVOICES.with(|m| {
+        m.borrow().values()
+            .filter(|i| i.creator == who || i.payer == Some(who) || i.paid_by == Some(who))
+            .cloned()
+            .collect()
+    })
+}
+
+/// Build a transfer plan letting `payer` settle invoice `id`.
+pub fn plan_payment(payer: Principal, id: u64, created: u64) -> Result<TransferPlan, Value> {
+    let inv = get(id).ok_or_else(|| json!({"status":"err","code":"UnknownInvoice","field":"id"}))?;
+    if inv.status != InvoiceStatus::Open {
+        return Err(json!({"status":"err","code":"InvoiceClosed","error": describe(&inv)}));
+    }
+    if inv.creator == payer {
+        return Err(json!({"status":"err","code":"OwnInvoice","error":"you created this invoice"}));
+    }
+    if inv.payer.is_some_and(|p| p != payer) {
+        return Err(json!({"status":"err","code":"NotPayer","error":"invoice is addressed to someone else"}));
+    }
+    let (_, decimals, _) = resolve_token(Some(&inv.symbol), None)
+        .map_err(|_| json!({"status":"err","code":"BadToken","options": token_symbols()}))?;
+    let args = PlanArgs {
+        to: inv.creator.to_text(),
+        amount_dec: format_amount(&inv.amount, decimals),
+        symbol: Some(inv.symbol.clone()),
+        ledger: None,
+        decimals: None,
+        memo: None,
+    };
+    let mut plan = build_plan(&args, payer, created)?;
+    plan.invoice_id = Some(id);
+    plan.memo = plan_memo(inv.memo.as_deref(), Some(id), payer, &inv.symbol)?;
+    plan.human_readable = format!("Pay invoice #{}: {}", id, plan.human_readable);
+    plan.checksum = plan_checksum(&plan);
+    Ok(plan)
+}
+
+/// Fail unless invoice `id` can still be paid.
+pub fn check_open(id: u64) -> Result<(), ExecError> {
+    match get(id) {
+        Some(i) if i.status == InvoiceStatus::Open => Ok(()),
+        _ => Err(ExecError::InvoiceClosed { id }),
+    }
+}
+
+/// Mark open invoice `id` as paid before its transfer is awaited, so a concurrent payment fails.
+///
+/// [`reopen`] undoes this when the transfer fails; [`mark_paid`] records it when it succeeds.
+pub fn claim(id: u64) -> Result<(), ExecError> {
+    INVOICES.with(|m| {
+        match m.borrow_mut().get_mut(&id) {
+            Some(i) if i.status == InvoiceStatus::Open => {
+                i.status = InvoiceStatus::Paid;
+                Ok(())
+            }
+            _ => Err(ExecError::InvoiceClosed { id }),
+        }
+    })
+}
+
+/// Reopen invoice `id` after the transfer paying it failed.
+pub fn reopen(id: u64) {
+    INVOICES.with(|m| {
+        if let Some(i) = m.borrow_mut().get_mut(&id).filter(|i| i.paid_block_index.is_none()) {
+            i.status = InvoiceStatus::Open;
+        }
+    });
+}
+
+/// Record the settlement of invoice `id`.
+pub fn mark_paid(id: u64, payer: Principal, block_index: Nat, now: u64) {
+    INVOICES.with(|m| {
+        if let Some(i) = m.borrow_mut().get_mut(&id) {
+            i.status = InvoiceStatus::Paid;
+            i.paid_by = Some(payer);
+            i.paid_block_index = Some(block_index);
+            i.paid_at = Some(now);
+        }
+    });
+}
+
+/// Cancel an open invoice created by `creator`.
+pub fn cancel(creator: Principal, id: u64) -> Result<Invoice, String> {
+    INVOICES.with(|m| {
+        let mut m = m.borrow_mut();
+        let i = m.get_mut(&id).filter(|i| i.creator == creator).ok_or("invoice not found")?;
+        if i.status != InvoiceStatus::Open { return Err("invoice is not open".into()); }
+        i.status = InvoiceStatus::Cancelled;
+        Ok(i.clone())
+    })
+}
+
+/// `create_invoice`: request a payment.
+pub struct CreateInvoiceTool;
+
+impl Tool for CreateInvoiceTool {
+    fn name(&self) -> &'static str { "create_invoice" }
+    fn description(&self) -> Option<&'static str> { Some("Request a payment from someone; returns the invoice id to share.") }
+    fn params(&self) -> Vec<Param> { InvoiceArgs::params() }
+    fn permission(&self) -> Permission { Permission::Write }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let i = create(ctx.caller, parse_args(args)?, ctx.now)?;
+            Ok(json!({"status":"ok","id":i.id,"summary":describe(&i)}))
+        })
+    }
+}
+
+/// `pay_invoice`: plan the payment of an invoice.
+pub struct PayInvoiceTool;
+
+impl Tool for PayInvoiceTool {
+    fn name(&self) -> &'static str { "pay_invoice" }
+    fn description(&self) -> Option<&'static str> { Some("Plan the payment of an invoice by id. Returns a TransferPlan+checksum.") }
+    fn params(&self) -> Vec<Param> { vec![Param::number("id").required()] }
+    fn permission(&self) -> Permission { Permission::Plan }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let id = id_arg(&args)?;
+            let mut plan = plan_payment(ctx.caller, id, ctx.now)?;
+            annotate_fiat(&mut plan).await;
+            log!("[pay_invoice] invoice={} checksum={}", id, plan.checksum);
+            remember_plan(ctx.caller, &plan);
+            Ok(serde_json::to_value(&plan).unwrap())
+        })
+    }
+}
+
+/// `list_invoices`.
+pub struct ListInvoicesTool;
+
+impl Tool for ListInvoicesTool {
+    fn name(&self) -> &'static str { "list_invoices" }
+    fn description(&self) -> Option<&'static str> { Some("List invoices the user created, owes or paid") }
+    fn permission(&self) -> Permission { Permission::Read }
+    fn call<'a>(&'a self, _args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move { Ok(json!(list(ctx.caller).iter().map(describe).collect::<Vec<_>>())) })
+    }
+}
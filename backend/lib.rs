@@ -1,27 +1,56 @@
 use candid::{Nat, Principal};
-use ic_cdk::{query, update};
-use ic_llm::{Model, ChatMessage, ToolCall, ParameterType};
+use ic_cdk::{init, post_upgrade, pre_upgrade, query, update};
+use ic_llm::{AssistantMessage, ChatMessage, ToolCall};
 
 use icrc_ledger_types::icrc1::{
     account::Account as IcrcAccount,
-    transfer::Memo,
+    transfer::{Memo, TransferArg, TransferError},
 };
+use icrc_ledger_types::icrc2::allowance::{Allowance, AllowanceArgs};
 use icrc_ledger_types::icrc2::transfer_from::{TransferFromArgs, TransferFromError};
 
-use serde::Deserialize;
-use serde_json::json;
+use serde_json::{json, Value};
 use std::cell::RefCell;
 use std::collections::{BTreeMap, BTreeSet};
 use ic_cdk::println;
+use ic_cdk_timers::TimerId;
+use futures::future::LocalBoxFuture;
 
 pub mod types;
 pub mod utils;
+pub mod recurring;
+pub mod policy;
+pub mod cosign;
+pub mod split;
+pub mod invoice;
+pub mod escrow;
+pub mod amend;
+pub mod simulate;
+pub mod allowance;
+pub mod swap;
+pub mod oracle;
+pub mod portfolio;
+pub mod history;
+pub mod analytics;
+pub mod llm;
+pub mod i18n;
+pub mod intent;
+pub mod session;
+pub mod tools;
 
-use types::{PlanArgs, TransferPlan, SavedAccount};
+use types::{
+    PlanArgs, SaveAccountArgs, PendingAction, TransferPlan, SavedAccount, ExecError, RecurringArgs, RecurringTransfer,
+    Receipt, SpendingPolicy, CosignConfig, ApprovalRequest, BatchPlan, PendingPlan,
+    Invoice, InvoiceArgs, Escrow, SimulationReport,
+    ApprovalPrepArgs, PreparedApproval, SwapPlan, Config, Price, TOKENS, Portfolio,
+    HistoryArgs, TxPage, SummaryArgs, SpendingSummary, LlmModel, InitArgs, ModelTrace, Session, ChatResponse, ChatStatus,
+    StableState,
+};
+use tools::{parse_args, Param, Permission, Registry, Tool, ToolArgs, ToolCtx};
 use utils::{
-    token_symbols, resolve_to, decode_plan_value, resolve_token, example_for_decimals,
-    scale_amount, plan_checksum, is_placeholder, tool_args_json,
-    cap_messages_in_place, detect_lang_last_user, lang_guard,
+    resolve_to, decode_plan_value, resolve_token, resolve_plan_token, example_for_decimals,
+    scale_amount, plan_checksum, is_placeholder, PLAN_TTL_NS,
+    trim_to_token_budget, pending_plan_note, MAX_CONTEXT_TOKENS, detect_lang_last_user, set_lang_guard, normalize_lang, plan_memo, describe_memo, format_amount,
 };
 
 #[macro_export]
@@ -30,10 +59,42 @@ macro_rules! log {
 }
 
 thread_local! {
-    pub(crate) static ACCOUNTS: RefCell<BTreeMap<String, SavedAccount>> = RefCell::new(BTreeMap::new());
-    pub(crate) static LAST_PLAN_BY_CALLER: RefCell<BTreeMap<Principal, TransferPlan>> = RefCell::new(BTreeMap::new());
-    pub(crate) static PLAN_BY_CHECKSUM:    RefCell<BTreeMap<String, TransferPlan>>    = RefCell::new(BTreeMap::new());
-    pub(crate) static EXECUTED_CHECKSUMS:  RefCell<BTreeSet<String>>                  = RefCell::new(BTreeSet::new());
+    pub(crate) static ACCOUNTS: RefCell<BTreeMap<String, SavedAccount>> = const { RefCell::new(BTreeMap::new()) };
+    /// Tags each caller gave a saved account, keyed by (caller, alias).
+    pub(crate) static ACCOUNT_TAGS: RefCell<BTreeMap<(Principal, String), Vec<String>>> = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static LAST_PLAN_BY_CALLER: RefCell<BTreeMap<Principal, PendingPlan>>  = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static PLAN_BY_CHECKSUM:    RefCell<BTreeMap<String, TransferPlan>>    = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static BATCH_BY_CHECKSUM:   RefCell<BTreeMap<String, BatchPlan>>       = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static SWAP_BY_CHECKSUM:    RefCell<BTreeMap<String, SwapPlan>>        = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static EXECUTED_CHECKSUMS:  RefCell<BTreeSet<String>>                  = const { RefCell::new(BTreeSet::new()) };
+    pub(crate) static SUPERSEDED_BY:       RefCell<BTreeMap<String, String>>          = const { RefCell::new(BTreeMap::new()) };
+    /// Checksums of cancelled plans and their legs; they can no longer execute.
+    pub(crate) static CANCELLED_CHECKSUMS: RefCell<BTreeSet<String>>                  = const { RefCell::new(BTreeSet::new()) };
+    /// Fund-moving tool call of each caller waiting for confirmation.
+    pub(crate) static PENDING_ACTIONS:     RefCell<BTreeMap<Principal, PendingAction>> = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static RECURRING:           RefCell<BTreeMap<u64, RecurringTransfer>>  = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static RECURRING_TIMERS:    RefCell<BTreeMap<u64, TimerId>>            = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static NEXT_RECURRING_ID:   RefCell<u64>                               = const { RefCell::new(0) };
+    pub(crate) static RECEIPTS:            RefCell<BTreeMap<Principal, Vec<Receipt>>> = const { RefCell::new(BTreeMap::new()) };
+    /// Transfers awaiting their ledger reply by checksum; they count toward spending caps.
+    pub(crate) static PENDING_SPENDS:      RefCell<BTreeMap<String, Receipt>>         = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static POLICIES:            RefCell<BTreeMap<Principal, SpendingPolicy>> = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static COSIGN_CONFIGS:      RefCell<BTreeMap<Principal, CosignConfig>> = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static APPROVALS:           RefCell<BTreeMap<String, ApprovalRequest>> = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static INVOICES:            RefCell<BTreeMap<u64, Invoice>>            = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static NEXT_INVOICE_ID:     RefCell<u64>                               = const { RefCell::new(0) };
+    pub(crate) static ESCROWS:             RefCell<BTreeMap<u64, Escrow>>             = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static NEXT_ESCROW_ID:      RefCell<u64>                               = const { RefCell::new(0) };
+    pub(crate) static CONFIG:              RefCell<Config>                            = const { RefCell::new(Config::new()) };
+    /// Models used by each caller's latest chat, one entry per LLM round.
+    pub(crate) static CHAT_TRACES:         RefCell<BTreeMap<Principal, Vec<ModelTrace>>> = const { RefCell::new(BTreeMap::new()) };
+    /// Server-side conversations by session id.
+    pub(crate) static SESSIONS:            RefCell<BTreeMap<u64, Session>>            = const { RefCell::new(BTreeMap::new()) };
+    pub(crate) static NEXT_SESSION_ID:     RefCell<u64>                               = const { RefCell::new(0) };
+    /// Reply language chosen by each user; absent means detect it per message.
+    pub(crate) static LANG_PREFS:          RefCell<BTreeMap<Principal, String>>       = const { RefCell::new(BTreeMap::new()) };
+    /// Prices keyed by (symbol, currency) with the time they were fetched.
+    pub(crate) static PRICE_CACHE:         RefCell<BTreeMap<(String, String), (Price, u64)>> = const { RefCell::new(BTreeMap::new()) };
 }
 
 /// System prompt provided to the language model.
@@ -41,7 +102,7 @@ const SYSTEM_PROMPT: &str = r#"
 You are a finance copilot for ICRC tokens on the Internet Computer.
 
 LANGUAGE
-- Reply ONLY in the user's last language (Indonesian or English). Do not mix languages or add translations.
+- Reply ONLY in the language of the LANGUAGE/BAHASA/BASA note (English, Indonesian, Malay or Javanese). Do not mix languages or add translations.
 
 STYLE
 - Be brief (1–2 sentences per step).
@@ -58,12 +119,45 @@ SLOT FILLING
 AMOUNT
 - Accept inputs like “10 CFXN” or “0.5 ICP” and extract the number as amount_dec.
 - If a tool returns BadAmount with an example, use that example next time.
+- Memos must be short (about 20 characters). On BadMemo, ask for a shorter memo within max_text_bytes.
 
 TOOL CALLING (STRICT)
 - Never narrate tool calls or print example JSON. When ready, CALL the tool via tool_calls.
-- Call plan_transfer once recipient & amount are known. Params: to, amount_dec, memo (optional). symbol/ledger/decimals optional (backend overrides).
-- After plan_transfer: show one-line summary (human_readable) and ask explicit confirmation (“confirm” / “lanjut” / “ya”).
-- On confirmation: CALL confirm_transfer. If plan object is missing, you may call with only checksum OR with no parameters; backend uses the last plan.
+- Call plan_transfer once recipient & amount are known. Params: to, amount_dec, memo (optional). symbol optional; leave ledger/decimals out (backend takes them from the allowlist). On Conflict, drop the conflicting field and call again.
+- After plan_transfer: show the one-line summary (message) and ask explicit confirmation (“confirm” / “lanjut” / “ya”).
+- Tool results may carry a `message` already in the user's language: relay it verbatim instead of translating error codes or human_readable.
+- On confirmation: CALL confirm_transfer. It only runs after the user's own message confirms a plan you already showed; never call it in the turn that created the plan. On ConfirmationRequired, show the plan and ask. If plan object is missing, you may call with only checksum OR with no parameters; backend uses the last plan.
+- You may CALL simulate_transfer before asking confirmation; if any check fails, show the failing checks in one line instead of asking to confirm.
+- If the user changes a detail before confirming ("actually make it 15"), CALL amend_plan with only the changed fields, then show the new summary and ask again.
+
+ALLOWANCE (ICRC-2)
+- Transfers are pulled with icrc2_transfer_from, so the user must first approve this canister in their wallet: approve → plan → confirm.
+- If simulate_transfer fails on allowance, or confirm_transfer returns InsufficientAllowance, CALL prepare_approval (with the plan checksum, or budget_dec+symbol) and tell the user to sign the approval in the app, then confirm again.
+- If prepare_approval says sufficient=true, no new approval is needed.
+
+SWAPS
+- For "swap 10 ICP to CFXN": CALL plan_swap with amount_in_dec, symbol_in, symbol_out and slippage_pct if the user set one.
+- Show expected output, minimum output, slippage and price impact in one line; warn if the summary says high price impact. Then ask confirmation; confirm_transfer executes the swap.
+- On SwapFailed, say whether the detail reports the deposit refunded; if the refund failed, tell the user the input is still held by the DEX. Offer to re-plan with a fresh quote.
+
+BALANCES
+- For "what's my balance?" / "saldo saya berapa?": CALL get_portfolio and reply with its summary. Tokens marked unavailable could not be queried right now; say so briefly.
+
+SPENDING
+- For "how much did I send to vendors in September?": CALL spending_summary with the filters the user gave (tag "vendor", from/to "2026-09"; use the current year unless stated). Answer only from its totals; never add amounts yourself.
+- Recipients get tags when saved (save_account with tags "vendor"); mention that if a tag filter finds nothing.
+
+HISTORY
+- For "did I receive anything?" / "riwayat transaksi": CALL get_account_transactions (direction "in" for incoming, "out" for outgoing). It reads the ledger, so it also shows transfers made outside the copilot.
+- List at most a few lines (date, in/out, amount, counterparty). If next_start is set and the user wants more, CALL again with start = next_start. On NoIndex, say history is not available for that token.
+
+FIAT
+- For "send Rp 50.000 worth of ICP to alice": CALL plan_transfer with amount_dec "Rp 50.000" (keep the currency; IDR and USD are supported) and the token symbol; the backend converts it at the oracle price.
+- Summaries may end with "≈ Rp … / $…"; repeat that value to the user. On PriceUnavailable, ask for the amount in tokens instead.
+
+SPLITTING
+- For "split X between A, B and me": CALL plan_split with total_dec, recipients (comma separated, keep "me" for the user) and optionally weights ("2,1,1") or percentages (mode "percent", "50,25,25").
+- Show the batch summary and ask ONE confirmation; confirm_transfer then executes every transfer of the batch.
 
 ERROR HANDLING
 - If tool returns {"status":"err",...}:
@@ -72,6 +166,30 @@ ERROR HANDLING
   3) Show ONE "example" if provided.
   4) Do NOT call tools again until the field is provided.
 
+POLICY
+- The user's spending policy (caps, allowed recipients, quiet hours) is enforced by the backend at confirmation.
+- You may read it with get_policy but can NEVER change or relax it; the user edits it in the app settings.
+- On a Policy* error, explain the limit in one sentence and do not retry the same transfer.
+
+CO-SIGNING
+- Large transfers may need approval from the user's co-signers; the plan then has approvals_required > 0. Mention it in the summary.
+- On AwaitingApproval: tell the user how many approvals are still missing and to ask again once co-signers approved. On Rejected: relay the reason. Never approve on anyone's behalf.
+
+INVOICES
+- "Request 50 CFXN from bob for dinner": CALL create_invoice (payer optional, due_date YYYY-MM-DD optional) and share the returned id.
+- "Pay invoice 12": CALL pay_invoice with id, show the summary and ask confirmation; confirm_transfer pays it. Use list_invoices for status.
+
+ESCROW
+- For "hold 100 CFXN for alice until the goods arrive": collect recipient, amount and a refund deadline (YYYY-MM-DD), arbiter optional; CALL create_escrow.
+- Funding works like a transfer: show the summary, ask confirmation, then confirm_transfer.
+- Only call release_escrow or refund_escrow by id after the user explicitly asks for it. The ledger fee is deducted from the payout.
+- release_escrow, refund_escrow and resume_recurring first return ConfirmationRequired with a summary: show it, and after the user confirms CALL the same tool again with the same arguments.
+
+RECURRING
+- For repeated payments (rent, payroll) collect recipient, amount, frequency (daily/weekly/monthly) and optional start_date, end_date (YYYY-MM-DD) or count.
+- CALL schedule_recurring; it returns ConfirmationRequired with the schedule summary. Show it in one line and ask explicit confirmation; once the user confirms, CALL schedule_recurring again with the same arguments.
+- Use list_recurring, cancel_recurring and resume_recurring (by id) to manage existing series.
+
 HYGIENE
 - Do not re-ask fields already provided unless a tool says they are invalid/missing.
 "#;
@@ -79,16 +197,12 @@ HYGIENE
 /// Execute an ICRC-2 `transfer_from` on the specified ledger.
 async fn icrc2_transfer_from(
     ledger: Principal,
-    from_owner: Principal,
-    from_sub: Option<[u8;32]>,
-    to_owner: Principal,
-    to_sub: Option<[u8;32]>,
+    from: IcrcAccount,
+    to: IcrcAccount,
     amount: Nat,
     memo: Option<Memo>,
     created_at_time: u64,
 ) -> Result<Nat, TransferFromError> {
-    let from = IcrcAccount { owner: from_owner, subaccount: from_sub };
-    let to   = IcrcAccount { owner: to_owner,   subaccount: to_sub };
     let args = TransferFromArgs {
         from, to, amount,
         fee: None,
@@ -104,90 +218,319 @@ async fn icrc2_transfer_from(
     .0
 }
 
-/// Dispatch tool calls coming from the language model.
-async fn handle_tool_call(call: &ToolCall) -> (String, String) {
-    match call.function.name.as_str() {
-        "plan_transfer" => {
-            let parsed = tool_args_json(&call);
-            log!("[plan_transfer] raw_args={}", serde_json::to_string(&parsed).unwrap_or_default());
-
-            let args: Result<PlanArgs, _> = serde_json::from_value(parsed);
-            if let Err(e) = args {
-                log!("[plan_transfer] BadArgs: {}", e);
-                return (call.id.clone(), json!({"status":"err","code":"BadArgs","error":e.to_string()}).to_string());
-            }
-            let a = args.unwrap();
+/// Execute an ICRC-1 `transfer` from a subaccount of this canister.
+pub(crate) async fn icrc1_transfer(
+    ledger: Principal,
+    from_subaccount: Option<[u8; 32]>,
+    to: IcrcAccount,
+    amount: Nat,
+    created_at_time: u64,
+) -> Result<Nat, TransferError> {
+    let args = TransferArg {
+        from_subaccount, to, amount,
+        fee: None,
+        memo: None,
+        created_at_time: Some(created_at_time),
+    };
+    ic_cdk::call::<(TransferArg,), (Result<Nat, TransferError>,)>(ledger, "icrc1_transfer", (args,))
+        .await
+        .map_err(|_| TransferError::TemporarilyUnavailable)?
+        .0
+}
+
+/// Query the allowance `owner` granted to `spender` on a ledger.
+pub(crate) async fn icrc2_allowance(ledger: Principal, owner: IcrcAccount, spender: Principal) -> Result<Allowance, String> {
+    let args = AllowanceArgs { account: owner, spender: IcrcAccount { owner: spender, subaccount: None } };
+    ic_cdk::call::<(AllowanceArgs,), (Allowance,)>(ledger, "icrc2_allowance", (args,))
+        .await
+        .map(|(a,)| a)
+        .map_err(|(code, msg)| format!("icrc2_allowance failed: {:?} {}", code, msg))
+}
+
+/// Query the balance of an account on a ledger.
+pub(crate) async fn icrc1_balance_of(ledger: Principal, account: IcrcAccount) -> Result<Nat, String> {
+    ic_cdk::call::<(IcrcAccount,), (Nat,)>(ledger, "icrc1_balance_of", (account,))
+        .await
+        .map(|(b,)| b)
+        .map_err(|(code, msg)| format!("icrc1_balance_of failed: {:?} {}", code, msg))
+}
+
+/// Query the transfer fee of a ledger.
+pub(crate) async fn icrc1_fee(ledger: Principal) -> Result<Nat, String> {
+    ic_cdk::call::<(), (Nat,)>(ledger, "icrc1_fee", ())
+        .await
+        .map(|(fee,)| fee)
+        .map_err(|(code, msg)| format!("icrc1_fee failed: {:?} {}", code, msg))
+}
+
+/// Validate [`PlanArgs`] against the allowlist and build a checksummed plan for `caller`.
+pub(crate) fn build_plan(a: &PlanArgs, caller: Principal, created: u64) -> Result<TransferPlan, Value> {
+    if is_placeholder(&a.to) {
+        log!("[plan_transfer] NeedRecipient");
+        return Err(json!({"status":"err","code":"NeedRecipient","field":"to"}));
+    }
+    if is_placeholder(&a.amount_dec) {
+        log!("[plan_transfer] NeedAmount");
+        return Err(json!({"status":"err","code":"NeedAmount","field":"amount_dec","example":"10"}));
+    }
+
+    let (ledger_p, decimals, symbol) = resolve_plan_token(a.symbol.as_deref(), a.ledger.as_deref(), a.decimals)
+        .inspect_err(|e| { log!("[plan_transfer] {}", e["code"]); })?;
+    log!("[plan_transfer] resolved token: symbol={} ledger={} decimals={}", symbol, ledger_p.to_text(), decimals);
+
+    let amount = match scale_amount(&a.amount_dec, decimals) {
+        Ok(n) => n,
+        Err(err) => {
+            log!("[plan_transfer] BadAmount: {}", err);
+            return Err(json!({"status":"err","code":"BadAmount","field":"amount_dec","error": err,"example": example_for_decimals(decimals)}));
+        }
+    };
+    log!("[plan_transfer] scaled amount(min_units)={}", amount);
 
-            if is_placeholder(&a.to) {
-                log!("[plan_transfer] NeedRecipient");
-                return (call.id.clone(), json!({"status":"err","code":"NeedRecipient","field":"to"}).to_string());
+    let (to_p, to_sub) = match resolve_to(&a.to) {
+        Ok(v) => v,
+        Err(err) => {
+            log!("[plan_transfer] BadRecipient: {}", err);
+            return Err(json!({"status":"err","code":"BadRecipient","field":"to","error":err}));
+        }
+    };
+    log!("[plan_transfer] to_principal={} subaccount_present={}", to_p.to_text(), to_sub.is_some());
+
+    let memo = plan_memo(a.memo.as_deref(), None, caller, &symbol)?;
+    let approvals_required = cosign::required_for_owner(caller, &symbol, &amount);
+    let mut human_readable = format!("Send {} {} to {} (ledger={}).", amount, symbol, to_p.to_text(), ledger_p.to_text());
+    if approvals_required > 0 {
+        human_readable.push_str(&format!(" Needs {} co-signer approval(s).", approvals_required));
+    }
+    let mut plan = TransferPlan {
+        from_owner: Some(caller),
+        from_sub: None,
+        to_principal: to_p,
+        to_sub,
+        amount: amount.clone(),
+        symbol: symbol.clone(),
+        ledger: ledger_p,
+        memo,
+        created_at_time: created,
+        human_readable,
+        approvals_required,
+        invoice_id: None,
+        escrow_id: None,
+        supersedes: None,
+        checksum: String::new(),
+    };
+    plan.checksum = plan_checksum(&plan);
+    log!("[plan_transfer] plan checksum={} human='{}'", plan.checksum, plan.human_readable);
+    Ok(plan)
+}
+
+/// Execute a plan through ICRC-2 `transfer_from`, enforcing anti-replay on its checksum.
+pub(crate) async fn execute_plan(plan: &TransferPlan) -> Result<Nat, ExecError> {
+    let cs = plan.checksum.clone();
+    let dup = EXECUTED_CHECKSUMS.with(|s| s.borrow().contains(&cs));
+    log!("[execute_plan] anti-replay checksum={} already_executed={}", cs, dup);
+    if dup {
+        return Err(ExecError::Duplicate);
+    }
+    amend::check_current(&cs)?;
+    let user = plan.from_owner.ok_or(ExecError::MissingOwner)?;
+    let now = ic_cdk::api::time();
+    if let Err(v) = policy::check_owner(user, plan, now) {
+        log!("[execute_plan] policy violation {}: {}", v.code(), v.detail());
+        return Err(ExecError::Policy(v));
+    }
+    cosign::check(&cs, plan.approvals_required)?;
+    if let Some(id) = plan.escrow_id {
+        escrow::check_awaiting(id)?;
+    }
+    // claimed last, so no check can fail after the invoice stops being open
+    if let Some(id) = plan.invoice_id {
+        invoice::claim(id)?;
+    }
+
+    let mut receipt = Receipt {
+        checksum: cs.clone(),
+        owner: user,
+        to_principal: plan.to_principal,
+        to_sub: plan.to_sub,
+        amount: plan.amount.clone(),
+        symbol: plan.symbol.clone(),
+        ledger: plan.ledger,
+        memo: plan.memo.clone(),
+        block_index: Nat::from(0u32),
+        executed_at: now,
+        invoice_id: plan.invoice_id,
+        memo_text: plan.memo.as_ref().map(|m| describe_memo(&m.0)),
+        swap: None,
+        fiat_value: TOKENS.iter().find(|t| t.symbol == plan.symbol)
+            .and_then(|t| oracle::annotation_cached(&plan.symbol, &plan.amount, t.decimals, now)),
+    };
+    // counts toward the caps of transfers checked while this one awaits the ledger
+    policy::reserve(receipt.clone());
+
+    log!("[execute_plan] exec icrc2_transfer_from amount={} symbol={} to={}",
+        plan.amount, plan.symbol, plan.to_principal.to_text());
+    let res = icrc2_transfer_from(
+        plan.ledger,
+        IcrcAccount { owner: user, subaccount: plan.from_sub },
+        IcrcAccount { owner: plan.to_principal, subaccount: plan.to_sub },
+        plan.amount.clone(),
+        plan.memo.clone(),
+        plan.created_at_time,
+    ).await;
+    policy::release(&cs);
+    if let (Err(_), Some(id)) = (&res, plan.invoice_id) {
+        invoice::reopen(id);
+    }
+    let block_idx = res.map_err(ExecError::Ledger)?;
+    EXECUTED_CHECKSUMS.with(|s| { s.borrow_mut().insert(cs.clone()); });
+    let now = ic_cdk::api::time();
+    if let Some(id) = plan.invoice_id {
+        invoice::mark_paid(id, user, block_idx.clone(), now);
+    }
+    if let Some(id) = plan.escrow_id {
+        escrow::mark_funded(id, block_idx.clone());
+    }
+    receipt.block_index = block_idx.clone();
+    receipt.executed_at = now;
+    RECEIPTS.with(|m| m.borrow_mut().entry(user).or_default().push(receipt));
+    Ok(block_idx)
+}
+
+/// Look up a stored plan or batch by checksum.
+pub(crate) fn lookup_pending(checksum: &str) -> Option<PendingPlan> {
+    PLAN_BY_CHECKSUM.with(|m| m.borrow().get(checksum).cloned()).map(PendingPlan::Transfer)
+        .or_else(|| BATCH_BY_CHECKSUM.with(|m| m.borrow().get(checksum).cloned()).map(PendingPlan::Batch))
+        .or_else(|| SWAP_BY_CHECKSUM.with(|m| m.borrow().get(checksum).cloned()).map(|s| PendingPlan::Swap(Box::new(s))))
+}
+
+/// Dry-run every transfer of a plan or batch.
+async fn simulate_pending(pending: &PendingPlan) -> Vec<SimulationReport> {
+    simulate::run(pending.legs()).await
+}
+
+/// Execute a confirmed plan or batch and describe the outcome for the model.
+async fn execute_pending(pending: &PendingPlan) -> Value {
+    if let Err(e) = amend::check_current(pending.checksum()) {
+        return json!({"status":"err","code":e.code(),"error":e.detail()});
+    }
+    match pending {
+        PendingPlan::Transfer(plan) => match execute_plan(plan).await {
+            Ok(block_idx) => {
+                log!("[confirm_transfer] OK block_index={}", block_idx);
+                json!({ "status":"ok", "block_index": block_idx.to_string() })
             }
-            if is_placeholder(&a.amount_dec) {
-                log!("[plan_transfer] NeedAmount");
-                return (call.id.clone(), json!({"status":"err","code":"NeedAmount","field":"amount_dec","example":"10"}).to_string());
+            Err(ExecError::Duplicate) => json!({"status":"err","code":"Duplicate"}),
+            Err(e) => {
+                log!("[confirm_transfer] ExecError: {}", e.detail());
+                json!({ "status":"err", "code": e.code(), "error": e.detail() })
             }
-
-            let (ledger_p, decimals, symbol) = match resolve_token(a.symbol.as_deref(), a.ledger.as_deref()) {
-                Ok(t) => t,
-                Err(_) => {
-                    log!("[plan_transfer] BadToken");
-                    return (call.id.clone(), json!({"status":"err","code":"BadToken","field":"symbol","options": token_symbols()}).to_string());
+        },
+        PendingPlan::Swap(plan) => match swap::execute(plan).await {
+            Ok(res) => {
+                log!("[confirm_transfer] swap OK amount_out={}", res.amount_out);
+                json!({ "status":"ok", "amount_out": res.amount_out.to_string(), "symbol_out": plan.symbol_out })
+            }
+            Err(ExecError::Duplicate) => json!({"status":"err","code":"Duplicate"}),
+            Err(e) => {
+                log!("[confirm_transfer] swap ExecError: {}", e.detail());
+                json!({ "status":"err", "code": e.code(), "error": e.detail() })
+            }
+        },
+        PendingPlan::Batch(batch) => {
+            // legs run in order; already executed legs are skipped so a retry only redoes failures
+            let (mut executed, mut skipped, mut failed) = (0usize, 0usize, 0usize);
+            let mut legs = Vec::with_capacity(batch.legs.len());
+            for leg in &batch.legs {
+                let to = leg.to_principal.to_text();
+                match execute_plan(leg).await {
+                    Ok(block_idx) => {
+                        executed += 1;
+                        legs.push(json!({"to": to, "amount": leg.amount.to_string(), "status":"ok", "block_index": block_idx.to_string()}));
+                    }
+                    Err(ExecError::Duplicate) => {
+                        skipped += 1;
+                        legs.push(json!({"to": to, "amount": leg.amount.to_string(), "status":"ok", "code":"Duplicate"}));
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        legs.push(json!({"to": to, "amount": leg.amount.to_string(), "status":"err", "code": e.code(), "error": e.detail()}));
+                    }
                 }
-            };
-            log!("[plan_transfer] resolved token: symbol={} ledger={} decimals={}", symbol, ledger_p.to_text(), decimals);
+            }
+            log!("[confirm_transfer] batch={} executed={} skipped={} failed={}", batch.checksum, executed, skipped, failed);
+            if failed == 0 && executed == 0 {
+                json!({"status":"err","code":"Duplicate"})
+            } else if failed == 0 {
+                json!({"status":"ok","legs":legs})
+            } else {
+                json!({"status":"err","code":"PartialExec","legs":legs})
+            }
+        }
+    }
+}
 
-            let amount = match scale_amount(&a.amount_dec, decimals) {
-                Ok(n) => n,
-                Err(err) => {
-                    log!("[plan_transfer] BadAmount: {}", err);
-                    return (call.id.clone(), json!({"status":"err","code":"BadAmount","field":"amount_dec","error": err,"example": example_for_decimals(decimals)}).to_string());
-                }
-            };
-            log!("[plan_transfer] scaled amount(min_units)={}", amount);
+/// Rewrite a fiat `amount_dec` such as "Rp 50.000" into the token amount it is worth.
+pub(crate) async fn fiat_to_token_amount(a: &mut PlanArgs, now: u64) -> Result<(), Value> {
+    let Some((currency, fiat_e8)) = oracle::parse_fiat(&a.amount_dec) else { return Ok(()); };
+    let (_, decimals, symbol) = resolve_plan_token(a.symbol.as_deref(), a.ledger.as_deref(), a.decimals)?;
+    let rate = oracle::rate(&symbol, currency, now).await
+        .ok_or_else(|| json!({"status":"err","code":"PriceUnavailable","currency":currency,"symbol":symbol}))?;
+    let amount = oracle::token_amount_for_fiat(&fiat_e8, decimals, rate);
+    if amount == 0u32 {
+        return Err(json!({"status":"err","code":"BadAmount","field":"amount_dec","error":"fiat amount is worth less than one token unit"}));
+    }
+    log!("[plan_transfer] fiat {} {} -> {} {}", a.amount_dec, currency, amount, symbol);
+    a.amount_dec = format_amount(&amount, decimals);
+    Ok(())
+}
 
-            let (to_p, to_sub) = match resolve_to(&a.to) {
-                Ok(v) => v,
-                Err(err) => {
-                    log!("[plan_transfer] BadRecipient: {}", err);
-                    return (call.id.clone(), json!({"status":"err","code":"BadRecipient","field":"to","error":err}).to_string());
-                }
-            };
-            log!("[plan_transfer] to_principal={} subaccount_present={}", to_p.to_text(), to_sub.is_some());
-
-            let memo = a.memo.map(|m| {
-                let caller = ic_cdk::api::caller();
-                let payload = format!("{}|caller:{}", m, caller);
-                log!("[plan_transfer] memo_len={}", payload.len());
-                Memo(payload.into_bytes().into())
-            });
-            let created = ic_cdk::api::time();
-            let mut plan = TransferPlan {
-                from_owner: Some(ic_cdk::api::caller()),
-                from_sub: None,
-                to_principal: to_p,
-                to_sub,
-                amount: amount.clone(),
-                symbol: symbol.clone(),
-                ledger: ledger_p,
-                memo,
-                created_at_time: created,
-                human_readable: format!("Send {} {} to {} (ledger={}).", amount, symbol, to_p.to_text(), ledger_p.to_text()),
-                checksum: String::new(),
-            };
-            plan.checksum = plan_checksum(&plan);
-            log!("[plan_transfer] plan checksum={} human='{}'", plan.checksum, plan.human_readable);
+/// Append the fiat value of `plan` to its summary when a price is available.
+pub(crate) async fn annotate_fiat(plan: &mut TransferPlan) {
+    let Some(t) = TOKENS.iter().find(|t| t.symbol == plan.symbol) else { return; };
+    if let Some(note) = oracle::annotation(&plan.symbol, &plan.amount, t.decimals, ic_cdk::api::time()).await {
+        plan.human_readable.push_str(&format!(" {}", note));
+    }
+}
 
-            let caller = ic_cdk::api::caller();
-            LAST_PLAN_BY_CALLER.with(|m| { m.borrow_mut().insert(caller, plan.clone()); });
-            PLAN_BY_CHECKSUM.with(|m| { m.borrow_mut().insert(plan.checksum.clone(), plan.clone()); });
+/// Remember `plan` as the caller's pending plan and open it for co-signing.
+pub(crate) fn remember_plan(caller: Principal, plan: &TransferPlan) {
+    LAST_PLAN_BY_CALLER.with(|m| { m.borrow_mut().insert(caller, PendingPlan::Transfer(plan.clone())); });
+    PLAN_BY_CHECKSUM.with(|m| { m.borrow_mut().insert(plan.checksum.clone(), plan.clone()); });
+    cosign::open(plan, Some(plan.created_at_time + PLAN_TTL_NS));
+}
 
-            (call.id.clone(), serde_json::to_string(&plan).unwrap())
-        }
-        "confirm_transfer" => {
-            let raw = tool_args_json(&call);
-            log!("[confirm_transfer] raw_args={}", serde_json::to_string(&raw).unwrap_or_default());
+/// `plan_transfer`.
+struct PlanTransferTool;
 
-            let plan_from_params: Option<TransferPlan> = match raw.get("plan") {
+impl Tool for PlanTransferTool {
+    fn name(&self) -> &'static str { "plan_transfer" }
+    fn description(&self) -> Option<&'static str> { Some("Plan a token transfer (non-custodial). Returns a TransferPlan+checksum.") }
+    fn params(&self) -> Vec<Param> { PlanArgs::params() }
+    fn permission(&self) -> Permission { Permission::Plan }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let mut a: PlanArgs = parse_args(args)?;
+            fiat_to_token_amount(&mut a, ctx.now).await?;
+            let mut plan = build_plan(&a, ctx.caller, ctx.now)?;
+            annotate_fiat(&mut plan).await;
+            remember_plan(ctx.caller, &plan);
+            Ok(serde_json::to_value(&plan).unwrap())
+        })
+    }
+}
+
+/// `confirm_transfer`: execute the plan the user confirmed.
+struct ConfirmTransferTool;
+
+impl Tool for ConfirmTransferTool {
+    fn name(&self) -> &'static str { "confirm_transfer" }
+    fn description(&self) -> Option<&'static str> { Some("Execute after user confirms (uses ICRC-2 transfer_from).") }
+    fn params(&self) -> Vec<Param> { vec![Param::string("plan"), Param::string("checksum")] }
+    fn permission(&self) -> Permission { Permission::Execute }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let plan_from_params: Option<TransferPlan> = match args.get("plan") {
                 Some(v) => match decode_plan_value(v) {
                     Ok(pp) => {
                         log!("[confirm_transfer] plan provided in params; checksum={}", pp.checksum);
@@ -200,148 +543,360 @@ async fn handle_tool_call(call: &ToolCall) -> (String, String) {
                 },
                 None => None,
             };
+            let requested = plan_from_params.map(|p| p.checksum)
+                .or_else(|| args.get("checksum").and_then(|v| v.as_str()).map(str::to_string))
+                .filter(|cs| !is_placeholder(cs));
 
-            let plan = if let Some(p) = plan_from_params {
-                p
+            // only plans built by plan_transfer are executable; params merely select one
+            let pending = if let Some(cs) = requested {
+                lookup_pending(&cs).ok_or_else(|| {
+                    log!("[confirm_transfer] UnknownPlan checksum={}", cs);
+                    json!({"status":"err","code":"UnknownPlan","error":"unknown checksum; call confirm_transfer without parameters to use the last plan"})
+                })?
             } else {
-                let caller = ic_cdk::api::caller();
-                log!("[confirm_transfer] no plan+checksum param; using LAST_PLAN_BY_CALLER for {}", caller.to_text());
-                match LAST_PLAN_BY_CALLER.with(|m| m.borrow().get(&caller).cloned()) {
-                    Some(p) => p,
-                    None => {
-                        log!("[confirm_transfer] MissingPlan for caller");
-                        return (call.id.clone(), json!({"status":"err","code":"MissingPlan","error":"no last plan"}).to_string());
-                    }
-                }
+                log!("[confirm_transfer] no plan+checksum param; using LAST_PLAN_BY_CALLER for {}", ctx.caller.to_text());
+                LAST_PLAN_BY_CALLER.with(|m| m.borrow().get(&ctx.caller).cloned()).ok_or_else(|| {
+                    log!("[confirm_transfer] MissingPlan for caller");
+                    json!({"status":"err","code":"MissingPlan","error":"no last plan"})
+                })?
             };
-
-            let cs = plan.checksum.clone();
-            let dup = EXECUTED_CHECKSUMS.with(|s| s.borrow().contains(&cs));
-            log!("[confirm_transfer] anti-replay checksum={} already_executed={}", cs, dup);
-            if dup {
-                return (call.id.clone(), json!({"status":"err","code":"Duplicate"}).to_string());
+            if pending.owner() != Some(ctx.caller) {
+                log!("[confirm_transfer] NotOwner checksum={}", pending.checksum());
+                return Err(json!({"status":"err","code":"NotOwner"}));
             }
+            // only the user's own confirmation of an already shown plan executes it
+            if ctx.confirmed != Some(pending.checksum()) {
+                log!("[confirm_transfer] ConfirmationRequired checksum={}", pending.checksum());
+                return Err(json!({"status":"err","code":"ConfirmationRequired","checksum":pending.checksum(),
+                    "error":"show the plan and wait for the user to confirm it"}));
+            }
+            Ok(execute_pending(&pending).await)
+        })
+    }
+}
 
-            log!("[confirm_transfer] exec icrc2_transfer_from amount={} symbol={} to={}",
-                plan.amount, plan.symbol, plan.to_principal.to_text());
-            let exec = match plan.from_owner {
-                Some(user) => icrc2_transfer_from(
-                    plan.ledger,
-                    user, plan.from_sub,
-                    plan.to_principal, plan.to_sub,
-                    plan.amount.clone(),
-                    plan.memo.clone(),
-                    plan.created_at_time,
-                ).await.map_err(|e| format!("ICRC2::{:?}", e)),
-                None => Err("missing from_owner".into()),
-            };
+/// `save_account`: remember an alias for a principal.
+struct SaveAccountTool;
 
-            let res = match exec {
-                Ok(block_idx) => {
-                    EXECUTED_CHECKSUMS.with(|s| { s.borrow_mut().insert(cs); });
-                    log!("[confirm_transfer] OK block_index={}", block_idx);
-                    json!({ "status":"ok", "block_index": block_idx.to_string() })
-                }
-                Err(e) => {
-                    log!("[confirm_transfer] ExecError: {}", e);
-                    json!({ "status":"err", "code":"ExecError", "error": e })
+impl Tool for SaveAccountTool {
+    fn name(&self) -> &'static str { "save_account" }
+    fn params(&self) -> Vec<Param> { SaveAccountArgs::params() }
+    fn permission(&self) -> Permission { Permission::Write }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let a: SaveAccountArgs = parse_args(args)?;
+            let p = Principal::from_text(&a.owner).map_err(|_| json!({"status":"err","code":"BadPrincipal","field":"owner"}))?;
+            let sub32 = match a.sub.as_deref().filter(|s| !is_placeholder(s)) {
+                Some(h) => {
+                    let v = hex::decode(h.trim().trim_start_matches("0x")).ok().filter(|v| v.len() == 32)
+                        .ok_or_else(|| json!({"status":"err","code":"BadSub","field":"sub"}))?;
+                    let mut x=[0u8;32]; x.copy_from_slice(&v); Some(x)
                 }
+                None => None,
             };
-            (call.id.clone(), res.to_string())
-        }
-        "save_account" => {
-            #[derive(Deserialize)]
-            struct SaveArgs { alias: String, owner: String, sub: Option<Vec<u8>> }
-            let parsed = tool_args_json(&call);
-            let a: Result<SaveArgs, _> = serde_json::from_value(parsed);
-            if let Err(e) = a {
-                return (call.id.clone(), json!({"status":"err","code":"BadArgs","error":e.to_string()}).to_string());
+            if let Some(tags) = a.tags.as_deref() {
+                analytics::set_tags(ctx.caller, &a.alias, analytics::parse_tags(tags));
             }
-            let a = a.unwrap();
-            let p = match Principal::from_text(&a.owner) {
-                Ok(p) => p,
-                Err(_) => return (call.id.clone(), json!({"status":"err","code":"BadPrincipal"}).to_string()),
-            };
-            let sub32 = if let Some(v) = a.sub {
-                if v.len()!=32 { return (call.id.clone(), json!({"status":"err","code":"BadSub"}).to_string()); }
-                let mut x=[0u8;32]; x.copy_from_slice(&v); Some(x)
-            } else { None };
-            let rec = SavedAccount { alias: a.alias.clone(), owner: p, subaccount: sub32 };
+            let rec = SavedAccount { alias: a.alias.clone(), owner: p, subaccount: sub32, tags: vec![] };
             ACCOUNTS.with(|m| { m.borrow_mut().insert(a.alias, rec); });
-            (call.id.clone(), json!({"status":"ok"}).to_string())
-        }
-        "list_accounts" => {
-            let v = ACCOUNTS.with(|m| serde_json::to_string(&m.borrow().values().cloned().collect::<Vec<_>>()).unwrap());
-            (call.id.clone(), v)
-        }
-        _ => (call.id.clone(), json!({"status":"err","error":"unknown tool"}).to_string()),
+            Ok(json!({"status":"ok"}))
+        })
     }
 }
 
+/// `list_accounts`.
+struct ListAccountsTool;
+
+impl Tool for ListAccountsTool {
+    fn name(&self) -> &'static str { "list_accounts" }
+    fn description(&self) -> Option<&'static str> { Some("List saved accounts") }
+    fn permission(&self) -> Permission { Permission::Read }
+    fn call<'a>(&'a self, _args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move { Ok(json!(analytics::accounts_for(ctx.caller))) })
+    }
+}
+
+/// `set_language`: store the user's reply language.
+struct SetLanguageTool;
+
+impl Tool for SetLanguageTool {
+    fn name(&self) -> &'static str { "set_language" }
+    fn description(&self) -> Option<&'static str> { Some("Remember the user's reply language") }
+    fn params(&self) -> Vec<Param> {
+        vec![Param::string("lang").describe("en, id, ms, jv, or auto to detect per message").required()]
+    }
+    fn permission(&self) -> Permission { Permission::Write }
+    fn guidance(&self) -> Option<&'static str> {
+        Some("If the user asks you to always use a language (or to go back to automatic), call set_language.")
+    }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let lang = args.get("lang").and_then(|v| v.as_str()).unwrap_or_default();
+            let l = set_language_for(ctx.caller, lang)
+                .map_err(|e| json!({"status":"err","code":"BadArgs","field":"lang","error":e,"options":["en","id","ms","jv","auto"]}))?;
+            Ok(json!({"status":"ok","lang":l.unwrap_or("auto")}))
+        })
+    }
+}
+
+/// Every tool offered to the model.
+fn tool_registry() -> Registry {
+    Registry::default()
+        .with(PlanTransferTool)
+        .with(ConfirmTransferTool)
+        .with(amend::AmendPlanTool)
+        .with(simulate::SimulateTransferTool)
+        .with(allowance::PrepareApprovalTool)
+        .with(swap::PlanSwapTool)
+        .with(split::PlanSplitTool)
+        .with(invoice::CreateInvoiceTool)
+        .with(invoice::PayInvoiceTool)
+        .with(invoice::ListInvoicesTool)
+        .with(escrow::CreateEscrowTool)
+        .with(escrow::SettleEscrowTool { refund: false })
+        .with(escrow::SettleEscrowTool { refund: true })
+        .with(escrow::ListEscrowsTool)
+        .with(SaveAccountTool)
+        .with(analytics::SpendingSummaryTool)
+        .with(portfolio::GetPortfolioTool)
+        .with(history::AccountTransactionsTool)
+        .with(ListAccountsTool)
+        .with(SetLanguageTool)
+        .with(policy::GetPolicyTool)
+        .with(recurring::ScheduleRecurringTool)
+        .with(recurring::ListRecurringTool)
+        .with(recurring::ChangeRecurringTool { resume: false })
+        .with(recurring::ChangeRecurringTool { resume: true })
+}
+
+/// Dispatch tool calls coming from the language model.
+///
+/// `confirmed` is the checksum of the plan the user explicitly confirmed in this turn, if any.
+async fn handle_tool_call(call: &ToolCall, confirmed: Option<&str>) -> (String, String) {
+    let ctx = ToolCtx { caller: ic_cdk::api::caller(), now: ic_cdk::api::time(), confirmed };
+    (call.id.clone(), tool_registry().dispatch(call, &ctx).await)
+}
+
 /// Chat entrypoint used by the frontend to converse with the copilot.
+///
+/// Sends `user_text` in session `session_id`; `model` overrides the configured model for this request.
+#[update]
+pub async fn copilot_chat(session_id: u64, user_text: String, model: Option<LlmModel>) -> Result<ChatResponse, String> {
+    let caller = ic_cdk::api::caller();
+    session::validate_text(&user_text)?;
+    let session = session::get(caller, session_id, ic_cdk::api::time())?;
+    let receipts_before = RECEIPTS.with(|m| m.borrow().get(&caller).map_or(0, Vec::len));
+    let user = ChatMessage::User { content: user_text };
+    let before = awaiting_confirmation(caller);
+    let shown = session.pending_checksum.as_deref();
+    let (mut resp, mut turn) = chat_turn(caller, session.messages, user.clone(), model, shown, session.pending_in_last_turn).await;
+    turn.insert(0, user);
+    turn.push(ChatMessage::Assistant(AssistantMessage { content: Some(resp.reply.clone()), tool_calls: Vec::new() }));
+    resp.pending_plan = pending_plan(caller);
+    resp.receipts = RECEIPTS.with(|m| m.borrow().get(&caller).map(|r| r[receipts_before.min(r.len())..].to_vec())).unwrap_or_default();
+    if resp.status == ChatStatus::Done && resp.pending_plan.is_some() {
+        resp.status = ChatStatus::AwaitingConfirmation;
+    }
+    // a new plan or tool call, or one this turn's tool results mention, counts as presented now
+    let after = awaiting_confirmation(caller);
+    let presented = after.as_deref().is_some_and(|cs| {
+        before.as_deref() != Some(cs) || resp.reply.contains(cs)
+            || turn.iter().any(|m| matches!(m, ChatMessage::Tool { content, .. } if content.contains(cs)))
+    });
+    let pending = after.filter(|cs| presented || shown == Some(cs.as_str()));
+    session::append(session_id, turn, pending, presented, ic_cdk::api::time());
+    Ok(resp)
+}
+
+/// The caller's last plan unless it was already executed or is older than [`PLAN_TTL_NS`].
+fn pending_plan(caller: Principal) -> Option<PendingPlan> {
+    let now = ic_cdk::api::time();
+    LAST_PLAN_BY_CALLER.with(|m| m.borrow().get(&caller).cloned())
+        .filter(|p| !EXECUTED_CHECKSUMS.with(|s| s.borrow().contains(p.checksum())))
+        .filter(|p| p.legs().first().is_some_and(|l| l.created_at_time + PLAN_TTL_NS >= now))
+}
+
+/// Checksum of the caller's newest plan or fund-moving tool call awaiting confirmation.
+fn awaiting_confirmation(caller: Principal) -> Option<String> {
+    let plan = pending_plan(caller).map(|p| (p.legs().first().map_or(0, |l| l.created_at_time), p.checksum().to_string()));
+    let action = PENDING_ACTIONS.with(|m| m.borrow().get(&caller).map(|a| (a.created_at, a.checksum.clone())));
+    plan.into_iter().chain(action).max().map(|(_, cs)| cs)
+}
+
+/// Execute the caller's pending plan `checksum`; calling this is the user's explicit confirmation.
+///
+/// Only the plan currently awaiting confirmation qualifies; plans replaced by a newer one are refused.
 #[update]
-pub async fn copilot_chat(messages: Vec<ChatMessage>) -> String {
-    let tools = vec![
-        ic_llm::tool("plan_transfer")
-            .with_description("Plan a token transfer (non-custodial). Returns a TransferPlan+checksum.")
-            .with_parameter(ic_llm::parameter("to",          ParameterType::String).is_required())
-            .with_parameter(ic_llm::parameter("amount_dec",  ParameterType::String).is_required())
-            .with_parameter(ic_llm::parameter("symbol",      ParameterType::String))
-            .with_parameter(ic_llm::parameter("ledger",      ParameterType::String))
-            .with_parameter(ic_llm::parameter("decimals",    ParameterType::Number))
-            .with_parameter(ic_llm::parameter("memo",        ParameterType::String))
-            .build(),
-        ic_llm::tool("confirm_transfer")
-            .with_description("Execute after user confirms (uses ICRC-2 transfer_from).")
-            .with_parameter(ic_llm::parameter("plan",        ParameterType::String))
-            .with_parameter(ic_llm::parameter("checksum",    ParameterType::String))
-            .build(),
-        ic_llm::tool("save_account")
-            .with_parameter(ic_llm::parameter("alias",       ParameterType::String).is_required())
-            .with_parameter(ic_llm::parameter("owner",       ParameterType::String).is_required())
-            .with_parameter(ic_llm::parameter("sub",         ParameterType::String))
-            .build(),
-        ic_llm::tool("list_accounts").with_description("List saved accounts").build(),
-    ];
-
-    let mut convo = vec![ChatMessage::System { content: SYSTEM_PROMPT.to_string() }];
-    convo.extend(messages);
-
-    let lang = detect_lang_last_user(&convo);
-    convo.insert(1, lang_guard(lang));
-    cap_messages_in_place(&mut convo);
-
-    let mut resp = ic_llm::chat(Model::Llama3_1_8B)
-        .with_messages(convo.clone())
-        .with_tools(tools.clone())
-        .send()
-        .await;
+pub async fn confirm(checksum: String) -> Result<String, String> {
+    let caller = ic_cdk::api::caller();
+    let pending = pending_plan(caller)
+        .filter(|p| p.checksum() == checksum)
+        .ok_or("not the pending plan")?;
+    let result = execute_pending(&pending).await;
+    if result["status"] == "ok" {
+        session::forget_pending(caller, &checksum);
+    }
+    if result["status"] == "ok" { Ok(result.to_string()) } else { Err(result.to_string()) }
+}
+
+/// Cancel the caller's pending plan so it can no longer execute; returns its checksum.
+#[update]
+pub fn cancel_plan() -> Option<String> {
+    let caller = ic_cdk::api::caller();
+    if let Some(action) = PENDING_ACTIONS.with(|m| m.borrow_mut().remove(&caller)) {
+        session::forget_pending(caller, &action.checksum);
+    }
+    let plan = pending_plan(caller)?;
+    LAST_PLAN_BY_CALLER.with(|m| { m.borrow_mut().remove(&caller); });
+    amend::cancel(&plan);
+    session::forget_pending(caller, plan.checksum());
+    Some(plan.checksum().to_string())
+}
+
+/// Run one user turn; returns the response without plan and receipts, and the tool calls and results it produced.
+///
+/// `shown` is the checksum the session presented for confirmation; nothing else can be confirmed, and
+/// without a checksum prefix only if it was presented in the last turn (`in_last_turn`).
+async fn chat_turn(
+    caller: Principal,
+    history: Vec<ChatMessage>,
+    user: ChatMessage,
+    model: Option<LlmModel>,
+    shown: Option<&str>,
+    in_last_turn: bool,
+) -> (ChatResponse, Vec<ChatMessage>) {
+    let registry = tool_registry();
+    let tools = registry.definitions();
+
+    let mut convo = vec![ChatMessage::System { content: format!("{}{}", SYSTEM_PROMPT, registry.guidance()) }];
+    convo.extend(history);
+    convo.push(user);
+
+    let mut lang = preferred_language(caller).unwrap_or_else(|| detect_lang_last_user(&convo));
+    // a plan can only be confirmed by this user message, and only if this session showed it
+    let confirmed = match convo.last() {
+        Some(ChatMessage::User { content }) => awaiting_confirmation(caller)
+            .filter(|cs| shown == Some(cs.as_str()) && intent::confirms(content, cs, in_last_turn)),
+        _ => None,
+    };
+
+    // simple commands ("send 10 CFXN to alice", "lanjut") are handled without the model
+    if let Some(ChatMessage::User { content }) = convo.last() {
+        if let Some(reply) = intent::handle(caller, content, lang, confirmed.as_deref()).await {
+            CHAT_TRACES.with(|m| { m.borrow_mut().insert(caller, Vec::new()); });
+            let resp = ChatResponse { reply, pending_plan: None, receipts: Vec::new(), tools: Vec::new(), status: ChatStatus::Done };
+            return (resp, Vec::new());
+        }
+    }
+
+    // restated if the turns that produced the plan get trimmed
+    let note = pending_plan(caller).map(|p| pending_plan_note(&p));
+    set_lang_guard(&mut convo, lang);
+    trim_to_token_budget(&mut convo, MAX_CONTEXT_TOKENS, note.clone());
+    if let Some(notice) = recurring::failure_notice(&recurring::take_unreported_failures(caller)) {
+        convo.push(notice);
+    }
+
+    let mut trace = Vec::new();
+    let mut turn = Vec::new();
+    let mut tools_called = Vec::new();
+    let (mut resp, t) = llm::chat(model, &convo, &tools, 1).await;
+    trace.push(t);
 
     let mut rounds = 0usize;
-    loop {
+    while let Some(r) = resp.as_ref() {
         rounds += 1;
         if rounds > 6 { break; }
 
-        let calls: Vec<ToolCall> = resp.message.tool_calls.clone();
+        let calls: Vec<ToolCall> = r.message.tool_calls.clone();
         if calls.is_empty() { break; }
 
+        let assistant = ChatMessage::Assistant(r.message.clone());
+        convo.push(assistant.clone());
+        turn.push(assistant);
         for call in calls {
-            let (id, result_json) = handle_tool_call(&call).await;
-            convo.push(ChatMessage::Tool { tool_call_id: id, content: result_json });
+            let ctx = ToolCtx { caller, now: ic_cdk::api::time(), confirmed: confirmed.as_deref() };
+            let result_json = registry.dispatch(&call, &ctx).await;
+            let id = call.id.clone();
+            tools_called.push(i18n::tool_trace(&call.function.name, &result_json));
+            let pending = LAST_PLAN_BY_CALLER.with(|m| m.borrow().get(&caller).cloned());
+            let content = i18n::localize(&result_json, lang, pending.as_ref(), ic_cdk::api::time());
+            let result = ChatMessage::Tool { tool_call_id: id, content };
+            convo.push(result.clone());
+            turn.push(result);
         }
 
-        let lang = detect_lang_last_user(&convo);
-        convo.insert(1, lang_guard(lang));
-        cap_messages_in_place(&mut convo);
+        // set_language may have changed it this round
+        lang = preferred_language(caller).unwrap_or(lang);
+        set_lang_guard(&mut convo, lang);
+        trim_to_token_budget(&mut convo, MAX_CONTEXT_TOKENS, note.clone());
+
+        let (next, t) = llm::chat(model, &convo, &tools, rounds as u32 + 1).await;
+        resp = next;
+        trace.push(t);
+    }
+
+    log!("[copilot_chat] models={:?}", trace.iter().map(|t| t.model).collect::<Vec<_>>());
+    CHAT_TRACES.with(|m| { m.borrow_mut().insert(caller, trace); });
+    let status = match &resp {
+        None => ChatStatus::ModelUnavailable,
+        Some(r) if !r.message.tool_calls.is_empty() => ChatStatus::RoundLimit,
+        Some(_) => ChatStatus::Done,
+    };
+    let reply = match status {
+        ChatStatus::ModelUnavailable => i18n::error_message("ModelUnavailable", lang, &Value::Null).unwrap_or_default(),
+        ChatStatus::RoundLimit => i18n::error_message("RoundLimit", lang, &Value::Null).unwrap_or_default(),
+        _ => resp.and_then(|r| r.message.content).unwrap_or_default(),
+    };
+    let resp = ChatResponse { reply, pending_plan: None, receipts: Vec::new(), tools: tools_called, status };
+    (resp, turn)
+}
+
+/// Stored reply language of `user`.
+fn preferred_language(user: Principal) -> Option<&'static str> {
+    LANG_PREFS.with(|m| m.borrow().get(&user).and_then(|l| normalize_lang(l)))
+}
 
-        resp = ic_llm::chat(Model::Llama3_1_8B)
-            .with_messages(convo.clone())
-            .with_tools(tools.clone())
-            .send()
-            .await;
+/// Store `lang` ("auto" or empty clears it) and return the stored code.
+fn set_language_for(user: Principal, lang: &str) -> Result<Option<&'static str>, String> {
+    if lang.trim().is_empty() || lang.trim().eq_ignore_ascii_case("auto") {
+        LANG_PREFS.with(|m| { m.borrow_mut().remove(&user); });
+        return Ok(None);
     }
+    let code = normalize_lang(lang).ok_or_else(|| format!("unsupported language '{}'", lang))?;
+    LANG_PREFS.with(|m| { m.borrow_mut().insert(user, code.to_string()); });
+    Ok(Some(code))
+}
 
-    resp.message.content.unwrap_or_default()
+/// Set the caller's reply language (en, id, ms, jv); `None` detects it per message again.
+#[update]
+pub fn set_language(lang: Option<String>) -> Result<Option<String>, String> {
+    set_language_for(ic_cdk::api::caller(), lang.as_deref().unwrap_or_default()).map(|l| l.map(str::to_string))
+}
+
+/// The caller's stored reply language, if any.
+#[query]
+pub fn get_language() -> Option<String> {
+    preferred_language(ic_cdk::api::caller()).map(str::to_string)
+}
+
+/// Start a conversation for the caller.
+#[update]
+pub fn start_session() -> u64 {
+    session::start(ic_cdk::api::caller(), ic_cdk::api::time()).id
+}
+
+/// Read one of the caller's sessions.
+#[query]
+pub fn get_session(session_id: u64) -> Result<Session, String> {
+    session::get(ic_cdk::api::caller(), session_id, ic_cdk::api::time())
+}
+
+/// Models used by the caller's latest chat; empty when it was handled without the model.
+#[query]
+pub fn get_chat_trace() -> Vec<ModelTrace> {
+    let caller = ic_cdk::api::caller();
+    CHAT_TRACES.with(|m| m.borrow().get(&caller).cloned()).unwrap_or_default()
 }
 
 /// Save an account alias for later reuse.
@@ -355,15 +910,288 @@ pub fn save_account(alias: String, owner: String, sub: Option<Vec<u8>>) -> Resul
             let mut a=[0u8;32]; a.copy_from_slice(&v); Some(a)
         }
     };
-    let rec = SavedAccount { alias: alias.clone(), owner: p, subaccount: sub32 };
+    let rec = SavedAccount { alias: alias.clone(), owner: p, subaccount: sub32, tags: vec![] };
     ACCOUNTS.with(|m| m.borrow_mut().insert(alias, rec));
     Ok(())
 }
 
-/// List all saved account aliases.
+/// Replace the caller's tags of a saved account; other callers keep their own.
+#[update]
+pub fn tag_account(alias: String, tags: Vec<String>) -> Result<SavedAccount, String> {
+    let mut a = ACCOUNTS.with(|m| m.borrow().get(&alias).cloned()).ok_or_else(|| format!("unknown alias {}", alias))?;
+    a.tags = analytics::parse_tags(&tags.join(","));
+    analytics::set_tags(ic_cdk::api::caller(), &alias, a.tags.clone());
+    Ok(a)
+}
+
+/// Spending of the caller aggregated by token, period, recipient and tag.
+#[query]
+pub fn spending_summary(args: SummaryArgs) -> Result<SpendingSummary, String> {
+    analytics::summary(ic_cdk::api::caller(), &args).map_err(|e| e.to_string())
+}
+
+/// List all saved account aliases with the caller's tags.
 #[query]
 pub fn list_accounts() -> Vec<SavedAccount> {
-    ACCOUNTS.with(|m| m.borrow().values().cloned().collect())
+    analytics::accounts_for(ic_cdk::api::caller())
+}
+
+/// Dry-run a stored plan of the caller; read-only, but an update call because it queries the ledger.
+#[update]
+pub async fn simulate_transfer(checksum: String) -> Result<Vec<SimulationReport>, String> {
+    let pending = lookup_pending(&checksum)
+        .filter(|p| p.owner() == Some(ic_cdk::api::caller()))
+        .ok_or("unknown plan")?;
+    Ok(simulate_pending(&pending).await)
+}
+
+/// Prepare the `icrc2_approve` call the caller signs so this canister can execute a plan or budget.
+#[update]
+pub async fn prepare_approval(args: ApprovalPrepArgs) -> Result<PreparedApproval, String> {
+    allowance::prepare(ic_cdk::api::caller(), ic_cdk::api::id(), &args, ic_cdk::api::time()).await
+        .map_err(|e| e.to_string())
+}
+
+/// All versions of an amended plan, oldest first.
+#[query]
+pub fn plan_history(checksum: String) -> Vec<TransferPlan> {
+    let caller = ic_cdk::api::caller();
+    amend::history(&checksum).into_iter().filter(|p| p.from_owner == Some(caller)).collect()
+}
+
+/// Apply the optional init arguments.
+#[init]
+pub fn init(args: Option<InitArgs>) {
+    let args = args.unwrap_or_default();
+    CONFIG.with(|c| {
+        let mut c = c.borrow_mut();
+        if let Some(m) = args.model { c.model = m; }
+        if args.fallback_model.is_some() { c.fallback_model = args.fallback_model; }
+    });
+}
+
+/// Save the escrows, recurring series, policies, invoices and co-signer state to stable memory before an upgrade.
+#[pre_upgrade]
+fn pre_upgrade() {
+    let (cosign_configs, approvals) = cosign::snapshot();
+    let state = StableState {
+        escrows: escrow::snapshot(),
+        recurring: recurring::snapshot(),
+        policies: policy::snapshot(),
+        invoices: invoice::snapshot(),
+        cosign_configs,
+        approvals,
+    };
+    ic_cdk::storage::stable_save((state,)).expect("failed to save state");
+}
+
+/// Restore the state saved by [`pre_upgrade`], re-arm the recurring timers and apply the init arguments again.
+#[post_upgrade]
+fn post_upgrade(args: Option<InitArgs>) {
+    init(args);
+    // nothing to restore when upgrading from a version that saved no state
+    if let Ok((state,)) = ic_cdk::storage::stable_restore::<(StableState,)>() {
+        escrow::restore(state.escrows);
+        recurring::restore(state.recurring);
+        policy::restore(state.policies);
+        invoice::restore(state.invoices);
+        cosign::restore((state.cosign_configs, state.approvals));
+    }
+    recurring::rearm_all(ic_cdk::api::time());
+}
+
+/// Set the DEX canister used for swaps (controllers only).
+#[update]
+pub fn set_dex_canister(dex: Option<Principal>) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::api::caller()) {
+        return Err("only controllers can change the configuration".into());
+    }
+    CONFIG.with(|c| c.borrow_mut().dex_canister = dex);
+    Ok(())
+}
+
+/// Set the price oracle canister used for fiat values (controllers only).
+#[update]
+pub fn set_oracle_canister(oracle: Option<Principal>) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::api::caller()) {
+        return Err("only controllers can change the configuration".into());
+    }
+    CONFIG.with(|c| c.borrow_mut().oracle_canister = oracle);
+    Ok(())
+}
+
+/// Balances and allowances of the caller across all allowlisted tokens.
+#[update]
+pub async fn get_portfolio() -> Portfolio {
+    portfolio::fetch(ic_cdk::api::caller(), ic_cdk::api::id(), ic_cdk::api::time()).await
+}
+
+/// One page of the caller's ledger history from the token's index canister.
+#[update]
+pub async fn get_account_transactions(args: HistoryArgs) -> Result<TxPage, String> {
+    history::fetch(ic_cdk::api::caller(), &args).await.map_err(|e| e.to_string())
+}
+
+/// Fiat value of `amount_dec` tokens, e.g. "≈ Rp 50.000 / $3.10"; `None` without a fresh price.
+#[update]
+pub async fn get_fiat_value(symbol: String, amount_dec: String) -> Result<Option<String>, String> {
+    let (_, decimals, symbol) = resolve_token(Some(&symbol), None).map_err(|_| format!("unknown token {}", symbol))?;
+    let amount = scale_amount(&amount_dec, decimals)?;
+    Ok(oracle::annotation(&symbol, &amount, decimals, ic_cdk::api::time()).await)
+}
+
+/// Set or clear the index canister of an allowlisted token (controllers only).
+#[update]
+pub fn set_index_canister(symbol: String, index: Option<Principal>) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::api::caller()) {
+        return Err("only controllers can change the configuration".into());
+    }
+    let (_, _, symbol) = resolve_token(Some(&symbol), None).map_err(|_| format!("unknown token {}", symbol))?;
+    CONFIG.with(|c| {
+        let mut c = c.borrow_mut();
+        c.index_canisters.retain(|(s, _)| *s != symbol);
+        if let Some(index) = index {
+            c.index_canisters.push((symbol, index));
+        }
+    });
+    Ok(())
+}
+
+/// Set the chat model and its fallback (controllers only).
+#[update]
+pub fn set_model(model: LlmModel, fallback_model: Option<LlmModel>) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&ic_cdk::api::caller()) {
+        return Err("only controllers can change the configuration".into());
+    }
+    CONFIG.with(|c| {
+        let mut c = c.borrow_mut();
+        c.model = model;
+        c.fallback_model = fallback_model;
+    });
+    Ok(())
+}
+
+/// Read the canister configuration.
+#[query]
+pub fn get_config() -> Config {
+    CONFIG.with(|c| c.borrow().clone())
+}
+
+/// Replace the caller's spending policy.
+#[update]
+pub fn set_policy(policy: SpendingPolicy) -> Result<(), String> {
+    policy::set(ic_cdk::api::caller(), policy)
+}
+
+/// Read the caller's spending policy.
+#[query]
+pub fn get_policy() -> SpendingPolicy {
+    let caller = ic_cdk::api::caller();
+    POLICIES.with(|m| m.borrow().get(&caller).cloned()).unwrap_or_default()
+}
+
+/// List transfers executed on behalf of the caller.
+#[query]
+pub fn list_receipts() -> Vec<Receipt> {
+    let caller = ic_cdk::api::caller();
+    RECEIPTS.with(|m| m.borrow().get(&caller).cloned()).unwrap_or_default()
+}
+
+/// Replace the caller's co-signer setup; plans created before keep their co-signers and quorum.
+#[update]
+pub fn set_cosign_config(config: CosignConfig) -> Result<(), String> {
+    cosign::set_config(ic_cdk::api::caller(), config)
+}
+
+/// Read the caller's co-signer setup.
+#[query]
+pub fn get_cosign_config() -> CosignConfig {
+    let caller = ic_cdk::api::caller();
+    COSIGN_CONFIGS.with(|m| m.borrow().get(&caller).cloned()).unwrap_or_default()
+}
+
+/// Approve a plan as one of its co-signers.
+#[update]
+pub fn approve_plan(checksum: String) -> Result<ApprovalRequest, String> {
+    cosign::decide_stored(&checksum, ic_cdk::api::caller(), true, None, ic_cdk::api::time())
+}
+
+/// Reject a plan as one of its co-signers.
+#[update]
+pub fn reject_plan(checksum: String, reason: Option<String>) -> Result<ApprovalRequest, String> {
+    cosign::decide_stored(&checksum, ic_cdk::api::caller(), false, reason, ic_cdk::api::time())
+}
+
+/// Plans waiting for the caller's approval as co-signer.
+#[query]
+pub fn pending_approvals() -> Vec<ApprovalRequest> {
+    cosign::pending_for(ic_cdk::api::caller(), ic_cdk::api::time())
+}
+
+/// Create an invoice payable to the caller.
+#[update]
+pub fn create_invoice(args: InvoiceArgs) -> Result<Invoice, String> {
+    invoice::create(ic_cdk::api::caller(), args, ic_cdk::api::time()).map_err(|e| e.to_string())
+}
+
+/// Look up an invoice by id.
+#[query]
+pub fn get_invoice(id: u64) -> Option<Invoice> {
+    invoice::get(id)
+}
+
+/// Invoices the caller created, owes or paid.
+#[query]
+pub fn list_invoices() -> Vec<Invoice> {
+    invoice::list(ic_cdk::api::caller())
+}
+
+/// Cancel an open invoice created by the caller.
+#[update]
+pub fn cancel_invoice(id: u64) -> Result<Invoice, String> {
+    invoice::cancel(ic_cdk::api::caller(), id)
+}
+
+/// Escrows the caller pays, receives or arbitrates.
+#[query]
+pub fn list_escrows() -> Vec<Escrow> {
+    escrow::list(ic_cdk::api::caller())
+}
+
+/// Release a funded escrow to its recipient.
+#[update]
+pub async fn release_escrow(id: u64) -> Result<Escrow, String> {
+    escrow::settle(ic_cdk::api::caller(), id, false, ic_cdk::api::time()).await
+}
+
+/// Refund a funded escrow to its payer.
+#[update]
+pub async fn refund_escrow(id: u64) -> Result<Escrow, String> {
+    escrow::settle(ic_cdk::api::caller(), id, true, ic_cdk::api::time()).await
+}
+
+/// Schedule a recurring transfer for the caller.
+#[update]
+pub fn schedule_recurring(args: RecurringArgs) -> Result<RecurringTransfer, String> {
+    recurring::create(ic_cdk::api::caller(), args, ic_cdk::api::time()).map_err(|e| e.to_string())
+}
+
+/// List the caller's recurring transfers.
+#[query]
+pub fn list_recurring() -> Vec<RecurringTransfer> {
+    recurring::list(ic_cdk::api::caller())
+}
+
+/// Cancel one of the caller's recurring transfers.
+#[update]
+pub fn cancel_recurring(id: u64) -> Result<RecurringTransfer, String> {
+    recurring::cancel(ic_cdk::api::caller(), id)
+}
+
+/// Resume a paused recurring transfer of the caller.
+#[update]
+pub fn resume_recurring(id: u64) -> Result<RecurringTransfer, String> {
+    recurring::resume(ic_cdk::api::caller(), id, ic_cdk::api::time())
 }
 
 ic_cdk::export_candid!();
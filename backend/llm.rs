@@ -0,0 +1,77 @@
+use candid::{CandidType, Principal};
+use ic_llm::{ChatMessage, Response, Tool};
+use serde::Serialize;
+
+use crate::{log, CONFIG};
+use crate::types::{LlmModel, ModelTrace};
+
+/// The LLM canister serving `v1_chat`.
+const LLM_CANISTER: &str = "w36hm-eqaaa-aaaal-qr76a-cai";
+
+#[derive(CandidType, Serialize)]
+struct Request {
+    model: String,
+    messages: Vec<ChatMessage>,
+    tools: Option<Vec<Tool>>,
+}
+
+/// Primary and fallback model for a chat; a requested model replaces the configured primary.
+pub fn models_for(requested: Option<LlmModel>) -> (LlmModel, Option<LlmModel>) {
+    let (model, fallback) = CONFIG.with(|c| { let c = c.borrow(); (c.model, c.fallback_model) });
+    let primary = requested.unwrap_or(model);
+    (primary, fallback.filter(|f| *f != primary))
+}
+
+/// Why `resp` cannot be acted on: unknown tools, missing call ids or missing required arguments.
+pub fn malformed(resp: &Response, tools: &[Tool]) -> Option<String> {
+    for call in &resp.message.tool_calls {
+        let Some(Tool::Function(f)) = tools.iter().find(|Tool::Function(f)| f.name == call.function.name) else {
+            return Some(format!("unknown tool '{}'", call.function.name));
+        };
+        if call.id.trim().is_empty() {
+            return Some(format!("tool call to '{}' without id", f.name));
+        }
+        let required = f.parameters.as_ref().and_then(|p| p.required.as_ref());
+        if let Some(missing) = required.into_iter().flatten().find(|r| call.function.get(r).is_none()) {
+            return Some(format!("tool '{}' missing argument '{}'", f.name, missing));
+        }
+    }
+    None
+}
+
+async fn call(model: LlmModel, messages: &[ChatMessage], tools: &[Tool]) -> Result<Response, String> {
+    let canister = Principal::from_text(LLM_CANISTER).map_err(|e| e.to_string())?;
+    let req = Request {
+        model: model.model().to_string(),
+        messages: messages.to_vec(),
+        tools: (!tools.is_empty()).then(|| tools.to_vec()),
+    };
+    ic_cdk::call::<(Request,), (Response,)>(canister, "v1_chat", (req,))
+        .await
+        .map(|(r,)| r)
+        .map_err(|(code, msg)| format!("v1_chat failed: {:?} {}", code, msg))
+}
+
+/// Run one chat round, retrying on the fallback model when the primary fails or returns malformed tool calls.
+pub async fn chat(requested: Option<LlmModel>, messages: &[ChatMessage], tools: &[Tool], round: u32) -> (Option<Response>, ModelTrace) {
+    let (primary, fallback) = models_for(requested);
+    let error = match call(primary, messages, tools).await {
+        Ok(resp) => match malformed(&resp, tools) {
+            None => return (Some(resp), ModelTrace { round, model: Some(primary), fallback: false, error: None }),
+            Some(e) => e,
+        },
+        Err(e) => e,
+    };
+    log!("[llm] round={} model={:?} rejected: {}", round, primary, error);
+    let Some(fallback) = fallback else {
+        return (None, ModelTrace { round, model: None, fallback: false, error: Some(error) });
+    };
+    match call(fallback, messages, tools).await {
+        // a malformed fallback reply is still used; unknown tools are reported back to the model
+        Ok(resp) => (Some(resp), ModelTrace { round, model: Some(fallback), fallback: true, error: Some(error) }),
+        Err(e) => {
+            log!("[llm] round={} fallback={:?} failed: {}", round, fallback, e);
+            (None, ModelTrace { round, model: None, fallback: true, error: Some(format!("{}; fallback: {}", error, e)) })
+        }
+    }
+}
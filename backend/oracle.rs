@@ -0,0 +1,117 @@
+use candid::{Nat, Principal};
+use num_bigint::BigUint;
+
+use crate::{log, CONFIG, PRICE_CACHE};
+use crate::types::{Price, PriceRequest};
+use crate::utils::scale_amount;
+
+/// Cached prices younger than this are used without asking the oracle again.
+pub const PRICE_CACHE_TTL_NS: u64 = 60 * 1_000_000_000;
+/// Prices observed longer ago than this are never shown or used for conversions.
+pub const PRICE_MAX_AGE_NS: u64 = 15 * 60 * 1_000_000_000;
+/// Fiat currencies plans are annotated with.
+pub const CURRENCIES: [&str; 2] = ["IDR", "USD"];
+
+/// Configured price oracle canister, if any.
+pub fn oracle_canister() -> Option<Principal> {
+    CONFIG.with(|c| c.borrow().oracle_canister)
+}
+
+/// Store a price fetched at `now`.
+pub fn cache_price(symbol: &str, currency: &str, price: Price, now: u64) {
+    PRICE_CACHE.with(|m| { m.borrow_mut().insert((symbol.to_string(), currency.to_string()), (price, now)); });
+}
+
+/// Cached rate (fiat per whole token, 1e8 scaled) if the price is not older than [`PRICE_MAX_AGE_NS`].
+pub fn cached_rate(symbol: &str, currency: &str, now: u64) -> Option<u64> {
+    PRICE_CACHE.with(|m| {
+        let m = m.borrow();
+        let (p, _) = m.get(&(symbol.to_string(), currency.to_string()))?;
+        (now.saturating_sub(p.timestamp) <= PRICE_MAX_AGE_NS).then_some(p.rate_e8)
+    })
+}
+
+/// Rate from the cache, refreshed from the oracle when older than [`PRICE_CACHE_TTL_NS`].
+pub async fn rate(symbol: &str, currency: &str, now: u64) -> Option<u64> {
+    let fetched_at = PRICE_CACHE.with(|m| m.borrow().get(&(symbol.to_string(), currency.to_string())).map(|(_, at)| *at));
+    if fetched_at.is_some_and(|at| now.saturating_sub(at) <= PRICE_CACHE_TTL_NS) {
+        return cached_rate(symbol, currency, now);
+    }
+    if let Some(oracle) = oracle_canister() {
+        let req = PriceRequest { symbol: symbol.to_string(), currency: currency.to_string() };
+        match ic_cdk::call::<(PriceRequest,), (Result<Price, String>,)>(oracle, "get_price", (req,)).await {
+            Ok((Ok(p),)) => cache_price(symbol, currency, p, now),
+            Ok((Err(e),)) => { log!("[oracle] {} {} error: {}", symbol, currency, e); }
+            Err((code, msg)) => { log!("[oracle] call failed: {:?} {}", code, msg); }
+        }
+    }
+    // on failure a previously cached price is still used while young enough
+    cached_rate(symbol, currency, now)
+}
+
+/// Fiat value (1e8 scaled) of `amount` minimal units at `rate_e8`.
+pub fn fiat_value_e8(amount: &Nat, decimals: u8, rate_e8: u64) -> Nat {
+    Nat(&amount.0 * BigUint::from(rate_e8) / BigUint::from(10u32).pow(decimals as u32))
+}
+
+/// Minimal token units worth `fiat_e8` (1e8 scaled) at `rate_e8`, rounded down.
+pub fn token_amount_for_fiat(fiat_e8: &Nat, decimals: u8, rate_e8: u64) -> Nat {
+    if rate_e8 == 0 { return Nat::from(0u32); }
+    Nat(&fiat_e8.0 * BigUint::from(10u32).pow(decimals as u32) / BigUint::from(rate_e8))
+}
+
+fn group_thousands(int: &str, sep: char) -> String {
+    let mut out = String::new();
+    for (i, c) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i).is_multiple_of(3) { out.push(sep); }
+        out.push(c);
+    }
+    out
+}
+
+/// Format a 1e8 scaled fiat value, e.g. "Rp 50.000" or "$3.10".
+pub fn format_fiat(value_e8: &Nat, currency: &str) -> String {
+    match currency {
+        "IDR" => {
+            let rupiah = (&value_e8.0 + BigUint::from(50_000_000u32)) / BigUint::from(100_000_000u32);
+            format!("Rp {}", group_thousands(&rupiah.to_str_radix(10), '.'))
+        }
+        _ => {
+            let cents = (&value_e8.0 + BigUint::from(500_000u32)) / BigUint::from(1_000_000u32);
+            let s = format!("{:0>3}", cents.to_str_radix(10));
+            let (int, frac) = s.split_at(s.len() - 2);
+            let sym = if currency == "USD" { "$".to_string() } else { format!("{} ", currency) };
+            format!("{}{}.{}", sym, group_thousands(int, ','), frac)
+        }
+    }
+}
+
+/// Parse a fiat amount such as "Rp 50.000", "IDR 50000", "$5.50" or "5 USD".
+///
+/// Returns the currency and the value scaled by 1e8; rupiah use "." for thousands and "," for decimals.
+pub fn parse_fiat(raw: &str) -> Option<(&'static str, Nat)> {
+    let lower = raw.trim().to_ascii_lowercase();
+    let (currency, rest) = [("rp", "IDR"), ("idr", "IDR"), ("$", "USD"), ("usd", "USD")].iter()
+        .find_map(|(p, c)| lower.strip_prefix(p).map(|r| (*c, r)))
+        .or_else(|| [("rupiah", "IDR"), ("idr", "IDR"), ("usd", "USD")].iter()
+            .find_map(|(p, c)| lower.strip_suffix(p).map(|r| (*c, r))))?;
+    let num = rest.trim().trim_start_matches('.').trim();
+    let normalized = if currency == "IDR" { num.replace('.', "").replace(',', ".") } else { num.replace(',', "") };
+    scale_amount(&normalized, 8).ok().map(|v| (currency, v))
+}
+
+/// Annotation such as "≈ Rp 50.000 / $3.10" from cached prices only.
+pub fn annotation_cached(symbol: &str, amount: &Nat, decimals: u8, now: u64) -> Option<String> {
+    let parts: Vec<String> = CURRENCIES.iter()
+        .filter_map(|c| cached_rate(symbol, c, now).map(|r| format_fiat(&fiat_value_e8(amount, decimals, r), c)))
+        .collect();
+    (!parts.is_empty()).then(|| format!("≈ {}", parts.join(" / ")))
+}
+
+/// Annotation such as "≈ Rp 50.000 / $3.10", refreshing prices from the oracle.
+pub async fn annotation(symbol: &str, amount: &Nat, decimals: u8, now: u64) -> Option<String> {
+    for c in CURRENCIES {
+        rate(symbol, c, now).await;
+    }
+    annotation_cached(symbol, amount, decimals, now)
+}
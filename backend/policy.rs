@@ -0,0 +1,131 @@
+use candid::{Nat, Principal};
+use futures::future::LocalBoxFuture;
+use serde_json::Value;
+
+use crate::{PENDING_SPENDS, POLICIES, RECEIPTS};
+use crate::tools::{Permission, Tool, ToolCtx};
+use crate::types::{PolicyViolation, Receipt, SpendingPolicy, TransferPlan};
+use crate::utils::NANOS_PER_DAY;
+
+/// Validate a policy before storing it.
+pub fn validate(p: &SpendingPolicy) -> Result<(), String> {
+    if let Some(q) = &p.quiet_hours {
+        if q.start_hour > 23 || q.end_hour > 23 { return Err("quiet hours must be 0-23".into()); }
+        if q.start_hour == q.end_hour { return Err("quiet hours window is empty".into()); }
+        if q.utc_offset_minutes.abs() > 14 * 60 { return Err("utc offset out of range".into()); }
+    }
+    if p.allowed_recipients_only && p.allowed_recipients.is_empty() {
+        return Err("allowed recipients list is empty".into());
+    }
+    Ok(())
+}
+
+/// Validate and store the policy of `owner`.
+pub fn set(owner: Principal, p: SpendingPolicy) -> Result<(), String> {
+    validate(&p)?;
+    POLICIES.with(|m| m.borrow_mut().insert(owner, p));
+    Ok(())
+}
+
+/// All stored policies, as saved across upgrades.
+pub fn snapshot() -> Vec<(Principal, SpendingPolicy)> {
+    POLICIES.with(|m| m.borrow().iter().map(|(k, v)| (*k, v.clone())).collect())
+}
+
+/// Replace the stored policies with a [`snapshot`].
+pub fn restore(policies: Vec<(Principal, SpendingPolicy)>) {
+    POLICIES.with(|m| *m.borrow_mut() = policies.into_iter().collect());
+}
+
+/// Local hour of day for a nanosecond timestamp and UTC offset.
+pub fn local_hour(now: u64, utc_offset_minutes: i16) -> u8 {
+    let secs = (now / 1_000_000_000) as i64 + utc_offset_minutes as i64 * 60;
+    (secs.rem_euclid(86_400) / 3_600) as u8
+}
+
+/// Sum of `receipts` in `symbol` executed after `since`, optionally only to `to`.
+pub fn spent_since(receipts: &[Receipt], symbol: &str, to: Option<Principal>, since: u64) -> Nat {
+    receipts.iter()
+        .filter(|r| r.symbol == symbol && r.executed_at > since && to.is_none_or(|t| r.to_principal == t))
+        .fold(Nat::from(0u32), |acc, r| acc + r.amount.clone())
+}
+
+/// Evaluate `plan` against `policy` given the owner's previous `receipts`.
+pub fn check(policy: &SpendingPolicy, receipts: &[Receipt], plan: &TransferPlan, now: u64) -> Result<(), PolicyViolation> {
+    if let Some(q) = &policy.quiet_hours {
+        let h = local_hour(now, q.utc_offset_minutes);
+        let quiet = if q.start_hour < q.end_hour {
+            h >= q.start_hour && h < q.end_hour
+        } else {
+            h >= q.start_hour || h < q.end_hour
+        };
+        if quiet {
+            return Err(PolicyViolation::QuietHours { start_hour: q.start_hour, end_hour: q.end_hour });
+        }
+    }
+    if policy.allowed_recipients_only && !policy.allowed_recipients.contains(&plan.to_principal) {
+        return Err(PolicyViolation::RecipientNotAllowed);
+    }
+
+    let day_ago = now.saturating_sub(NANOS_PER_DAY);
+    let week_ago = now.saturating_sub(7 * NANOS_PER_DAY);
+    for l in policy.token_limits.iter().filter(|l| l.symbol.eq_ignore_ascii_case(&plan.symbol)) {
+        if let Some(max) = &l.per_tx_max {
+            if plan.amount > *max { return Err(PolicyViolation::PerTxMax { limit: max.clone() }); }
+        }
+        if let Some(cap) = &l.daily_cap {
+            let spent = spent_since(receipts, &plan.symbol, None, day_ago);
+            if spent.clone() + plan.amount.clone() > *cap {
+                return Err(PolicyViolation::DailyCap { limit: cap.clone(), spent });
+            }
+        }
+        if let Some(cap) = &l.weekly_cap {
+            let spent = spent_since(receipts, &plan.symbol, None, week_ago);
+            if spent.clone() + plan.amount.clone() > *cap {
+                return Err(PolicyViolation::WeeklyCap { limit: cap.clone(), spent });
+            }
+        }
+    }
+    for c in policy.recipient_caps.iter()
+        .filter(|c| c.recipient == plan.to_principal && c.symbol.eq_ignore_ascii_case(&plan.symbol))
+    {
+        let spent = spent_since(receipts, &plan.symbol, Some(plan.to_principal), day_ago);
+        if spent.clone() + plan.amount.clone() > c.daily_cap {
+            return Err(PolicyViolation::RecipientCap { limit: c.daily_cap.clone(), spent });
+        }
+    }
+    Ok(())
+}
+
+/// Evaluate `plan` against the stored policy, receipts and pending spends of `owner`.
+pub fn check_owner(owner: Principal, plan: &TransferPlan, now: u64) -> Result<(), PolicyViolation> {
+    let Some(policy) = POLICIES.with(|m| m.borrow().get(&owner).cloned()) else { return Ok(()); };
+    let mut receipts = RECEIPTS.with(|m| m.borrow().get(&owner).cloned()).unwrap_or_default();
+    PENDING_SPENDS.with(|m| receipts.extend(m.borrow().values().filter(|r| r.owner == owner).cloned()));
+    check(&policy, &receipts, plan, now)
+}
+
+/// Count a transfer toward its owner's caps until [`release`], while its ledger call is in flight.
+pub fn reserve(spend: Receipt) {
+    PENDING_SPENDS.with(|m| { m.borrow_mut().insert(spend.checksum.clone(), spend); });
+}
+
+/// Drop the pending spend of `checksum` once its ledger call returned.
+pub fn release(checksum: &str) {
+    PENDING_SPENDS.with(|m| { m.borrow_mut().remove(checksum); });
+}
+
+/// `get_policy`.
+pub struct GetPolicyTool;
+
+impl Tool for GetPolicyTool {
+    fn name(&self) -> &'static str { "get_policy" }
+    fn description(&self) -> Option<&'static str> { Some("Read the user's spending policy (read-only; amounts in minimal units).") }
+    fn permission(&self) -> Permission { Permission::Read }
+    fn call<'a>(&'a self, _args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let p = POLICIES.with(|m| m.borrow().get(&ctx.caller).cloned()).unwrap_or_default();
+            Ok(serde_json::to_value(&p).unwrap())
+        })
+    }
+}
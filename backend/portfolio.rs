@@ -0,0 +1,85 @@
+use candid::{Nat, Principal};
+use futures::future::{join_all, LocalBoxFuture};
+use icrc_ledger_types::icrc1::account::Account as IcrcAccount;
+use icrc_ledger_types::icrc2::allowance::Allowance;
+use serde_json::{json, Value};
+
+use crate::{icrc1_balance_of, icrc2_allowance, log, oracle};
+use crate::tools::{Permission, Tool, ToolCtx};
+use crate::types::{Portfolio, TokenBalance, TokenEntry, TOKENS};
+use crate::utils::format_amount;
+
+/// Build the portfolio entry of `token` from the ledger replies.
+pub fn entry(
+    token: &TokenEntry,
+    ledger: Option<Principal>,
+    balance: Result<Nat, String>,
+    allowance: Result<Allowance, String>,
+    now: u64,
+) -> TokenBalance {
+    let mut errors = Vec::new();
+    let balance = balance.map_err(|e| errors.push(format!("balance: {}", e))).ok();
+    let allowance = allowance.map_err(|e| errors.push(format!("allowance: {}", e))).ok();
+    // an expired allowance cannot be spent, so it counts as zero
+    let (allowance, allowance_expires_at) = match allowance {
+        Some(a) if a.expires_at.is_some_and(|e| e <= now) => (Some(Nat::from(0u32)), None),
+        Some(a) => (Some(a.allowance), a.expires_at),
+        None => (None, None),
+    };
+    TokenBalance {
+        symbol: token.symbol.to_string(),
+        ledger,
+        decimals: token.decimals,
+        balance_dec: balance.as_ref().map(|b| format_amount(b, token.decimals)),
+        fiat_value: balance.as_ref().and_then(|b| oracle::annotation_cached(token.symbol, b, token.decimals, now)),
+        balance,
+        allowance,
+        allowance_expires_at,
+        errors,
+    }
+}
+
+/// One-line summary such as "CFXN 120 (≈ Rp 48.000), ICP unavailable".
+pub fn describe(tokens: &[TokenBalance]) -> String {
+    let parts: Vec<String> = tokens.iter().map(|t| match (&t.balance_dec, &t.fiat_value) {
+        (Some(b), Some(f)) => format!("{} {} ({})", t.symbol, b, f),
+        (Some(b), None) => format!("{} {}", t.symbol, b),
+        (None, _) => format!("{} unavailable", t.symbol),
+    }).collect();
+    format!("Balances: {}.", parts.join(", "))
+}
+
+async fn fetch_token(token: &TokenEntry, owner: Principal, spender: Principal, now: u64) -> TokenBalance {
+    let Ok(ledger) = Principal::from_text(token.ledger) else {
+        let err = "ledger id invalid".to_string();
+        return entry(token, None, Err(err.clone()), Err(err), now);
+    };
+    let account = IcrcAccount { owner, subaccount: None };
+    let (balance, allowance) = futures::join!(icrc1_balance_of(ledger, account), icrc2_allowance(ledger, account, spender));
+    if let Ok(b) = &balance {
+        oracle::annotation(token.symbol, b, token.decimals, now).await;
+    }
+    entry(token, Some(ledger), balance, allowance, now)
+}
+
+/// Query every allowlisted ledger concurrently for `owner`'s balance and allowance to `spender`.
+pub async fn fetch(owner: Principal, spender: Principal, now: u64) -> Portfolio {
+    let tokens = join_all(TOKENS.iter().map(|t| fetch_token(t, owner, spender, now))).await;
+    Portfolio { owner, human_readable: describe(&tokens), tokens }
+}
+
+/// `get_portfolio`.
+pub struct GetPortfolioTool;
+
+impl Tool for GetPortfolioTool {
+    fn name(&self) -> &'static str { "get_portfolio" }
+    fn description(&self) -> Option<&'static str> { Some("Read the user's balance and approved allowance for every supported token.") }
+    fn permission(&self) -> Permission { Permission::Read }
+    fn call<'a>(&'a self, _args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let p = fetch(ctx.caller, ic_cdk::api::id(), ctx.now).await;
+            log!("[get_portfolio] {}", p.human_readable);
+            Ok(json!({"status":"ok","summary":p.human_readable,"tokens":p.tokens}))
+        })
+    }
+}
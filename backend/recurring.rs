@@ -0,0 +1,315 @@
+use candid::Principal;
+use futures::future::LocalBoxFuture;
+use ic_llm::ChatMessage;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::{build_plan, cosign, execute_plan, log, NEXT_RECURRING_ID, RECURRING, RECURRING_TIMERS};
+use crate::tools::{id_arg, parse_args, Param, Permission, Tool, ToolArgs, ToolCtx};
+use crate::types::{Frequency, PlanArgs, RecurringArgs, RecurringStatus, RecurringTransfer};
+use crate::utils::{add_months_ns, format_date_ns, parse_date_ns, plan_checksum, NANOS_PER_DAY};
+
+/// Time of occurrence `n` of a series anchored at `anchor`.
+pub fn occurrence_at(anchor: u64, freq: Frequency, n: u32) -> u64 {
+    match freq {
+        Frequency::Daily   => anchor + n as u64 * NANOS_PER_DAY,
+        Frequency::Weekly  => anchor + n as u64 * 7 * NANOS_PER_DAY,
+        Frequency::Monthly => add_months_ns(anchor, n),
+    }
+}
+
+/// First slot after `slot` whose time is strictly after `now`; missed slots are skipped, not caught up.
+pub fn next_slot_after(anchor: u64, freq: Frequency, slot: u32, now: u64) -> (u32, u64) {
+    let mut n = slot + 1;
+    let mut at = occurrence_at(anchor, freq, n);
+    while at <= now {
+        n += 1;
+        at = occurrence_at(anchor, freq, n);
+    }
+    (n, at)
+}
+
+/// Whether a series may not run another occurrence at `next_at`.
+pub fn is_finished(s: &RecurringTransfer, next_at: u64) -> bool {
+    s.max_count.is_some_and(|c| s.executed_count >= c) || s.end_at.is_some_and(|e| next_at > e)
+}
+
+/// Summary line describing a series.
+pub fn describe(s: &RecurringTransfer) -> String {
+    let freq = match s.frequency {
+        Frequency::Daily => "daily",
+        Frequency::Weekly => "weekly",
+        Frequency::Monthly => "monthly",
+    };
+    let status = match s.status {
+        RecurringStatus::Active => "active",
+        RecurringStatus::Paused => "paused",
+        RecurringStatus::Completed => "completed",
+        RecurringStatus::Cancelled => "cancelled",
+    };
+    format!("#{} {} {} {} to {} since {} ({})", s.id, freq, s.plan.amount, s.plan.symbol,
+        s.plan.to_principal.to_text(), format_date_ns(s.anchor_at), status)
+}
+
+/// Validate the arguments and register a new active series for `owner`.
+pub fn create(owner: Principal, a: RecurringArgs, now: u64) -> Result<RecurringTransfer, Value> {
+    let frequency = Frequency::parse(&a.frequency).ok_or_else(|| serde_json::json!({
+        "status":"err","code":"BadFrequency","field":"frequency","options":["daily","weekly","monthly"]
+    }))?;
+    let anchor_at = match a.start_date.as_deref() {
+        Some(d) => parse_date_ns(d)
+            .map_err(|e| serde_json::json!({"status":"err","code":"BadDate","field":"start_date","error":e,"example":"2025-01-31"}))?
+            .max(now),
+        None => now,
+    };
+    let end_at = match a.end_date.as_deref() {
+        Some(d) => Some(parse_date_ns(d)
+            .map_err(|e| serde_json::json!({"status":"err","code":"BadDate","field":"end_date","error":e,"example":"2025-12-31"}))?
+            + NANOS_PER_DAY - 1),
+        None => None,
+    };
+    if end_at.is_some_and(|e| e < anchor_at) || a.count == Some(0) {
+        return Err(serde_json::json!({"status":"err","code":"EmptySchedule","error":"schedule has no occurrences"}));
+    }
+
+    let plan_args = PlanArgs {
+        to: a.to, amount_dec: a.amount_dec, symbol: a.symbol,
+        ledger: None, decimals: None, memo: a.memo,
+    };
+    let plan = build_plan(&plan_args, owner, now)?;
+    // co-signers approve the series once, not every occurrence
+    cosign::open(&plan, None);
+
+    let id = NEXT_RECURRING_ID.with(|n| { let mut n = n.borrow_mut(); *n += 1; *n });
+    let series = RecurringTransfer {
+        id, owner, plan, frequency, anchor_at,
+        slot: 0,
+        next_run_at: anchor_at,
+        end_at,
+        max_count: a.count,
+        executed_count: 0,
+        status: RecurringStatus::Active,
+        last_block_index: None,
+        last_error: None,
+        failure_reported: true,
+    };
+    RECURRING.with(|m| { m.borrow_mut().insert(id, series.clone()); });
+    arm(id, anchor_at, now);
+    log!("[recurring] created {}", describe(&series));
+    Ok(series)
+}
+
+/// Cancel a series owned by `owner`.
+pub fn cancel(owner: Principal, id: u64) -> Result<RecurringTransfer, String> {
+    let s = update_owned(owner, id, |s| {
+        if matches!(s.status, RecurringStatus::Completed | RecurringStatus::Cancelled) {
+            return Err("series already finished".into());
+        }
+        s.status = RecurringStatus::Cancelled;
+        Ok(())
+    })?;
+    disarm(id);
+    Ok(s)
+}
+
+/// Resume a paused series; the failed occurrence is retried immediately.
+pub fn resume(owner: Principal, id: u64, now: u64) -> Result<RecurringTransfer, String> {
+    let s = update_owned(owner, id, |s| {
+        if s.status != RecurringStatus::Paused { return Err("series is not paused".into()); }
+        s.status = RecurringStatus::Active;
+        s.next_run_at = now;
+        s.last_error = None;
+        s.failure_reported = true;
+        Ok(())
+    })?;
+    arm(id, now, now);
+    Ok(s)
+}
+
+/// All series owned by `owner`.
+pub fn list(owner: Principal) -> Vec<RecurringTransfer> {
+    RECURRING.with(|m| m.borrow().values().filter(|s| s.owner == owner).cloned().collect())
+}
+
+/// Paused series of `owner` whose failure was not yet shown, marking them as reported.
+pub fn take_unreported_failures(owner: Principal) -> Vec<RecurringTransfer> {
+    RECURRING.with(|m| {
+        m.borrow_mut().values_mut()
+            .filter(|s| s.owner == owner && s.status == RecurringStatus::Paused && !s.failure_reported)
+            .map(|s| { s.failure_reported = true; s.clone() })
+            .collect()
+    })
+}
+
+/// System message telling the model about paused series.
+pub fn failure_notice(paused: &[RecurringTransfer]) -> Option<ChatMessage> {
+    if paused.is_empty() { return None; }
+    let lines: Vec<String> = paused.iter()
+        .map(|s| format!("- {}: {}", describe(s), s.last_error.as_deref().unwrap_or("unknown error")))
+        .collect();
+    Some(ChatMessage::System { content: format!(
+        "NOTICE: these recurring payments were PAUSED after a failed run. Tell the user briefly before anything else, \
+         suggest fixing the cause (e.g. raise the ICRC-2 allowance) and offer resume_recurring:\n{}",
+        lines.join("\n")
+    ) })
+}
+
+/// All series and the last issued id, as saved across upgrades.
+pub fn snapshot() -> (Vec<RecurringTransfer>, u64) {
+    let series = RECURRING.with(|m| m.borrow().values().cloned().collect());
+    (series, NEXT_RECURRING_ID.with(|n| *n.borrow()))
+}
+
+/// Replace the series and id counter with a [`snapshot`]; timers are armed by [`rearm_all`].
+pub fn restore((series, next_id): (Vec<RecurringTransfer>, u64)) {
+    RECURRING.with(|m| *m.borrow_mut() = series.into_iter().map(|s| (s.id, s)).collect());
+    NEXT_RECURRING_ID.with(|n| *n.borrow_mut() = next_id);
+}
+
+/// Arm a timer for every active series, e.g. after an upgrade dropped them.
+/// An overdue occurrence runs right away; [`next_slot_after`] then skips any other missed slot.
+pub fn rearm_all(now: u64) {
+    let due: Vec<(u64, u64)> = RECURRING.with(|m| {
+        m.borrow().values()
+            .filter(|s| s.status == RecurringStatus::Active)
+            .map(|s| (s.id, s.next_run_at.max(now)))
+            .collect()
+    });
+    for (id, at) in due {
+        arm(id, at, now);
+    }
+}
+
+fn update_owned<F>(owner: Principal, id: u64, f: F) -> Result<RecurringTransfer, String>
+where F: FnOnce(&mut RecurringTransfer) -> Result<(), String> {
+    RECURRING.with(|m| {
+        let mut m = m.borrow_mut();
+        let s = m.get_mut(&id).filter(|s| s.owner == owner).ok_or("series not found")?;
+        f(s)?;
+        Ok(s.clone())
+    })
+}
+
+fn arm(id: u64, at: u64, now: u64) {
+    disarm(id);
+    let delay = Duration::from_nanos(at.saturating_sub(now));
+    let timer = ic_cdk_timers::set_timer(delay, move || ic_cdk::spawn(run_occurrence(id)));
+    RECURRING_TIMERS.with(|t| { t.borrow_mut().insert(id, timer); });
+}
+
+fn disarm(id: u64) {
+    if let Some(t) = RECURRING_TIMERS.with(|t| t.borrow_mut().remove(&id)) {
+        ic_cdk_timers::clear_timer(t);
+    }
+}
+
+async fn run_occurrence(id: u64) {
+    RECURRING_TIMERS.with(|t| { t.borrow_mut().remove(&id); });
+    let Some(series) = RECURRING.with(|m| m.borrow().get(&id).cloned()) else { return; };
+    if series.status != RecurringStatus::Active { return; }
+
+    let now = ic_cdk::api::time();
+    let mut plan = series.plan.clone();
+    plan.created_at_time = now;
+    plan.approvals_required = 0;
+    plan.checksum = plan_checksum(&plan);
+    log!("[recurring] run #{} slot={} checksum={}", id, series.slot, plan.checksum);
+    let res = match cosign::check(&series.plan.checksum, series.plan.approvals_required) {
+        Ok(()) => execute_plan(&plan).await,
+        Err(e) => Err(e),
+    };
+
+    let rearm = RECURRING.with(|m| {
+        let mut m = m.borrow_mut();
+        let s = m.get_mut(&id)?;
+        let active = s.status == RecurringStatus::Active;
+        match res {
+            Ok(block_idx) => {
+                log!("[recurring] #{} OK block_index={}", id, block_idx);
+                s.executed_count += 1;
+                s.last_block_index = Some(block_idx);
+                let (slot, at) = next_slot_after(s.anchor_at, s.frequency, s.slot, now);
+                s.slot = slot;
+                s.next_run_at = at;
+                if active && is_finished(s, at) {
+                    s.status = RecurringStatus::Completed;
+                }
+            }
+            Err(e) => {
+                log!("[recurring] #{} failed: {}", id, e.detail());
+                s.last_error = Some(format!("{}: {}", e.code(), e.detail()));
+                if active {
+                    s.status = RecurringStatus::Paused;
+                    s.failure_reported = false;
+                }
+            }
+        }
+        (s.status == RecurringStatus::Active).then_some(s.next_run_at)
+    });
+    if let Some(at) = rearm {
+        arm(id, at, now);
+    }
+}
+
+/// `schedule_recurring`.
+pub struct ScheduleRecurringTool;
+
+impl Tool for ScheduleRecurringTool {
+    fn name(&self) -> &'static str { "schedule_recurring" }
+    fn description(&self) -> Option<&'static str> { Some("Schedule a recurring transfer after the user confirms the schedule.") }
+    fn params(&self) -> Vec<Param> { RecurringArgs::params() }
+    // the first payment runs right away when there is no start date
+    fn permission(&self) -> Permission { Permission::Execute }
+    fn confirmation(&self, args: &Value) -> Option<String> {
+        let field = |k: &str| args.get(k).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let mut s = format!("Send {} {} to {} {}", field("amount_dec"), field("symbol"), field("to"), field("frequency"));
+        if let Some(d) = args.get("start_date").and_then(|v| v.as_str()) { s.push_str(&format!(" from {}", d)); }
+        if let Some(d) = args.get("end_date").and_then(|v| v.as_str()) { s.push_str(&format!(" until {}", d)); }
+        if let Some(n) = args.get("count") { s.push_str(&format!(", {} times", n)); }
+        Some(s.replace("  ", " ") + ".")
+    }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let s = create(ctx.caller, parse_args(args)?, ctx.now)?;
+            Ok(json!({"status":"ok","id":s.id,"summary":describe(&s)}))
+        })
+    }
+}
+
+/// `list_recurring`.
+pub struct ListRecurringTool;
+
+impl Tool for ListRecurringTool {
+    fn name(&self) -> &'static str { "list_recurring" }
+    fn description(&self) -> Option<&'static str> { Some("List the user's recurring transfers") }
+    fn permission(&self) -> Permission { Permission::Read }
+    fn call<'a>(&'a self, _args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move { Ok(json!(list(ctx.caller).iter().map(describe).collect::<Vec<_>>())) })
+    }
+}
+
+/// `cancel_recurring` or, with `resume`, `resume_recurring`.
+pub struct ChangeRecurringTool {
+    pub resume: bool,
+}
+
+impl Tool for ChangeRecurringTool {
+    fn name(&self) -> &'static str { if self.resume { "resume_recurring" } else { "cancel_recurring" } }
+    fn description(&self) -> Option<&'static str> {
+        self.resume.then_some("Resume a paused recurring transfer; the failed payment is retried now.")
+    }
+    fn params(&self) -> Vec<Param> { vec![Param::number("id").required()] }
+    fn permission(&self) -> Permission { if self.resume { Permission::Execute } else { Permission::Write } }
+    fn confirmation(&self, args: &Value) -> Option<String> {
+        let id = id_arg(args).map_or_else(|_| "?".to_string(), |id| id.to_string());
+        Some(format!("Resume recurring transfer #{} and retry its failed payment now.", id))
+    }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let id = id_arg(&args)?;
+            let res = if self.resume { resume(ctx.caller, id, ctx.now) } else { cancel(ctx.caller, id) };
+            let s = res.map_err(|e| json!({"status":"err","code":"BadSeries","error":e}))?;
+            Ok(json!({"status":"ok","summary":describe(&s)}))
+        })
+    }
+}
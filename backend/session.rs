@@ -0,0 +1,97 @@
+use candid::Principal;
+use ic_llm::ChatMessage;
+
+use crate::{NEXT_SESSION_ID, SESSIONS};
+use crate::types::Session;
+use crate::utils::NANOS_PER_DAY;
+
+/// Sessions idle for longer than this are discarded.
+pub const SESSION_TTL_NS: u64 = NANOS_PER_DAY;
+/// Messages kept per session; older ones are dropped first.
+pub const MAX_SESSION_MESSAGES: usize = 50;
+/// Largest user message accepted, in bytes.
+pub const MAX_USER_TEXT_BYTES: usize = 2_000;
+/// Live sessions per caller; starting another evicts the least recently used.
+pub const MAX_SESSIONS_PER_CALLER: usize = 5;
+
+fn expired(s: &Session, now: u64) -> bool {
+    now.saturating_sub(s.updated_at) > SESSION_TTL_NS
+}
+
+/// Drop every expired session.
+pub fn purge_expired(now: u64) {
+    SESSIONS.with(|m| m.borrow_mut().retain(|_, s| !expired(s, now)));
+}
+
+/// Open a new session for `owner`.
+pub fn start(owner: Principal, now: u64) -> Session {
+    purge_expired(now);
+    let id = NEXT_SESSION_ID.with(|n| { let mut n = n.borrow_mut(); *n += 1; *n });
+    let session = Session { id, owner, messages: Vec::new(), pending_checksum: None, pending_in_last_turn: false, created_at: now, updated_at: now };
+    SESSIONS.with(|m| {
+        let mut m = m.borrow_mut();
+        let mut mine: Vec<(u64, u64)> = m.values().filter(|s| s.owner == owner).map(|s| (s.updated_at, s.id)).collect();
+        mine.sort();
+        for (_, old) in mine.iter().take((mine.len() + 1).saturating_sub(MAX_SESSIONS_PER_CALLER)) {
+            m.remove(old);
+        }
+        m.insert(id, session.clone());
+    });
+    session
+}
+
+/// Live session `id` of `owner`.
+pub fn get(owner: Principal, id: u64, now: u64) -> Result<Session, String> {
+    match SESSIONS.with(|m| m.borrow().get(&id).cloned()) {
+        Some(s) if s.owner == owner && !expired(&s, now) => Ok(s),
+        Some(s) if s.owner == owner => Err(format!("session {} expired; start a new one", id)),
+        _ => Err(format!("unknown session {}", id)),
+    }
+}
+
+/// Validate a user message before it enters a session.
+pub fn validate_text(text: &str) -> Result<(), String> {
+    if text.trim().is_empty() {
+        return Err("message is empty".into());
+    }
+    if text.len() > MAX_USER_TEXT_BYTES {
+        return Err(format!("message longer than {} bytes", MAX_USER_TEXT_BYTES));
+    }
+    Ok(())
+}
+
+/// Drop the oldest messages beyond [`MAX_SESSION_MESSAGES`].
+pub fn trim(messages: &mut Vec<ChatMessage>) {
+    let mut excess = messages.len().saturating_sub(MAX_SESSION_MESSAGES);
+    // never keep a tool result whose call was dropped
+    while excess < messages.len() && matches!(messages[excess], ChatMessage::Tool { .. }) {
+        excess += 1;
+    }
+    messages.drain(..excess);
+}
+
+/// Append the messages of one turn to session `id` and record what awaits confirmation,
+/// and whether this turn presented it.
+pub fn append(id: u64, new: Vec<ChatMessage>, pending_checksum: Option<String>, presented: bool, now: u64) {
+    SESSIONS.with(|m| {
+        if let Some(s) = m.borrow_mut().get_mut(&id) {
+            s.messages.extend(new);
+            trim(&mut s.messages);
+            s.pending_in_last_turn = presented && pending_checksum.is_some();
+            s.pending_checksum = pending_checksum;
+            s.updated_at = now;
+        }
+    });
+}
+
+/// Clear `checksum` as the awaited confirmation in every session of `owner`.
+pub fn forget_pending(owner: Principal, checksum: &str) {
+    SESSIONS.with(|m| {
+        for s in m.borrow_mut().values_mut().filter(|s| s.owner == owner) {
+            if s.pending_checksum.as_deref() == Some(checksum) {
+                s.pending_checksum = None;
+                s.pending_in_last_turn = false;
+            }
+        }
+    });
+}
@@ -0,0 +1,158 @@
+use candid::{Nat, Principal};
+use icrc_ledger_types::icrc1::account::Account as IcrcAccount;
+use icrc_ledger_types::icrc2::allowance::Allowance;
+use futures::future::LocalBoxFuture;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::{
+    amend, cosign, escrow, icrc1_balance_of, icrc1_fee, icrc2_allowance, invoice, log, lookup_pending, policy, simulate_pending,
+    EXECUTED_CHECKSUMS, LAST_PLAN_BY_CALLER,
+};
+use crate::tools::{Param, Permission, Tool, ToolCtx};
+use crate::types::{CheckStatus, SimCheck, SimulationReport, TransferPlan, TOKENS};
+use crate::utils::{format_date_ns, is_placeholder, PLAN_TTL_NS};
+
+/// Ledger data a simulation is evaluated against; `Err` when the ledger could not be queried.
+pub struct LedgerView {
+    pub fee: Result<Nat, String>,
+    pub balance: Result<Nat, String>,
+    pub allowance: Result<Allowance, String>,
+}
+
+fn check(name: &str, ok: bool, detail: String) -> SimCheck {
+    let status = if ok { CheckStatus::Pass } else { CheckStatus::Fail };
+    SimCheck { name: name.into(), status, detail }
+}
+
+fn unknown(name: &str, err: &str) -> SimCheck {
+    SimCheck { name: name.into(), status: CheckStatus::Unknown, detail: err.into() }
+}
+
+/// Evaluate `plan` against local state and the given ledger view at `now`.
+pub fn evaluate(plan: &TransferPlan, ledger: &LedgerView, now: u64) -> SimulationReport {
+    evaluate_all(std::slice::from_ref(plan), std::slice::from_ref(ledger), now).remove(0)
+}
+
+/// Evaluate the transfers of one plan, each with its ledger view; balance and allowance
+/// must cover the amounts and fees of all transfers on the same ledger together.
+pub fn evaluate_all(legs: &[TransferPlan], views: &[LedgerView], now: u64) -> Vec<SimulationReport> {
+    let mut totals: BTreeMap<Principal, Nat> = BTreeMap::new();
+    for (leg, view) in legs.iter().zip(views) {
+        let fee = view.fee.clone().unwrap_or_else(|_| Nat::from(0u32));
+        *totals.entry(leg.ledger).or_insert_with(|| Nat::from(0u32)) += leg.amount.clone() + fee;
+    }
+    legs.iter().zip(views).map(|(leg, view)| evaluate_leg(leg, view, &totals[&leg.ledger], now)).collect()
+}
+
+fn evaluate_leg(plan: &TransferPlan, ledger: &LedgerView, total: &Nat, now: u64) -> SimulationReport {
+    let mut checks = Vec::new();
+
+    let listed = TOKENS.iter().any(|t| t.symbol == plan.symbol && Principal::from_text(t.ledger).ok() == Some(plan.ledger));
+    checks.push(check("allowlist", listed, format!("{} on {}", plan.symbol, plan.ledger.to_text())));
+
+    let expires_at = plan.created_at_time + PLAN_TTL_NS;
+    checks.push(check("expiry", now <= expires_at, format!("plan valid until {}", format_date_ns(expires_at))));
+
+    let executed = EXECUTED_CHECKSUMS.with(|s| s.borrow().contains(&plan.checksum));
+    let current = amend::check_current(&plan.checksum).err().map(|e| e.detail());
+    checks.push(match (executed, current) {
+        (true, _) => check("duplicate", false, "plan already executed".into()),
+        (_, Some(d)) => check("duplicate", false, d),
+        _ => check("duplicate", true, "not executed yet".into()),
+    });
+
+    let needed = match &ledger.fee {
+        Ok(fee) => {
+            checks.push(check("fee", true, format!("ledger fee {}", fee)));
+            plan.amount.clone() + fee.clone()
+        }
+        Err(e) => { checks.push(unknown("fee", e)); plan.amount.clone() }
+    };
+    let required = total.clone();
+    let scope = if *total == needed { "" } else { " for all transfers" };
+    checks.push(match &ledger.balance {
+        Ok(b) => check("balance", *b >= required, format!("balance {} needs {}{}", b, required, scope)),
+        Err(e) => unknown("balance", e),
+    });
+    checks.push(match &ledger.allowance {
+        Ok(a) if a.expires_at.is_some_and(|e| e <= now) => check("allowance", false, "allowance expired".into()),
+        Ok(a) => check("allowance", a.allowance >= required, format!("allowance {} needs {}{}", a.allowance, required, scope)),
+        Err(e) => unknown("allowance", e),
+    });
+
+    checks.push(match plan.from_owner.map(|o| policy::check_owner(o, plan, now)) {
+        Some(Ok(())) => check("policy", true, "within limits".into()),
+        Some(Err(v)) => check("policy", false, format!("{}: {}", v.code(), v.detail())),
+        None => check("policy", false, "missing from_owner".into()),
+    });
+    if plan.approvals_required > 0 {
+        checks.push(match cosign::check(&plan.checksum, plan.approvals_required) {
+            Ok(()) => check("approvals", true, "co-signer quorum reached".into()),
+            Err(e) => check("approvals", false, e.detail()),
+        });
+    }
+    if let Some(id) = plan.invoice_id {
+        checks.push(match invoice::check_open(id) {
+            Ok(()) => check("invoice", true, format!("invoice #{} open", id)),
+            Err(e) => check("invoice", false, e.detail()),
+        });
+    }
+    if let Some(id) = plan.escrow_id {
+        checks.push(match escrow::check_awaiting(id) {
+            Ok(()) => check("escrow", true, format!("escrow #{} awaiting funds", id)),
+            Err(e) => check("escrow", false, e.detail()),
+        });
+    }
+
+    SimulationReport {
+        checksum: plan.checksum.clone(),
+        ok: checks.iter().all(|c| c.status == CheckStatus::Pass),
+        checks,
+    }
+}
+
+/// Query the ledger for the fee, balance and allowance relevant to `plan`.
+pub async fn fetch_view(plan: &TransferPlan, spender: Principal) -> LedgerView {
+    let owner = IcrcAccount { owner: plan.from_owner.unwrap_or(Principal::anonymous()), subaccount: plan.from_sub };
+    let fee = icrc1_fee(plan.ledger).await;
+    let balance = icrc1_balance_of(plan.ledger, owner).await;
+    let allowance = icrc2_allowance(plan.ledger, owner, spender).await;
+    LedgerView { fee, balance, allowance }
+}
+
+/// Dry-run the transfers of one plan without calling `icrc2_transfer_from`.
+pub async fn run(legs: &[TransferPlan]) -> Vec<SimulationReport> {
+    let mut views = Vec::with_capacity(legs.len());
+    for leg in legs {
+        views.push(fetch_view(leg, ic_cdk::api::id()).await);
+    }
+    evaluate_all(legs, &views, ic_cdk::api::time())
+}
+
+/// `simulate_transfer`: dry-run a plan.
+pub struct SimulateTransferTool;
+
+impl Tool for SimulateTransferTool {
+    fn name(&self) -> &'static str { "simulate_transfer" }
+    fn description(&self) -> Option<&'static str> {
+        Some("Dry-run a plan (allowlist, balance, allowance, fee, policy, expiry, duplicate) without sending anything.")
+    }
+    fn params(&self) -> Vec<Param> { vec![Param::string("checksum")] }
+    fn permission(&self) -> Permission { Permission::Read }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let pending = match args.get("checksum").and_then(|v| v.as_str()).filter(|cs| !is_placeholder(cs)) {
+                Some(cs) => lookup_pending(cs),
+                None => LAST_PLAN_BY_CALLER.with(|m| m.borrow().get(&ctx.caller).cloned()),
+            };
+            let Some(pending) = pending.filter(|p| p.owner() == Some(ctx.caller)) else {
+                return Err(json!({"status":"err","code":"UnknownPlan"}));
+            };
+            let reports = simulate_pending(&pending).await;
+            let ok = reports.iter().all(|r| r.ok);
+            log!("[simulate_transfer] checksum={} ok={}", pending.checksum(), ok);
+            Ok(json!({"status": if ok { "ok" } else { "fail" }, "reports": reports}))
+        })
+    }
+}
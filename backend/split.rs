@@ -0,0 +1,163 @@
+use candid::{Nat, Principal};
+use futures::future::LocalBoxFuture;
+use num_bigint::BigUint;
+use serde_json::{json, Value};
+
+use crate::{build_plan, cosign, log, BATCH_BY_CHECKSUM, LAST_PLAN_BY_CALLER};
+use crate::tools::{parse_args, Param, Permission, Tool, ToolArgs, ToolCtx};
+use crate::types::{BatchPlan, PendingPlan, PlanArgs, SplitArgs};
+use crate::utils::{batch_checksum, format_amount, resolve_token, scale_amount, token_symbols, PLAN_TTL_NS};
+
+/// Precision used when parsing fractional weights and percentages.
+const WEIGHT_DECIMALS: u8 = 6;
+
+/// Split a comma separated list, dropping empty entries.
+pub fn split_list(raw: &str) -> Vec<String> {
+    raw.split([',', ';']).map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()
+}
+
+/// Whether a recipient entry refers to the caller.
+pub fn is_self(entry: &str, caller: Principal) -> bool {
+    let e = entry.trim().to_ascii_lowercase();
+    matches!(e.as_str(), "me" | "myself" | "i" | "self" | "saya" | "aku" | "gue")
+        || Principal::from_text(entry.trim()).is_ok_and(|p| p == caller)
+}
+
+/// Parse weights (or percentages summing to 100) into integer weights.
+pub fn parse_weights(raw: &str, n: usize, percent: bool) -> Result<Vec<Nat>, String> {
+    let parts = split_list(raw);
+    if parts.len() != n {
+        return Err(format!("expected {} weights, got {}", n, parts.len()));
+    }
+    let mut out = Vec::with_capacity(n);
+    for p in parts {
+        let w = scale_amount(p.trim_end_matches('%').trim(), WEIGHT_DECIMALS)
+            .map_err(|_| format!("invalid weight '{}'", p))?;
+        out.push(w);
+    }
+    let sum = out.iter().fold(Nat::from(0u32), |a, w| a + w.clone());
+    if sum == 0u32 {
+        return Err("weights must not all be zero".into());
+    }
+    if percent && sum != 100u64 * 10u64.pow(WEIGHT_DECIMALS as u32) {
+        return Err("percentages must add up to 100".into());
+    }
+    Ok(out)
+}
+
+/// Split `total` proportionally to `weights`.
+///
+/// The indivisible remainder is handed out one unit at a time to the largest
+/// fractional parts, earlier entries first on ties, so the result is deterministic.
+pub fn allocate(total: &Nat, weights: &[Nat]) -> Vec<Nat> {
+    let sum: BigUint = weights.iter().map(|w| w.0.clone()).sum();
+    if sum == BigUint::ZERO {
+        return weights.iter().map(|_| Nat::from(0u32)).collect();
+    }
+    let mut shares: Vec<BigUint> = Vec::with_capacity(weights.len());
+    let mut fracs: Vec<(BigUint, usize)> = Vec::with_capacity(weights.len());
+    for (i, w) in weights.iter().enumerate() {
+        let scaled = &total.0 * &w.0;
+        shares.push(&scaled / &sum);
+        fracs.push((scaled % &sum, i));
+    }
+    let allocated: BigUint = shares.iter().sum();
+    let mut remainder = &total.0 - allocated;
+    fracs.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    for (_, i) in fracs {
+        if remainder == BigUint::ZERO { break; }
+        shares[i] += 1u32;
+        remainder -= 1u32;
+    }
+    shares.into_iter().map(Nat::from).collect()
+}
+
+/// Build a batch plan paying every non-caller participant its share.
+pub fn plan(caller: Principal, a: &SplitArgs, created: u64) -> Result<BatchPlan, Value> {
+    let people = split_list(&a.recipients);
+    if people.is_empty() {
+        return Err(json!({"status":"err","code":"NeedRecipients","field":"recipients","example":"alice, bob, me"}));
+    }
+    let (_, decimals, symbol) = resolve_token(a.symbol.as_deref(), None)
+        .map_err(|_| json!({"status":"err","code":"BadToken","field":"symbol","options": token_symbols()}))?;
+    let total = scale_amount(&a.total_dec, decimals)
+        .map_err(|e| json!({"status":"err","code":"BadAmount","field":"total_dec","error":e}))?;
+
+    let mode = a.mode.as_deref().map(|m| m.trim().to_ascii_lowercase())
+        .unwrap_or_else(|| if a.weights.is_some() { "weights".into() } else { "equal".into() });
+    let weights = match (mode.as_str(), a.weights.as_deref()) {
+        ("equal", _) => vec![Nat::from(1u32); people.len()],
+        ("weights", Some(w)) => parse_weights(w, people.len(), false)
+            .map_err(|e| json!({"status":"err","code":"BadWeights","field":"weights","error":e,"example":"2,1,1"}))?,
+        ("percent" | "percentage", Some(w)) => parse_weights(w, people.len(), true)
+            .map_err(|e| json!({"status":"err","code":"BadWeights","field":"weights","error":e,"example":"50%,25%,25%"}))?,
+        _ => return Err(json!({"status":"err","code":"BadMode","field":"mode","options":["equal","weights","percent"]})),
+    };
+    let shares = allocate(&total, &weights);
+    log!("[plan_split] total={} shares={:?}", total, shares);
+
+    let mut legs = Vec::new();
+    let mut parts = Vec::new();
+    let mut own = Nat::from(0u32);
+    for (i, (who, share)) in people.iter().zip(shares).enumerate() {
+        if is_self(who, caller) {
+            own += share;
+            continue;
+        }
+        if share == 0u32 { continue; }
+        let args = PlanArgs {
+            to: who.clone(),
+            amount_dec: format_amount(&share, decimals),
+            symbol: Some(symbol.clone()),
+            ledger: None,
+            decimals: None,
+            memo: a.memo.clone(),
+        };
+        // distinct created_at_time keeps legs to the same recipient from colliding
+        let leg = build_plan(&args, caller, created + i as u64)?;
+        parts.push(format!("{} to {}", args.amount_dec, who));
+        legs.push(leg);
+    }
+    if legs.is_empty() {
+        return Err(json!({"status":"err","code":"NothingToSend","error":"no share goes to anyone but the caller"}));
+    }
+
+    let mut human_readable = format!("Split {} {}: send {}.", format_amount(&total, decimals), symbol, parts.join(", "));
+    if own > 0u32 {
+        human_readable.push_str(&format!(" Your share {} stays with you.", format_amount(&own, decimals)));
+    }
+    Ok(BatchPlan {
+        owner: caller,
+        checksum: batch_checksum(&legs),
+        legs,
+        symbol,
+        total,
+        human_readable,
+        created_at_time: created,
+    })
+}
+
+/// `plan_split`: split an amount between several people.
+pub struct PlanSplitTool;
+
+impl Tool for PlanSplitTool {
+    fn name(&self) -> &'static str { "plan_split" }
+    fn description(&self) -> Option<&'static str> {
+        Some("Split an amount between several people (equal, weights or percent). Returns one BatchPlan+checksum.")
+    }
+    fn params(&self) -> Vec<Param> { SplitArgs::params() }
+    fn permission(&self) -> Permission { Permission::Plan }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let a: SplitArgs = parse_args(args)?;
+            let batch = plan(ctx.caller, &a, ctx.now)?;
+            for leg in &batch.legs {
+                cosign::open(leg, Some(leg.created_at_time + PLAN_TTL_NS));
+            }
+            log!("[plan_split] batch checksum={} human='{}'", batch.checksum, batch.human_readable);
+            LAST_PLAN_BY_CALLER.with(|m| { m.borrow_mut().insert(ctx.caller, PendingPlan::Batch(batch.clone())); });
+            BATCH_BY_CHECKSUM.with(|m| { m.borrow_mut().insert(batch.checksum.clone(), batch.clone()); });
+            Ok(serde_json::to_value(&batch).unwrap())
+        })
+    }
+}
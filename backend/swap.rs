@@ -0,0 +1,198 @@
+use candid::{Nat, Principal};
+use futures::future::LocalBoxFuture;
+use icrc_ledger_types::icrc1::account::Account as IcrcAccount;
+use serde_json::{json, Value};
+
+use crate::{build_plan, cosign, execute_plan, log, CONFIG, LAST_PLAN_BY_CALLER, RECEIPTS, SWAP_BY_CHECKSUM};
+use crate::tools::{parse_args, Param, Permission, Tool, ToolArgs, ToolCtx};
+use crate::types::{
+    DexQuote, DexQuoteArgs, DexSwapArgs, DexSwapResult, DexWithdrawArgs, ExecError, PendingPlan, PlanArgs, SwapFill, SwapPlan, SwapPlanArgs,
+};
+use crate::utils::{
+    format_amount, plan_checksum, resolve_token, scale_amount, swap_checksum, swap_deposit_subaccount, token_symbols, PLAN_TTL_NS,
+};
+
+/// Slippage bound used when the user gives none (0.5%).
+pub const DEFAULT_SLIPPAGE_BPS: u32 = 50;
+/// Largest slippage bound accepted (50%).
+pub const MAX_SLIPPAGE_BPS: u32 = 5_000;
+/// Price impact above which the summary warns the user (5%).
+pub const HIGH_IMPACT_BPS: u32 = 500;
+
+/// Configured DEX canister, if any.
+pub fn dex_canister() -> Option<Principal> {
+    CONFIG.with(|c| c.borrow().dex_canister)
+}
+
+/// Parse a slippage percentage such as "0.5" or "1%" into basis points.
+pub fn parse_slippage(raw: Option<&str>) -> Result<u32, String> {
+    let Some(raw) = raw.map(str::trim).filter(|r| !r.is_empty()) else { return Ok(DEFAULT_SLIPPAGE_BPS); };
+    let bps = scale_amount(raw.trim_end_matches('%').trim(), 2).map_err(|_| format!("invalid slippage '{}'", raw))?;
+    let bps = u32::try_from(bps.0).map_err(|_| "slippage too large".to_string())?;
+    if bps == 0 || bps > MAX_SLIPPAGE_BPS {
+        return Err(format!("slippage must be between 0.01% and {}%", MAX_SLIPPAGE_BPS / 100));
+    }
+    Ok(bps)
+}
+
+/// Minimum output accepted for `expected` under a slippage bound in basis points.
+pub fn min_out(expected: &Nat, slippage_bps: u32) -> Nat {
+    expected.clone() * Nat::from(10_000 - slippage_bps) / Nat::from(10_000u32)
+}
+
+/// Resolve the tokens and input amount of a swap request.
+pub fn resolve(a: &SwapPlanArgs) -> Result<(DexQuoteArgs, u32), Value> {
+    let (ledger_in, decimals_in, sym_in) = resolve_token(Some(&a.symbol_in), None)
+        .map_err(|_| json!({"status":"err","code":"BadToken","field":"symbol_in","options": token_symbols()}))?;
+    let (ledger_out, _, sym_out) = resolve_token(Some(&a.symbol_out), None)
+        .map_err(|_| json!({"status":"err","code":"BadToken","field":"symbol_out","options": token_symbols()}))?;
+    if sym_in == sym_out {
+        return Err(json!({"status":"err","code":"BadToken","field":"symbol_out","error":"tokens must differ"}));
+    }
+    let amount_in = scale_amount(&a.amount_in_dec, decimals_in)
+        .map_err(|e| json!({"status":"err","code":"BadAmount","field":"amount_in_dec","error":e}))?;
+    let slippage = parse_slippage(a.slippage_pct.as_deref())
+        .map_err(|e| json!({"status":"err","code":"BadSlippage","field":"slippage_pct","error":e,"example":"0.5"}))?;
+    Ok((DexQuoteArgs { token_in: ledger_in, token_out: ledger_out, amount_in }, slippage))
+}
+
+/// Build a swap plan for `owner` from a DEX quote.
+pub fn build(owner: Principal, dex: Principal, a: &SwapPlanArgs, quote: &DexQuote, created: u64) -> Result<SwapPlan, Value> {
+    let (q, slippage_bps) = resolve(a)?;
+    if quote.amount_out == 0u32 {
+        return Err(json!({"status":"err","code":"NoLiquidity","error":"the DEX quotes no output for this amount"}));
+    }
+    let args = PlanArgs {
+        to: dex.to_text(),
+        amount_dec: a.amount_in_dec.clone(),
+        symbol: Some(a.symbol_in.clone()),
+        ledger: None,
+        decimals: None,
+        memo: None,
+    };
+    let mut deposit = build_plan(&args, owner, created)?;
+    deposit.to_sub = Some(swap_deposit_subaccount(owner, created));
+    deposit.checksum = plan_checksum(&deposit);
+
+    let (_, decimals_out, symbol_out) = resolve_token(Some(&a.symbol_out), None)
+        .map_err(|_| json!({"status":"err","code":"BadToken","field":"symbol_out"}))?;
+    let min_out = min_out(&quote.amount_out, slippage_bps);
+    let mut human_readable = format!(
+        "Swap {} {} for ~{} {} (min {} after {}% slippage, price impact {}%).",
+        a.amount_in_dec, deposit.symbol, format_amount(&quote.amount_out, decimals_out), symbol_out,
+        format_amount(&min_out, decimals_out), format_amount(&Nat::from(slippage_bps), 2),
+        format_amount(&Nat::from(quote.price_impact_bps), 2),
+    );
+    if quote.price_impact_bps > HIGH_IMPACT_BPS {
+        human_readable.push_str(" Warning: high price impact.");
+    }
+    if deposit.approvals_required > 0 {
+        human_readable.push_str(&format!(" Needs {} co-signer approval(s).", deposit.approvals_required));
+    }
+    let mut plan = SwapPlan {
+        owner, dex, deposit, symbol_out,
+        ledger_out: q.token_out,
+        expected_out: quote.amount_out.clone(),
+        min_out,
+        price_impact_bps: quote.price_impact_bps,
+        slippage_bps,
+        human_readable,
+        checksum: String::new(),
+    };
+    plan.checksum = swap_checksum(&plan);
+    Ok(plan)
+}
+
+/// Ask `dex` for a quote.
+pub async fn quote(dex: Principal, args: DexQuoteArgs) -> Result<DexQuote, String> {
+    ic_cdk::call::<(DexQuoteArgs,), (Result<DexQuote, String>,)>(dex, "quote", (args,))
+        .await
+        .map_err(|(code, msg)| format!("quote failed: {:?} {}", code, msg))?
+        .0
+}
+
+/// Quote and plan a swap for `owner` against the configured DEX.
+pub async fn plan(owner: Principal, a: &SwapPlanArgs, created: u64) -> Result<SwapPlan, Value> {
+    let dex = dex_canister().ok_or_else(|| json!({"status":"err","code":"NoDex","error":"swaps are not configured"}))?;
+    let (q, _) = resolve(a)?;
+    let quote = quote(dex, q).await.map_err(|e| json!({"status":"err","code":"QuoteFailed","error":e}))?;
+    log!("[plan_swap] quote out={} impact_bps={}", quote.amount_out, quote.price_impact_bps);
+    build(owner, dex, a, &quote, created)
+}
+
+/// Deposit the input into the DEX and swap it; the DEX enforces the minimum output.
+/// A failed swap withdraws the deposit back to the owner.
+pub async fn execute(plan: &SwapPlan) -> Result<DexSwapResult, ExecError> {
+    execute_plan(&plan.deposit).await?;
+    let args = DexSwapArgs {
+        token_in: plan.deposit.ledger,
+        token_out: plan.ledger_out,
+        amount_in: plan.deposit.amount.clone(),
+        min_amount_out: plan.min_out.clone(),
+        deposit_subaccount: plan.deposit.to_sub.unwrap_or_default(),
+        recipient: IcrcAccount { owner: plan.owner, subaccount: None },
+    };
+    let res = match ic_cdk::call::<(DexSwapArgs,), (Result<DexSwapResult, String>,)>(plan.dex, "swap", (args,))
+        .await
+        .map_err(|(code, msg)| format!("swap call failed: {:?} {}", code, msg))
+        .and_then(|(r,)| r)
+    {
+        Ok(res) => res,
+        Err(detail) => return Err(ExecError::Swap { detail: refund(plan, detail).await }),
+    };
+    // the DEX enforces min_amount_out; the swap already happened, so only record a mismatch
+    if res.amount_out < plan.min_out {
+        log!("[swap] {} DEX reported output {} below minimum {}", plan.checksum, res.amount_out, plan.min_out);
+    }
+    let fill = SwapFill { dex: plan.dex, symbol_out: plan.symbol_out.clone(), amount_out: res.amount_out.clone() };
+    RECEIPTS.with(|m| {
+        if let Some(r) = m.borrow_mut().get_mut(&plan.owner)
+            .and_then(|v| v.iter_mut().rev().find(|r| r.checksum == plan.deposit.checksum))
+        {
+            r.swap = Some(fill);
+        }
+    });
+    Ok(res)
+}
+
+/// Withdraw the deposit of a failed swap back to the owner; returns the failure detail with the refund outcome.
+async fn refund(plan: &SwapPlan, detail: String) -> String {
+    let args = DexWithdrawArgs {
+        token: plan.deposit.ledger,
+        amount: plan.deposit.amount.clone(),
+        deposit_subaccount: plan.deposit.to_sub.unwrap_or_default(),
+        recipient: IcrcAccount { owner: plan.owner, subaccount: None },
+    };
+    let res = ic_cdk::call::<(DexWithdrawArgs,), (Result<Nat, String>,)>(plan.dex, "withdraw", (args,))
+        .await
+        .map_err(|(code, msg)| format!("withdraw call failed: {:?} {}", code, msg))
+        .and_then(|(r,)| r);
+    log!("[swap] {} failed: {}; refund: {:?}", plan.checksum, detail, res);
+    match res {
+        Ok(_) => format!("{}; deposit refunded", detail),
+        Err(e) => format!("{}; refund failed, deposit still held by the DEX: {}", detail, e),
+    }
+}
+
+/// `plan_swap`: quote a swap on the DEX.
+pub struct PlanSwapTool;
+
+impl Tool for PlanSwapTool {
+    fn name(&self) -> &'static str { "plan_swap" }
+    fn description(&self) -> Option<&'static str> {
+        Some("Quote a token swap on the DEX. Returns a SwapPlan+checksum with expected and minimum output.")
+    }
+    fn params(&self) -> Vec<Param> { SwapPlanArgs::params() }
+    fn permission(&self) -> Permission { Permission::Plan }
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let a: SwapPlanArgs = parse_args(args)?;
+            let plan = plan(ctx.caller, &a, ctx.now).await?;
+            log!("[plan_swap] checksum={} human='{}'", plan.checksum, plan.human_readable);
+            cosign::open(&plan.deposit, Some(plan.deposit.created_at_time + PLAN_TTL_NS));
+            LAST_PLAN_BY_CALLER.with(|m| { m.borrow_mut().insert(ctx.caller, PendingPlan::Swap(Box::new(plan.clone()))); });
+            SWAP_BY_CHECKSUM.with(|m| { m.borrow_mut().insert(plan.checksum.clone(), plan.clone()); });
+            Ok(serde_json::to_value(&plan).unwrap())
+        })
+    }
+}
@@ -0,0 +1,54 @@
+use candid::{Nat, Principal};
+use icrc_ledger_types::icrc2::allowance::Allowance;
+
+use backend::allowance::*;
+use backend::utils::{resolve_token, NANOS_PER_DAY};
+
+fn spender() -> Principal {
+    Principal::from_slice(&[42; 10])
+}
+
+#[test]
+fn test_needed_for_adds_fee_per_transfer() {
+    let amounts = [Nat::from(100u32), Nat::from(50u32)];
+    assert_eq!(needed_for(&amounts, &Nat::from(10u32)), Nat::from(170u32));
+}
+
+#[test]
+fn test_build_pins_expected_allowance() {
+    let (ledger, _, _) = resolve_token(Some("CFXN"), None).unwrap();
+    let token = (ledger, 8, "ICP".to_string());
+    let current = Allowance { allowance: Nat::from(5u32), expires_at: None };
+    let p = build(spender(), &token, Nat::from(110_000u32), Nat::from(10_000u32), &current, 3 * NANOS_PER_DAY, 1);
+    assert_eq!(p.args.spender.owner, spender());
+    assert_eq!(p.args.amount, Nat::from(110_000u32));
+    assert_eq!(p.args.expected_allowance, Some(Nat::from(5u32)));
+    assert_eq!(p.args.expires_at, Some(3 * NANOS_PER_DAY));
+    assert_eq!(p.args.fee, Some(Nat::from(10_000u32)));
+    assert!(!p.sufficient);
+    assert!(p.human_readable.contains("0.0011 ICP"));
+}
+
+#[test]
+fn test_build_reports_sufficient_and_expired_allowances() {
+    let token = resolve_token(Some("CFXN"), None).unwrap();
+    let enough = Allowance { allowance: Nat::from(500u32), expires_at: None };
+    assert!(build(spender(), &token, Nat::from(101u32), Nat::from(1u32), &enough, 10, 1).sufficient);
+
+    let expired = Allowance { allowance: Nat::from(500u32), expires_at: Some(5) };
+    let p = build(spender(), &token, Nat::from(101u32), Nat::from(1u32), &expired, 10, 6);
+    assert!(!p.sufficient);
+    assert_eq!(p.current_allowance, Nat::from(0u32));
+}
+
+#[test]
+fn test_build_expects_zero_for_expired_allowance() {
+    // the ledger treats an expired allowance as zero, so pinning the stale value would fail
+    let token = resolve_token(Some("CFXN"), None).unwrap();
+    let expired = Allowance { allowance: Nat::from(500u32), expires_at: Some(5) };
+    let p = build(spender(), &token, Nat::from(101u32), Nat::from(1u32), &expired, 10, 6);
+    assert_eq!(p.args.expected_allowance, Some(Nat::from(0u32)));
+    let live = Allowance { allowance: Nat::from(500u32), expires_at: Some(50) };
+    let p = build(spender(), &token, Nat::from(101u32), Nat::from(1u32), &live, 10, 6);
+    assert_eq!(p.args.expected_allowance, Some(Nat::from(500u32)));
+}
@@ -0,0 +1,73 @@
+use candid::{Nat, Principal};
+use icrc_ledger_types::icrc1::transfer::Memo;
+
+use backend::amend::*;
+use backend::types::{AmendArgs, ExecError, TransferPlan};
+use backend::utils::plan_memo_text;
+
+mod common;
+use common::{p, plan, with_checksum};
+
+fn old_plan(owner: Principal) -> TransferPlan {
+    with_checksum(TransferPlan {
+        from_owner: Some(owner),
+        to_sub: Some([7; 32]),
+        memo: Some(Memo(format!("rent|caller:{}", owner).into_bytes().into())),
+        human_readable: "Send 10 CFXN".into(),
+        ..plan(10)
+    })
+}
+
+#[test]
+fn test_amend_keeps_unchanged_fields() {
+    let old = old_plan(p(1));
+    let a = AmendArgs { amount_dec: Some("15".into()), ..Default::default() };
+    let new = amend(p(1), &old, &a, 2).unwrap();
+    assert_eq!(new.amount, Nat::from(15u32));
+    assert_eq!(new.to_principal, old.to_principal);
+    assert_eq!(new.to_sub, old.to_sub);
+    assert_eq!(plan_memo_text(&new).as_deref(), Some("rent"));
+    assert_eq!(new.memo, old.memo);
+    assert_eq!(new.supersedes.as_deref(), Some(old.checksum.as_str()));
+    assert_ne!(new.checksum, old.checksum);
+
+    let a = AmendArgs { memo: Some("deposit".into()), ..Default::default() };
+    let new = amend(p(1), &old, &a, 2).unwrap();
+    assert_eq!(plan_memo_text(&new).as_deref(), Some("deposit"));
+    assert_eq!(new.amount, old.amount);
+}
+
+#[test]
+fn test_amend_rejects_foreign_and_closed_plans() {
+    let old = old_plan(p(3));
+    let a = AmendArgs { amount_dec: Some("15".into()), ..Default::default() };
+    assert!(amend(p(4), &old, &a, 2).is_err());
+
+    let invoice = TransferPlan { invoice_id: Some(1), ..old.clone() };
+    assert!(amend(p(3), &invoice, &a, 2).is_err());
+}
+
+#[test]
+fn test_superseded_plan_cannot_be_confirmed_or_amended_again() {
+    let old = old_plan(p(5));
+    let a = AmendArgs { amount_dec: Some("20".into()), ..Default::default() };
+    let new = amend(p(5), &old, &a, 2).unwrap();
+    supersede(&old.checksum, &new.checksum);
+
+    assert!(matches!(check_current(&old.checksum), Err(ExecError::Superseded { by }) if by == new.checksum));
+    assert!(check_current(&new.checksum).is_ok());
+    assert!(amend(p(5), &old, &a, 3).is_err());
+}
+
+#[test]
+fn test_cancelled_plan_cannot_be_confirmed_or_approved() {
+    use backend::cosign;
+    use backend::types::PendingPlan;
+    let plan = TransferPlan { approvals_required: 1, ..old_plan(p(8)) };
+    cosign::open(&plan, None);
+    cancel(&PendingPlan::Transfer(plan.clone()));
+    assert!(matches!(check_current(&plan.checksum), Err(ExecError::Cancelled)));
+    assert_eq!(cosign::decide_stored(&plan.checksum, p(9), true, None, 2).unwrap_err(), "unknown plan");
+    // other plans are unaffected
+    assert!(check_current(&old_plan(p(10)).checksum).is_ok());
+}
@@ -0,0 +1,120 @@
+use candid::{Nat, Principal};
+
+use backend::analytics::*;
+use backend::types::{Receipt, SavedAccount, SummaryArgs};
+use backend::utils::{days_from_civil, NANOS_PER_DAY};
+
+fn day(y: i64, m: u32, d: u32) -> u64 {
+    days_from_civil(y, m, d) as u64 * NANOS_PER_DAY
+}
+
+fn who(b: u8) -> Principal {
+    Principal::from_slice(&[b; 29])
+}
+
+fn receipt(to: Principal, amount: u32, symbol: &str, executed_at: u64) -> Receipt {
+    Receipt {
+        checksum: String::new(),
+        owner: Principal::anonymous(),
+        to_principal: to,
+        to_sub: None,
+        amount: Nat::from(amount),
+        symbol: symbol.to_string(),
+        ledger: Principal::management_canister(),
+        memo: None,
+        block_index: Nat::from(1u32),
+        executed_at,
+        invoice_id: None,
+        memo_text: None,
+        swap: None,
+        fiat_value: None,
+    }
+}
+
+fn accounts() -> Vec<SavedAccount> {
+    vec![
+        SavedAccount { alias: "acme".into(), owner: who(1), subaccount: None, tags: vec!["vendor".into()] },
+        SavedAccount { alias: "alice".into(), owner: who(2), subaccount: None, tags: vec![] },
+    ]
+}
+
+fn receipts() -> Vec<Receipt> {
+    vec![
+        receipt(who(1), 100, "CFXN", day(2026, 8, 31)),
+        receipt(who(1), 40, "CFXN", day(2026, 9, 2)),
+        receipt(who(2), 10, "CFXN", day(2026, 9, 14)),
+        receipt(who(1), 60, "CFXN", day(2026, 9, 30) + NANOS_PER_DAY - 1),
+        receipt(who(2), 150_000_000, "ICP", day(2026, 9, 15)),
+    ]
+}
+
+#[test]
+fn test_period_keys_and_bounds() {
+    assert_eq!(period_key(day(2026, 9, 17), "day"), "2026-09-17");
+    assert_eq!(period_key(day(2026, 9, 17), "week"), "week of 2026-09-14");
+    assert_eq!(period_key(day(2026, 9, 14), "week"), "week of 2026-09-14");
+    assert_eq!(period_key(day(2026, 9, 17), "month"), "2026-09");
+    assert_eq!(parse_bound("2026-02"), Ok((day(2026, 2, 1), day(2026, 3, 1))));
+    assert_eq!(parse_bound("2026-09-30"), Ok((day(2026, 9, 30), day(2026, 10, 1))));
+    assert!(parse_bound("2026-13").is_err());
+    assert_eq!(parse_tags(" Vendor, office,vendor,"), vec!["office".to_string(), "vendor".to_string()]);
+}
+
+#[test]
+fn test_summary_by_tag_in_month() {
+    let args = SummaryArgs { tag: Some("Vendor".into()), from: Some("2026-09".into()), to: Some("2026-09".into()), ..Default::default() };
+    let s = summarize(&receipts(), &accounts(), &args, None).unwrap();
+    assert_eq!(s.totals.len(), 1);
+    assert_eq!((s.totals[0].total_dec.as_str(), s.totals[0].count), ("100", 2));
+    assert_eq!(s.by_tag[0].key, "vendor");
+    assert_eq!(s.top_recipients[0].key, "acme");
+    assert_eq!(s.human_readable, "Sent 100 CFXN in 2 transfer(s) from 2026-09-01 to 2026-09-30. Top recipient: acme (100 CFXN).");
+}
+
+#[test]
+fn test_summary_counts_a_tag_once_per_receipt() {
+    let mut accounts = accounts();
+    accounts.push(SavedAccount { alias: "acme-billing".into(), owner: who(1), subaccount: None, tags: vec!["vendor".into()] });
+    let args = SummaryArgs { tag: Some("vendor".into()), from: Some("2026-09".into()), to: Some("2026-09".into()), ..Default::default() };
+    let s = summarize(&receipts(), &accounts, &args, None).unwrap();
+    assert_eq!(s.by_tag.len(), 1);
+    assert_eq!((s.by_tag[0].total_dec.as_str(), s.by_tag[0].count), ("100", 2));
+}
+
+#[test]
+fn test_summary_groups_per_token_and_period() {
+    let args = SummaryArgs { period: Some("month".into()), top: Some(1), ..Default::default() };
+    let s = summarize(&receipts(), &accounts(), &args, None).unwrap();
+    let totals: Vec<(&str, &str)> = s.totals.iter().map(|g| (g.symbol.as_str(), g.total_dec.as_str())).collect();
+    assert_eq!(totals, [("CFXN", "210"), ("ICP", "1.5")]);
+    let periods: Vec<(&str, &str)> = s.by_period.iter().map(|g| (g.key.as_str(), g.symbol.as_str())).collect();
+    assert_eq!(periods, [("2026-08", "CFXN"), ("2026-09", "CFXN"), ("2026-09", "ICP")]);
+    // one top recipient per token
+    let top: Vec<(&str, &str)> = s.top_recipients.iter().map(|g| (g.key.as_str(), g.symbol.as_str())).collect();
+    assert_eq!(top, [("alice", "ICP"), ("acme", "CFXN")]);
+
+    let only_alice = summarize(&receipts(), &accounts(), &SummaryArgs { symbol: Some("cfxn".into()), ..Default::default() }, Some((who(2), None))).unwrap();
+    assert_eq!(only_alice.totals[0].total, Nat::from(10u32));
+
+    let bad = SummaryArgs { period: Some("year".into()), ..Default::default() };
+    assert_eq!(summarize(&receipts(), &accounts(), &bad, None).unwrap_err()["field"], "period");
+    let empty = SummaryArgs { from: Some("2027-01".into()), ..Default::default() };
+    assert_eq!(summarize(&receipts(), &accounts(), &empty, None).unwrap().human_readable, "No transfers found since 2027-01-01.");
+}
+
+#[test]
+fn test_tags_are_kept_per_caller() {
+    backend::save_account("acme".into(), who(1).to_text(), None).unwrap();
+    set_tags(who(7), "acme", parse_tags("vendor"));
+    set_tags(who(8), "acme", parse_tags("friend"));
+    let tags_of = |caller| accounts_for(caller).into_iter().find(|a| a.alias == "acme").unwrap().tags;
+    assert_eq!(tags_of(who(7)), vec!["vendor".to_string()]);
+    assert_eq!(tags_of(who(8)), vec!["friend".to_string()]);
+    assert!(tags_of(who(9)).is_empty());
+
+    // saving the alias again keeps every caller's tags
+    backend::save_account("acme".into(), who(1).to_text(), None).unwrap();
+    assert_eq!(tags_of(who(7)), vec!["vendor".to_string()]);
+    set_tags(who(7), "acme", Vec::new());
+    assert!(tags_of(who(7)).is_empty());
+}
@@ -0,0 +1,42 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use candid::{Nat, Principal};
+
+use backend::types::TransferPlan;
+use backend::utils::{plan_checksum, resolve_token};
+
+/// Principal made of byte `n`.
+pub fn p(n: u8) -> Principal {
+    Principal::from_slice(&[n; 29])
+}
+
+/// Plan sending `amount` CFXN from `p(1)` to `p(2)`, without a checksum.
+///
+/// Tests override the fields they care about: `TransferPlan { to_principal: p(9), ..plan(10) }`.
+pub fn plan(amount: u32) -> TransferPlan {
+    let (ledger, _, symbol) = resolve_token(Some("CFXN"), None).unwrap();
+    TransferPlan {
+        from_owner: Some(p(1)),
+        from_sub: None,
+        to_principal: p(2),
+        to_sub: None,
+        amount: Nat::from(amount),
+        symbol,
+        ledger,
+        memo: None,
+        created_at_time: 1,
+        human_readable: "Send".into(),
+        approvals_required: 0,
+        invoice_id: None,
+        escrow_id: None,
+        supersedes: None,
+        checksum: String::new(),
+    }
+}
+
+/// `plan` with its checksum filled in.
+pub fn with_checksum(mut plan: TransferPlan) -> TransferPlan {
+    plan.checksum = plan_checksum(&plan);
+    plan
+}
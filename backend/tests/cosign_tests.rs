@@ -0,0 +1,122 @@
+use candid::Nat;
+
+use backend::cosign::*;
+use backend::types::{ApprovalRequest, CosignConfig, ExecError, TokenThreshold, TransferPlan};
+
+mod common;
+use common::{p, plan};
+
+fn config() -> CosignConfig {
+    CosignConfig {
+        cosigners: vec![p(2), p(3), p(4)],
+        required: 2,
+        thresholds: vec![TokenThreshold { symbol: "CFXN".into(), amount: Nat::from(1_000u32) }],
+    }
+}
+
+fn request() -> ApprovalRequest {
+    ApprovalRequest {
+        checksum: "abc".into(),
+        plan: TransferPlan {
+            to_principal: p(9),
+            approvals_required: 2,
+            checksum: "abc".into(),
+            ..plan(5_000)
+        },
+        cosigners: vec![p(2), p(3), p(4)],
+        required: 2,
+        approved_by: vec![],
+        rejected_by: None,
+        rejection_reason: None,
+        expires_at: Some(100),
+    }
+}
+
+#[test]
+fn test_validate_config() {
+    assert!(validate(p(1), &config()).is_ok());
+    assert!(validate(p(2), &config()).is_err());
+    let mut c = config();
+    c.required = 4;
+    assert!(validate(p(1), &c).is_err());
+    c.required = 1;
+    c.cosigners.push(p(2));
+    assert!(validate(p(1), &c).is_err());
+}
+
+#[test]
+fn test_required_for_threshold() {
+    let c = config();
+    assert_eq!(required_for(&c, "cfxn", &Nat::from(999u32)), 0);
+    assert_eq!(required_for(&c, "CFXN", &Nat::from(1_000u32)), 2);
+    assert_eq!(required_for(&c, "ICP", &Nat::from(1_000_000u32)), 0);
+}
+
+#[test]
+fn test_quorum_flow() {
+    let mut req = request();
+    assert!(matches!(status(Some(&req), 2), Err(ExecError::AwaitingApproval { approved: 0, required: 2 })));
+    assert!(decide(&mut req, p(9), true, None, 10).is_err());
+    decide(&mut req, p(2), true, None, 10).unwrap();
+    assert!(decide(&mut req, p(2), true, None, 10).is_err());
+    assert!(matches!(status(Some(&req), 2), Err(ExecError::AwaitingApproval { approved: 1, .. })));
+    decide(&mut req, p(3), true, None, 10).unwrap();
+    assert!(status(Some(&req), 2).is_ok());
+    assert!(matches!(status(None, 1), Err(ExecError::AwaitingApproval { .. })));
+    assert!(status(None, 0).is_ok());
+}
+
+#[test]
+fn test_reject_and_expiry() {
+    let mut req = request();
+    assert!(decide(&mut req, p(2), true, None, 101).is_err());
+    decide(&mut req, p(4), false, Some("too much".into()), 10).unwrap();
+    match status(Some(&req), 2) {
+        Err(ExecError::Rejected { by, reason }) => {
+            assert_eq!(by, p(4));
+            assert_eq!(reason.as_deref(), Some("too much"));
+        }
+        _ => panic!(),
+    }
+    assert!(decide(&mut req, p(3), true, None, 10).is_err());
+}
+
+#[test]
+fn test_config_changes_do_not_weaken_open_plans() {
+    use backend::amend::amend;
+    use backend::types::AmendArgs;
+    set_config(p(1), config()).unwrap();
+    let plan = request().plan;
+    open(&plan, Some(100));
+    // the owner swaps in a single co-signer of their own
+    set_config(p(1), CosignConfig { cosigners: vec![p(7)], required: 1, ..config() }).unwrap();
+    open(&plan, Some(100));
+    assert_eq!(decide_stored("abc", p(7), true, None, 10).unwrap_err(), "not a co-signer of this plan");
+    assert!(matches!(check("abc", plan.approvals_required), Err(ExecError::AwaitingApproval { required: 2, .. })));
+
+    // an amendment keeps the quorum and co-signers of the plan it replaces
+    let new = amend(p(1), &plan, &AmendArgs { memo: Some("rent".into()), ..Default::default() }, 2).unwrap();
+    assert_eq!(new.approvals_required, 2);
+    assert!(new.human_readable.ends_with("Needs 2 co-signer approval(s)."));
+    open_amended("abc", &new, Some(100));
+    assert!(decide_stored(&new.checksum, p(7), true, None, 10).is_err());
+    decide_stored(&new.checksum, p(2), true, None, 10).unwrap();
+}
+
+#[test]
+fn test_snapshot_survives_upgrade_round_trip() {
+    set_config(p(1), config()).unwrap();
+    let plan = request().plan;
+    open(&plan, Some(100));
+    decide_stored("abc", p(2), true, None, 10).unwrap();
+    let bytes = candid::encode_one(snapshot()).unwrap();
+
+    // a fresh canister after the upgrade
+    restore((Vec::new(), Vec::new()));
+    assert_eq!(required_for_owner(p(1), "CFXN", &plan.amount), 0);
+    assert!(decide_stored("abc", p(3), true, None, 10).is_err());
+    restore(candid::decode_one(&bytes).unwrap());
+    assert_eq!(required_for_owner(p(1), "CFXN", &plan.amount), plan.approvals_required);
+    decide_stored("abc", p(3), true, None, 10).unwrap();
+    assert!(check("abc", plan.approvals_required).is_ok());
+}
@@ -0,0 +1,86 @@
+use candid::{Nat, Principal};
+
+use backend::escrow::*;
+use backend::types::{EscrowArgs, EscrowStatus, ExecError};
+use backend::utils::{escrow_subaccount, parse_date_ns, NANOS_PER_DAY};
+
+mod common;
+use common::p;
+
+fn args(to: Principal, arbiter: Option<Principal>) -> EscrowArgs {
+    EscrowArgs {
+        to: to.to_text(),
+        amount_dec: "100".into(),
+        symbol: Some("CFXN".into()),
+        memo: None,
+        arbiter: arbiter.map(|a| a.to_text()),
+        deadline: "2025-02-01".into(),
+    }
+}
+
+#[test]
+fn test_escrow_subaccount_is_stable_and_distinct() {
+    assert_eq!(escrow_subaccount(1), escrow_subaccount(1));
+    assert_ne!(escrow_subaccount(1), escrow_subaccount(2));
+}
+
+#[test]
+fn test_create_plans_funding_into_canister_subaccount() {
+    let now = parse_date_ns("2025-01-15").unwrap();
+    let canister = p(100);
+    let (e, plan) = create(p(1), canister, args(p(2), Some(p(3))), now).unwrap();
+    assert_eq!(e.status, EscrowStatus::AwaitingFunds);
+    assert_eq!(e.recipient, p(2));
+    assert_eq!(plan.to_principal, canister);
+    assert_eq!(plan.to_sub, Some(escrow_subaccount(e.id)));
+    assert_eq!(plan.escrow_id, Some(e.id));
+    assert_eq!(plan.amount, Nat::from(100u32));
+
+    assert!(create(p(1), canister, args(p(1), None), now).is_err());
+    assert!(create(p(1), canister, args(p(2), Some(p(1))), now).is_err());
+    let late = parse_date_ns("2025-03-01").unwrap();
+    assert!(create(p(1), canister, args(p(2), None), late).is_err());
+}
+
+#[test]
+fn test_funding_and_authorization() {
+    let now = parse_date_ns("2025-01-15").unwrap();
+    let (e, _) = create(p(4), p(100), args(p(5), Some(p(6))), now).unwrap();
+    assert!(authorize(&e, p(4), false, now).is_err());
+    assert!(check_awaiting(e.id).is_ok());
+
+    mark_funded(e.id, Nat::from(9u32));
+    assert!(matches!(check_awaiting(e.id), Err(ExecError::EscrowClosed { .. })));
+    let e = get(e.id).unwrap();
+    assert_eq!(e.status, EscrowStatus::Funded);
+
+    assert!(authorize(&e, p(4), false, now).is_ok());
+    assert!(authorize(&e, p(6), false, now).is_ok());
+    assert!(authorize(&e, p(5), false, now).is_err());
+    // payer refunds only after the deadline, arbiter any time
+    assert!(authorize(&e, p(4), true, now).is_err());
+    assert!(authorize(&e, p(4), true, e.deadline_at + NANOS_PER_DAY).is_ok());
+    assert!(authorize(&e, p(6), true, now).is_ok());
+
+    assert_eq!(list(p(5)).len(), 1);
+    assert_eq!(list(p(6)).len(), 1);
+}
+
+#[test]
+fn test_snapshot_survives_upgrade_round_trip() {
+    let now = parse_date_ns("2025-01-15").unwrap();
+    let (e, _) = create(p(1), p(9), args(p(2), Some(p(3))), now).unwrap();
+    mark_funded(e.id, Nat::from(7u32));
+    let bytes = candid::encode_one(snapshot()).unwrap();
+
+    // a fresh canister after the upgrade
+    restore((Vec::new(), 0));
+    assert!(get(e.id).is_none());
+    restore(candid::decode_one(&bytes).unwrap());
+    let back = get(e.id).unwrap();
+    assert_eq!(back.status, EscrowStatus::Funded);
+    assert_eq!(back.fund_block_index, Some(Nat::from(7u32)));
+    assert_eq!(back.arbiter, Some(p(3)));
+    let (next, _) = create(p(1), p(9), args(p(2), None), now).unwrap();
+    assert!(next.id > e.id);
+}
@@ -0,0 +1,71 @@
+use candid::{Nat, Principal};
+use icrc_ledger_types::icrc1::account::Account;
+
+use backend::history::*;
+use backend::types::{GetTransactions, HistoryArgs, IndexMint, IndexTransaction, IndexTransfer, TransactionWithId};
+
+fn acct(b: u8) -> Account {
+    Account { owner: Principal::from_slice(&[b; 29]), subaccount: None }
+}
+
+fn transfer(id: u64, from: Account, to: Account, amount: u64) -> TransactionWithId {
+    TransactionWithId {
+        id: Nat::from(id),
+        transaction: IndexTransaction {
+            kind: "transfer".into(),
+            timestamp: 0,
+            transfer: Some(IndexTransfer { from, to, amount: Nat::from(amount), fee: None, memo: Some(b"rent".to_vec()), spender: None }),
+            mint: None,
+            burn: None,
+            approve: None,
+        },
+    }
+}
+
+#[test]
+fn test_account_tx_directions() {
+    let (me, other) = (acct(1), acct(2));
+    let incoming = account_tx(&me, &transfer(3, other, me, 150_000_000), 8).unwrap();
+    assert_eq!((incoming.direction.as_str(), incoming.counterparty), ("in", Some(other)));
+    assert_eq!(incoming.amount_dec, "1.5");
+    assert_eq!(incoming.memo_text.as_deref(), Some("rent"));
+    assert_eq!(account_tx(&me, &transfer(4, me, other, 1), 0).unwrap().direction, "out");
+    assert_eq!(account_tx(&me, &transfer(5, me, me, 1), 0).unwrap().direction, "self");
+
+    let mut mint = transfer(6, other, me, 0);
+    mint.transaction.kind = "mint".into();
+    mint.transaction.transfer = None;
+    mint.transaction.mint = Some(IndexMint { to: me, amount: Nat::from(9u32), memo: None });
+    let m = account_tx(&me, &mint, 0).unwrap();
+    assert_eq!((m.direction.as_str(), m.counterparty, m.amount_dec.as_str()), ("in", None, "9"));
+}
+
+#[test]
+fn test_page_filters_and_cursor() {
+    let (me, other) = (acct(1), acct(2));
+    let res = GetTransactions {
+        balance: Nat::from(70u32),
+        transactions: vec![transfer(9, me, other, 30), transfer(7, other, me, 100)],
+        oldest_tx_id: Some(Nat::from(2u32)),
+    };
+    let p = page(&me, "CFXN", 0, &res, "in", 2);
+    assert_eq!(p.transactions.len(), 1);
+    assert_eq!(p.balance_dec, "70");
+    assert_eq!(p.next_start.as_deref(), Some("7"));
+    // a short page is the last one
+    assert_eq!(page(&me, "CFXN", 0, &res, "all", 10).next_start, None);
+}
+
+#[test]
+fn test_request_validation() {
+    let owner = acct(1).owner;
+    let (symbol, _, args, direction) = request(owner, &HistoryArgs { max_results: Some(500), ..Default::default() }).unwrap();
+    assert_eq!((symbol.as_str(), direction.as_str()), ("CFXN", "all"));
+    assert_eq!(args.max_results, Nat::from(MAX_PAGE));
+    assert_eq!(args.start, None);
+
+    let bad = HistoryArgs { direction: Some("sideways".into()), ..Default::default() };
+    assert_eq!(request(owner, &bad).unwrap_err()["field"], "direction");
+    let bad = HistoryArgs { start: Some("abc".into()), ..Default::default() };
+    assert_eq!(request(owner, &bad).unwrap_err()["field"], "start");
+}
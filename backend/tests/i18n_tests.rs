@@ -0,0 +1,72 @@
+use candid::Principal;
+use serde_json::{json, Value};
+
+use backend::i18n::*;
+use backend::types::{PendingPlan, TransferPlan};
+use backend::utils::LANGUAGES;
+
+mod common;
+use common::with_checksum;
+
+fn plan() -> TransferPlan {
+    with_checksum(TransferPlan { human_readable: "Send 10 CFXN".into(), approvals_required: 2, ..common::plan(10) })
+}
+
+#[test]
+fn test_error_messages_in_every_language() {
+    let err = json!({"status":"err","code":"BadAmount","example":"1.5"});
+    assert_eq!(error_message("BadAmount", "en", &err).unwrap(), "That amount is not valid for this token. Example: 1.5.");
+    assert_eq!(error_message("BadAmount", "id", &err).unwrap(), "Jumlah itu tidak valid untuk token ini. Contoh: 1.5.");
+    let token = json!({"code":"BadToken","options":["ICP","CFXN"]});
+    assert!(error_message("BadToken", "jv", &token).unwrap().ends_with("Pilihan: ICP, CFXN."));
+    assert!(error_message("NoSuchCode", "en", &err).is_none());
+    let args = json!({"code":"BadArgs","fields":[{"field":"to"},{"field":"decimals"}]});
+    assert!(error_message("BadArgs", "ms", &args).unwrap().ends_with("Medan: to, decimals."));
+    // unknown languages fall back to English
+    assert_eq!(error_message("NoChange", "fr", &json!({})).unwrap(), "Nothing changed.");
+    for lang in LANGUAGES {
+        assert!(error_message("PolicyDailyCap", lang, &err).is_some());
+    }
+}
+
+#[test]
+fn test_plan_summaries() {
+    let p = PendingPlan::Transfer(plan());
+    let to = Principal::from_slice(&[2; 29]).to_text();
+    assert_eq!(plan_summary(&p, "en", 1), format!("Send 10 CFXN to {}. Needs 2 co-signer approval(s).", to));
+    assert_eq!(plan_summary(&p, "id", 1), format!("Kirim 10 CFXN ke {}. Perlu 2 persetujuan co-signer.", to));
+    assert!(plan_summary(&p, "ms", 1).starts_with("Hantar 10 CFXN kepada"));
+    assert!(plan_summary(&p, "jv", 1).starts_with("Ngirim 10 CFXN menyang"));
+
+    let invoice = PendingPlan::Transfer(TransferPlan { invoice_id: Some(4), approvals_required: 0, ..plan() });
+    assert_eq!(plan_summary(&invoice, "id", 1), format!("Bayar tagihan #4: kirim 10 CFXN ke {}.", to));
+}
+
+#[test]
+fn test_localize_adds_message() {
+    let p = plan();
+    let pending = PendingPlan::Transfer(p.clone());
+    let out: Value = serde_json::from_str(&localize(&serde_json::to_string(&p).unwrap(), "ms", Some(&pending), 1)).unwrap();
+    assert!(out["message"].as_str().unwrap().starts_with("Hantar"));
+    assert_eq!(out["checksum"], p.checksum);
+
+    let err = json!({"status":"err","code":"NeedRecipient","field":"to"}).to_string();
+    let out: Value = serde_json::from_str(&localize(&err, "id", None, 1)).unwrap();
+    assert_eq!(out["message"], "Mau dikirim ke siapa?");
+    assert_eq!(out["field"], "to");
+
+    // results that are neither errors nor the pending plan are untouched
+    let ok = json!({"status":"ok"}).to_string();
+    assert_eq!(localize(&ok, "id", Some(&pending), 1), ok);
+    assert_eq!(localize("not json", "id", None, 1), "not json");
+}
+
+#[test]
+fn test_tool_trace_from_results() {
+    let t = tool_trace("plan_transfer", &json!({"status":"err","code":"BadToken"}).to_string());
+    assert_eq!((t.name.as_str(), t.status.as_str(), t.code.as_deref()), ("plan_transfer", "err", Some("BadToken")));
+    let t = tool_trace("get_portfolio", &json!({"status":"ok","summary":"…"}).to_string());
+    assert_eq!((t.status.as_str(), t.code), ("ok", None));
+    // plans carry no status field
+    assert_eq!(tool_trace("plan_swap", "{\"checksum\":\"ab\"}").status, "ok");
+}
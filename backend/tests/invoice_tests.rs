@@ -0,0 +1,113 @@
+use candid::{Nat, Principal};
+
+use backend::invoice::*;
+use backend::types::{ExecError, InvoiceArgs, InvoiceStatus};
+use backend::utils::{decode_memo, parse_date_ns};
+
+mod common;
+use common::p;
+
+fn args(payer: Option<Principal>) -> InvoiceArgs {
+    InvoiceArgs {
+        amount_dec: "50".into(),
+        symbol: Some("CFXN".into()),
+        memo: Some("dinner".into()),
+        due_date: Some("2025-02-01".into()),
+        payer: payer.map(|p| p.to_text()),
+    }
+}
+
+#[test]
+fn test_create_validates_input() {
+    let now = parse_date_ns("2025-01-15").unwrap();
+    let inv = create(p(1), args(Some(p(2))), now).unwrap();
+    assert_eq!(inv.amount, Nat::from(50u32));
+    assert_eq!(inv.status, InvoiceStatus::Open);
+    assert!(describe(&inv).contains("due 2025-02-01"));
+
+    let late = parse_date_ns("2025-03-01").unwrap();
+    assert!(create(p(1), args(None), late).is_err());
+    assert!(create(p(1), args(Some(p(1))), now).is_err());
+    assert!(create(p(1), InvoiceArgs { amount_dec: "0".into(), ..args(None) }, now).is_err());
+    assert!(create(p(1), InvoiceArgs { memo: Some("x".repeat(20)), ..args(None) }, now).is_err());
+}
+
+#[test]
+fn test_plan_payment_prefills_from_invoice() {
+    let now = parse_date_ns("2025-01-15").unwrap();
+    let inv = create(p(1), args(Some(p(2))), now).unwrap();
+
+    let plan = plan_payment(p(2), inv.id, now).unwrap();
+    assert_eq!(plan.invoice_id, Some(inv.id));
+    assert_eq!(plan.to_principal, p(1));
+    assert_eq!(plan.amount, inv.amount);
+    assert!(plan.human_readable.starts_with(&format!("Pay invoice #{}", inv.id)));
+    let memo = decode_memo(&plan.memo.unwrap().0).unwrap();
+    assert_eq!(memo.invoice_id, Some(inv.id));
+    assert_eq!(memo.text.as_deref(), Some("dinner"));
+
+    assert!(plan_payment(p(3), inv.id, now).is_err());
+    assert!(plan_payment(p(1), inv.id, now).is_err());
+    assert!(plan_payment(p(2), 9_999, now).is_err());
+}
+
+#[test]
+fn test_paid_invoice_is_closed_and_listed_for_both_sides() {
+    let now = parse_date_ns("2025-01-15").unwrap();
+    let inv = create(p(4), args(None), now).unwrap();
+    assert!(check_open(inv.id).is_ok());
+
+    mark_paid(inv.id, p(5), Nat::from(7u32), now);
+    assert!(matches!(check_open(inv.id), Err(ExecError::InvoiceClosed { .. })));
+    assert!(plan_payment(p(5), inv.id, now).is_err());
+    assert!(cancel(p(4), inv.id).is_err());
+
+    let paid = get(inv.id).unwrap();
+    assert_eq!(paid.paid_block_index, Some(Nat::from(7u32)));
+    assert!(list(p(4)).iter().any(|i| i.id == inv.id));
+    assert!(list(p(5)).iter().any(|i| i.id == inv.id));
+    assert!(list(p(6)).is_empty());
+}
+
+#[test]
+fn test_cancel_only_by_creator() {
+    let now = parse_date_ns("2025-01-15").unwrap();
+    let inv = create(p(7), args(None), now).unwrap();
+    assert!(cancel(p(8), inv.id).is_err());
+    assert_eq!(cancel(p(7), inv.id).unwrap().status, InvoiceStatus::Cancelled);
+}
+
+#[test]
+fn test_snapshot_survives_upgrade_round_trip() {
+    let now = parse_date_ns("2025-01-15").unwrap();
+    let inv = create(p(1), args(Some(p(2))), now).unwrap();
+    mark_paid(inv.id, p(2), Nat::from(9u32), now);
+    let bytes = candid::encode_one(snapshot()).unwrap();
+
+    // a fresh canister after the upgrade
+    restore((Vec::new(), 0));
+    assert!(get(inv.id).is_none());
+    restore(candid::decode_one(&bytes).unwrap());
+    let back = get(inv.id).unwrap();
+    assert_eq!(back.status, InvoiceStatus::Paid);
+    assert_eq!(back.paid_block_index, Some(Nat::from(9u32)));
+    let next = create(p(1), args(None), now).unwrap();
+    assert!(next.id > inv.id);
+}
+
+#[test]
+fn test_claim_blocks_concurrent_payment_until_reopened() {
+    let now = parse_date_ns("2025-01-15").unwrap();
+    let inv = create(p(1), args(None), now).unwrap();
+    claim(inv.id).unwrap();
+    assert!(matches!(claim(inv.id), Err(ExecError::InvoiceClosed { .. })));
+    assert!(check_open(inv.id).is_err());
+
+    // the transfer failed
+    reopen(inv.id);
+    assert!(check_open(inv.id).is_ok());
+    claim(inv.id).unwrap();
+    mark_paid(inv.id, p(2), Nat::from(3u32), now);
+    reopen(inv.id);
+    assert_eq!(get(inv.id).unwrap().status, InvoiceStatus::Paid);
+}
@@ -0,0 +1,40 @@
+use ic_llm::{AssistantMessage, FunctionCall, ParameterType, Response, ToolCall};
+
+use backend::llm::*;
+use backend::types::{Config, LlmModel};
+
+fn response(name: &str, id: &str, args: &[(&str, &str)]) -> Response {
+    let arguments = args.iter()
+        .map(|(n, v)| serde_json::from_value(serde_json::json!({"name": n, "value": v})).unwrap())
+        .collect();
+    Response {
+        message: AssistantMessage {
+            content: None,
+            tool_calls: vec![ToolCall { id: id.into(), function: FunctionCall { name: name.into(), arguments } }],
+        },
+    }
+}
+
+#[test]
+fn test_malformed_tool_calls() {
+    let tools = vec![
+        ic_llm::tool("plan_transfer")
+            .with_parameter(ic_llm::parameter("to", ParameterType::String).is_required())
+            .with_parameter(ic_llm::parameter("memo", ParameterType::String))
+            .build(),
+    ];
+    assert_eq!(malformed(&response("plan_transfer", "1", &[("to", "alice")]), &tools), None);
+    assert_eq!(malformed(&response("send_money", "1", &[]), &tools).unwrap(), "unknown tool 'send_money'");
+    assert!(malformed(&response("plan_transfer", " ", &[("to", "alice")]), &tools).unwrap().contains("without id"));
+    assert!(malformed(&response("plan_transfer", "1", &[("memo", "x")]), &tools).unwrap().contains("missing argument 'to'"));
+}
+
+#[test]
+fn test_models_for_request() {
+    let c = Config::new();
+    assert_eq!((c.model, c.fallback_model), (LlmModel::Llama3_1_8B, Some(LlmModel::Qwen3_32B)));
+    assert_eq!(models_for(None), (LlmModel::Llama3_1_8B, Some(LlmModel::Qwen3_32B)));
+    assert_eq!(models_for(Some(LlmModel::Llama4Scout)), (LlmModel::Llama4Scout, Some(LlmModel::Qwen3_32B)));
+    // the fallback is skipped when it is the requested model
+    assert_eq!(models_for(Some(LlmModel::Qwen3_32B)), (LlmModel::Qwen3_32B, None));
+}
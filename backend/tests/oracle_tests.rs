@@ -0,0 +1,55 @@
+use candid::Nat;
+
+use backend::oracle::*;
+use backend::types::Price;
+
+const SEC: u64 = 1_000_000_000;
+
+#[test]
+fn test_parse_fiat() {
+    assert_eq!(parse_fiat("Rp 50.000"), Some(("IDR", Nat::from(5_000_000_000_000u64))));
+    assert_eq!(parse_fiat("rp50.000,5"), Some(("IDR", Nat::from(5_000_050_000_000u64))));
+    assert_eq!(parse_fiat("IDR 50000"), Some(("IDR", Nat::from(5_000_000_000_000u64))));
+    assert_eq!(parse_fiat("50000 rupiah"), Some(("IDR", Nat::from(5_000_000_000_000u64))));
+    assert_eq!(parse_fiat("$5.50"), Some(("USD", Nat::from(550_000_000u64))));
+    assert_eq!(parse_fiat("1,000 USD"), Some(("USD", Nat::from(100_000_000_000u64))));
+    assert_eq!(parse_fiat("10"), None);
+    assert_eq!(parse_fiat("Rp abc"), None);
+}
+
+#[test]
+fn test_format_fiat() {
+    assert_eq!(format_fiat(&Nat::from(5_000_000_000_000u64), "IDR"), "Rp 50.000");
+    assert_eq!(format_fiat(&Nat::from(49_999_990_000u64), "IDR"), "Rp 500");
+    assert_eq!(format_fiat(&Nat::from(310_000_000u64), "USD"), "$3.10");
+    assert_eq!(format_fiat(&Nat::from(123_456_789_000_000u64), "USD"), "$1,234,567.89");
+    assert_eq!(format_fiat(&Nat::from(4_000_000u64), "USD"), "$0.04");
+}
+
+#[test]
+fn test_fiat_conversion_roundtrip() {
+    // 1 ICP (8 decimals) = Rp 160.000
+    let rate = 16_000_000_000_000u64;
+    let fiat = parse_fiat("Rp 50.000").unwrap().1;
+    let amount = token_amount_for_fiat(&fiat, 8, rate);
+    assert_eq!(amount, Nat::from(31_250_000u64));
+    assert_eq!(format_fiat(&fiat_value_e8(&amount, 8, rate), "IDR"), "Rp 50.000");
+    // rounded down to whole units for tokens without decimals
+    assert_eq!(token_amount_for_fiat(&fiat, 0, rate), Nat::from(0u32));
+    assert_eq!(token_amount_for_fiat(&fiat, 8, 0), Nat::from(0u32));
+}
+
+#[test]
+fn test_cached_prices_expire() {
+    let now = 10_000 * SEC;
+    cache_price("CFXN", "IDR", Price { rate_e8: 1_600_000_000_000, timestamp: now }, now);
+    assert_eq!(cached_rate("CFXN", "IDR", now + 60 * SEC), Some(1_600_000_000_000));
+    assert_eq!(annotation_cached("CFXN", &Nat::from(3u32), 0, now).as_deref(), Some("≈ Rp 48.000"));
+
+    cache_price("CFXN", "USD", Price { rate_e8: 100_000_000, timestamp: now }, now);
+    assert_eq!(annotation_cached("CFXN", &Nat::from(3u32), 0, now).as_deref(), Some("≈ Rp 48.000 / $3.00"));
+
+    assert_eq!(cached_rate("CFXN", "IDR", now + PRICE_MAX_AGE_NS + 1), None);
+    assert_eq!(annotation_cached("CFXN", &Nat::from(3u32), 0, now + PRICE_MAX_AGE_NS + 1), None);
+    assert_eq!(cached_rate("ICP", "IDR", now), None);
+}
@@ -1,25 +1,38 @@
 use candid::{Decode, Encode, Principal};
 use pocket_ic::PocketIc;
-use backend::types::SavedAccount;
+use backend::types::{
+    ChatResponse, Config, DexQuote, DexQuoteArgs, DexSwapArgs, DexSwapResult, DexWithdrawArgs, HistoryArgs, InitArgs, LlmModel,
+    SavedAccount, SpendingPolicy, TxPage,
+};
+use icrc_ledger_types::icrc1::account::Account;
 
-fn ensure_wasm() {
-    let wasm_path = std::path::Path::new("../target/wasm32-unknown-unknown/release/backend.wasm");
-    if !wasm_path.exists() {
+fn ensure_wasm(package: &str) -> Vec<u8> {
+    let path = format!("../target/wasm32-unknown-unknown/release/{}.wasm", package);
+    if !std::path::Path::new(&path).exists() {
         let status = std::process::Command::new("cargo")
-            .args(["build", "--target", "wasm32-unknown-unknown", "--release", "-p", "backend"])
+            .args(["build", "--target", "wasm32-unknown-unknown", "--release", "-p", package])
             .status()
             .expect("failed to build wasm");
         assert!(status.success());
     }
+    std::fs::read(&path).expect("wasm not found")
 }
 
-fn setup_pic() -> (PocketIc, Principal) {
-    ensure_wasm();
-    let wasm = std::fs::read("../target/wasm32-unknown-unknown/release/backend.wasm").expect("wasm not found");
-    let pic = PocketIc::new();
+fn install_with_args(pic: &PocketIc, package: &str, args: Vec<u8>) -> Principal {
+    let wasm = ensure_wasm(package);
     let canister_id = pic.create_canister();
     pic.add_cycles(canister_id, 2_000_000_000_000u128);
-    pic.install_canister(canister_id, wasm, vec![], None);
+    pic.install_canister(canister_id, wasm, args, None);
+    canister_id
+}
+
+fn install(pic: &PocketIc, package: &str) -> Principal {
+    install_with_args(pic, package, Encode!().unwrap())
+}
+
+fn setup_pic() -> (PocketIc, Principal) {
+    let pic = PocketIc::new();
+    let canister_id = install(&pic, "backend");
     (pic, canister_id)
 }
 
@@ -75,3 +88,210 @@ fn pocket_ic_save_account_errors() {
     let accounts: Vec<SavedAccount> = Decode!(&res_bytes, Vec<SavedAccount>).unwrap();
     assert!(accounts.is_empty());
 }
+
+#[test]
+fn pocket_ic_set_and_get_policy() {
+    let (pic, canister_id) = setup_pic();
+
+    let bad = SpendingPolicy { allowed_recipients_only: true, ..Default::default() };
+    let reply = pic
+        .update_call(canister_id, Principal::anonymous(), "set_policy", Encode!(&bad).unwrap())
+        .unwrap();
+    let res: Result<(), String> = Decode!(&reply, Result<(), String>).unwrap();
+    assert!(res.is_err());
+
+    let policy = SpendingPolicy {
+        allowed_recipients_only: true,
+        allowed_recipients: vec![Principal::management_canister()],
+        ..Default::default()
+    };
+    let reply = pic
+        .update_call(canister_id, Principal::anonymous(), "set_policy", Encode!(&policy).unwrap())
+        .unwrap();
+    let res: Result<(), String> = Decode!(&reply, Result<(), String>).unwrap();
+    assert!(res.is_ok());
+
+    let res_bytes = pic
+        .query_call(canister_id, Principal::anonymous(), "get_policy", Encode!().unwrap())
+        .unwrap();
+    let stored: SpendingPolicy = Decode!(&res_bytes, SpendingPolicy).unwrap();
+    assert!(stored.allowed_recipients_only);
+    assert_eq!(stored.allowed_recipients, vec![Principal::management_canister()]);
+}
+
+#[test]
+fn pocket_ic_mock_dex_enforces_min_out() {
+    let pic = PocketIc::new();
+    let dex = install(&pic, "mock_dex");
+    let (a, b) = (Principal::from_slice(&[1; 10]), Principal::from_slice(&[2; 10]));
+    let seed = Encode!(&a, &b, &candid::Nat::from(1_000_000u32), &candid::Nat::from(2_000_000u32)).unwrap();
+    pic.update_call(dex, Principal::anonymous(), "set_pool", seed).unwrap();
+
+    let q = DexQuoteArgs { token_in: a, token_out: b, amount_in: candid::Nat::from(10_000u32) };
+    let reply = pic.query_call(dex, Principal::anonymous(), "quote", Encode!(&q).unwrap()).unwrap();
+    let quote = Decode!(&reply, Result<DexQuote, String>).unwrap().unwrap();
+    assert!(quote.amount_out > 0u32 && quote.amount_out < 20_000u32);
+
+    let mut swap = DexSwapArgs {
+        token_in: a,
+        token_out: b,
+        amount_in: q.amount_in.clone(),
+        min_amount_out: quote.amount_out.clone() + 1u32,
+        deposit_subaccount: [0; 32],
+        recipient: Account { owner: Principal::anonymous(), subaccount: None },
+    };
+    let reply = pic.update_call(dex, Principal::anonymous(), "swap", Encode!(&swap).unwrap()).unwrap();
+    assert!(Decode!(&reply, Result<DexSwapResult, String>).unwrap().is_err());
+
+    swap.min_amount_out = quote.amount_out.clone();
+    let reply = pic.update_call(dex, Principal::anonymous(), "swap", Encode!(&swap).unwrap()).unwrap();
+    let res = Decode!(&reply, Result<DexSwapResult, String>).unwrap().unwrap();
+    assert_eq!(res.amount_out, quote.amount_out);
+}
+
+#[test]
+fn pocket_ic_mock_dex_refunds_failed_swap() {
+    let pic = PocketIc::new();
+    let dex = install(&pic, "mock_dex");
+    let (a, b) = (Principal::from_slice(&[1; 10]), Principal::from_slice(&[2; 10]));
+    let seed = Encode!(&a, &b, &candid::Nat::from(1_000_000u32), &candid::Nat::from(2_000_000u32)).unwrap();
+    pic.update_call(dex, Principal::anonymous(), "set_pool", seed).unwrap();
+
+    let recipient = Account { owner: Principal::anonymous(), subaccount: None };
+    let swap = DexSwapArgs {
+        token_in: a,
+        token_out: b,
+        amount_in: candid::Nat::from(10_000u32),
+        min_amount_out: candid::Nat::from(1_000_000u32),
+        deposit_subaccount: [7; 32],
+        recipient,
+    };
+    let reply = pic.update_call(dex, Principal::anonymous(), "swap", Encode!(&swap).unwrap()).unwrap();
+    assert!(Decode!(&reply, Result<DexSwapResult, String>).unwrap().is_err());
+
+    let withdraw = DexWithdrawArgs { token: a, amount: swap.amount_in.clone(), deposit_subaccount: [7; 32], recipient };
+    let reply = pic.update_call(dex, Principal::anonymous(), "withdraw", Encode!(&withdraw).unwrap()).unwrap();
+    assert_eq!(Decode!(&reply, Result<candid::Nat, String>).unwrap().unwrap(), swap.amount_in);
+
+    // the refunded deposit can be neither withdrawn again nor swapped
+    let reply = pic.update_call(dex, Principal::anonymous(), "withdraw", Encode!(&withdraw).unwrap()).unwrap();
+    assert!(Decode!(&reply, Result<candid::Nat, String>).unwrap().is_err());
+    let retry = DexSwapArgs { min_amount_out: candid::Nat::from(0u32), ..swap };
+    let reply = pic.update_call(dex, Principal::anonymous(), "swap", Encode!(&retry).unwrap()).unwrap();
+    assert!(Decode!(&reply, Result<DexSwapResult, String>).unwrap().is_err());
+    let reply = pic.query_call(dex, Principal::anonymous(), "withdraw_count", Encode!().unwrap()).unwrap();
+    assert_eq!(Decode!(&reply, u64).unwrap(), 1);
+}
+
+#[test]
+fn pocket_ic_set_dex_canister_requires_controller() {
+    let (pic, canister_id) = setup_pic();
+    let dex = Some(Principal::from_slice(&[3; 10]));
+
+    let stranger = Principal::from_slice(&[9; 29]);
+    let reply = pic.update_call(canister_id, stranger, "set_dex_canister", Encode!(&dex).unwrap()).unwrap();
+    assert!(Decode!(&reply, Result<(), String>).unwrap().is_err());
+
+    // canisters created by PocketIc::create_canister are controlled by the anonymous principal
+    let reply = pic.update_call(canister_id, Principal::anonymous(), "set_dex_canister", Encode!(&dex).unwrap()).unwrap();
+    assert!(Decode!(&reply, Result<(), String>).unwrap().is_ok());
+
+    let reply = pic.query_call(canister_id, Principal::anonymous(), "get_config", Encode!().unwrap()).unwrap();
+    assert_eq!(Decode!(&reply, Config).unwrap().dex_canister, dex);
+}
+
+#[test]
+fn pocket_ic_fiat_value_from_mock_oracle() {
+    let (pic, canister_id) = setup_pic();
+    let oracle = install(&pic, "mock_oracle");
+    // 1 CFXN = Rp 16.000 = $1
+    let seed = Encode!(&"CFXN".to_string(), &"IDR".to_string(), &1_600_000_000_000u64).unwrap();
+    pic.update_call(oracle, Principal::anonymous(), "set_price", seed).unwrap();
+    let seed = Encode!(&"CFXN".to_string(), &"USD".to_string(), &100_000_000u64).unwrap();
+    pic.update_call(oracle, Principal::anonymous(), "set_price", seed).unwrap();
+
+    let args = Encode!(&"CFXN".to_string(), &"3".to_string()).unwrap();
+    let reply = pic.update_call(canister_id, Principal::anonymous(), "get_fiat_value", args.clone()).unwrap();
+    assert_eq!(Decode!(&reply, Result<Option<String>, String>).unwrap().unwrap(), None);
+
+    let reply = pic.update_call(canister_id, Principal::anonymous(), "set_oracle_canister", Encode!(&Some(oracle)).unwrap()).unwrap();
+    assert!(Decode!(&reply, Result<(), String>).unwrap().is_ok());
+    let reply = pic.update_call(canister_id, Principal::anonymous(), "get_fiat_value", args).unwrap();
+    let value = Decode!(&reply, Result<Option<String>, String>).unwrap().unwrap();
+    assert_eq!(value.as_deref(), Some("≈ Rp 48.000 / $3.00"));
+}
+
+#[test]
+fn pocket_ic_account_transactions_page_through_mock_index() {
+    let (pic, canister_id) = setup_pic();
+    let index = install(&pic, "mock_index");
+    let me = Account { owner: Principal::from_slice(&[7; 29]), subaccount: None };
+    let other = Account { owner: Principal::from_slice(&[8; 29]), subaccount: None };
+    for (from, to, amount) in [(other, me, 100u32), (me, other, 30), (other, me, 5)] {
+        let args = Encode!(&from, &to, &candid::Nat::from(amount), &Option::<Vec<u8>>::None).unwrap();
+        pic.update_call(index, Principal::anonymous(), "record_transfer", args).unwrap();
+    }
+
+    let query = HistoryArgs { symbol: Some("CFXN".into()), max_results: Some(2), ..Default::default() };
+    let reply = pic.update_call(canister_id, me.owner, "get_account_transactions", Encode!(&query).unwrap()).unwrap();
+    assert!(Decode!(&reply, Result<TxPage, String>).unwrap().unwrap_err().contains("NoIndex"));
+
+    let args = Encode!(&"CFXN".to_string(), &Some(index)).unwrap();
+    let reply = pic.update_call(canister_id, Principal::anonymous(), "set_index_canister", args).unwrap();
+    assert!(Decode!(&reply, Result<(), String>).unwrap().is_ok());
+
+    let reply = pic.update_call(canister_id, me.owner, "get_account_transactions", Encode!(&query).unwrap()).unwrap();
+    let page = Decode!(&reply, Result<TxPage, String>).unwrap().unwrap();
+    assert_eq!(page.balance_dec, "75");
+    let dirs: Vec<&str> = page.transactions.iter().map(|t| t.direction.as_str()).collect();
+    assert_eq!(dirs, ["in", "out"]);
+    assert_eq!(page.next_start.as_deref(), Some("1"));
+
+    let next = HistoryArgs { start: page.next_start, ..query };
+    let reply = pic.update_call(canister_id, me.owner, "get_account_transactions", Encode!(&next).unwrap()).unwrap();
+    let page = Decode!(&reply, Result<TxPage, String>).unwrap().unwrap();
+    assert_eq!(page.transactions.len(), 1);
+    assert_eq!(page.transactions[0].amount_dec, "100");
+    assert_eq!(page.next_start, None);
+}
+
+#[test]
+fn pocket_ic_model_from_init_args_and_admin() {
+    let pic = PocketIc::new();
+    let args = Some(InitArgs { model: Some(LlmModel::Qwen3_32B), fallback_model: Some(LlmModel::Llama4Scout) });
+    let canister_id = install_with_args(&pic, "backend", Encode!(&args).unwrap());
+
+    let reply = pic.query_call(canister_id, Principal::anonymous(), "get_config", Encode!().unwrap()).unwrap();
+    let config = Decode!(&reply, Config).unwrap();
+    assert_eq!((config.model, config.fallback_model), (LlmModel::Qwen3_32B, Some(LlmModel::Llama4Scout)));
+
+    let change = Encode!(&LlmModel::Llama3_1_8B, &Option::<LlmModel>::None).unwrap();
+    let reply = pic.update_call(canister_id, Principal::from_slice(&[9; 29]), "set_model", change.clone()).unwrap();
+    assert!(Decode!(&reply, Result<(), String>).unwrap().is_err());
+    let reply = pic.update_call(canister_id, Principal::anonymous(), "set_model", change).unwrap();
+    assert!(Decode!(&reply, Result<(), String>).unwrap().is_ok());
+
+    let reply = pic.query_call(canister_id, Principal::anonymous(), "get_config", Encode!().unwrap()).unwrap();
+    let config = Decode!(&reply, Config).unwrap();
+    assert_eq!((config.model, config.fallback_model), (LlmModel::Llama3_1_8B, None));
+}
+
+#[test]
+fn pocket_ic_confirm_refuses_replaced_plan() {
+    let (pic, canister_id) = setup_pic();
+    let user = Principal::from_slice(&[7; 29]);
+    let reply = pic.update_call(canister_id, user, "start_session", Encode!().unwrap()).unwrap();
+    let session = Decode!(&reply, u64).unwrap();
+
+    let mut checksums = Vec::new();
+    for text in ["send 10 CFXN to aaaaa-aa", "send 11 CFXN to aaaaa-aa"] {
+        let args = Encode!(&session, &text.to_string(), &Option::<LlmModel>::None).unwrap();
+        let reply = pic.update_call(canister_id, user, "copilot_chat", args).unwrap();
+        let resp = Decode!(&reply, Result<ChatResponse, String>).unwrap().unwrap();
+        checksums.push(resp.pending_plan.expect("plan").checksum().to_string());
+    }
+
+    let reply = pic.update_call(canister_id, user, "confirm", Encode!(&checksums[0]).unwrap()).unwrap();
+    let res = Decode!(&reply, Result<String, String>).unwrap();
+    assert_eq!(res.unwrap_err(), "not the pending plan");
+}
@@ -0,0 +1,118 @@
+use candid::{Nat, Principal};
+
+use backend::policy::*;
+use backend::types::{PolicyViolation, QuietHours, Receipt, RecipientCap, SpendingPolicy, TokenLimit};
+use backend::utils::NANOS_PER_DAY;
+
+mod common;
+use common::{p, plan};
+
+const NOW: u64 = 30 * NANOS_PER_DAY;
+
+fn receipt(amount: u32, executed_at: u64) -> Receipt {
+    Receipt {
+        checksum: String::new(),
+        owner: Principal::anonymous(),
+        to_principal: p(2),
+        to_sub: None,
+        amount: Nat::from(amount),
+        symbol: "CFXN".to_string(),
+        ledger: Principal::management_canister(),
+        memo: None,
+        block_index: Nat::from(1u32),
+        executed_at,
+        invoice_id: None,
+        memo_text: None,
+        swap: None,
+        fiat_value: None,
+    }
+}
+
+fn limits(per_tx: Option<u32>, daily: Option<u32>, weekly: Option<u32>) -> SpendingPolicy {
+    SpendingPolicy {
+        token_limits: vec![TokenLimit {
+            symbol: "cfxn".into(),
+            per_tx_max: per_tx.map(Nat::from),
+            daily_cap: daily.map(Nat::from),
+            weekly_cap: weekly.map(Nat::from),
+        }],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_token_limits() {
+    let history = vec![receipt(40, NOW - NANOS_PER_DAY / 2), receipt(50, NOW - 3 * NANOS_PER_DAY)];
+    assert_eq!(check(&limits(Some(20), None, None), &history, &plan(25), NOW),
+        Err(PolicyViolation::PerTxMax { limit: Nat::from(20u32) }));
+    assert_eq!(check(&limits(None, Some(50), None), &history, &plan(15), NOW),
+        Err(PolicyViolation::DailyCap { limit: Nat::from(50u32), spent: Nat::from(40u32) }));
+    assert!(check(&limits(None, Some(50), None), &history, &plan(10), NOW).is_ok());
+    assert_eq!(check(&limits(None, None, Some(100)), &history, &plan(11), NOW).unwrap_err().code(), "PolicyWeeklyCap");
+}
+
+#[test]
+fn test_recipient_rules() {
+    let mut policy = SpendingPolicy {
+        recipient_caps: vec![RecipientCap { recipient: p(2), symbol: "CFXN".into(), daily_cap: Nat::from(30u32) }],
+        ..Default::default()
+    };
+    let history = vec![receipt(25, NOW - 10)];
+    assert_eq!(check(&policy, &history, &plan(10), NOW).unwrap_err().code(), "PolicyRecipientCap");
+
+    policy.recipient_caps.clear();
+    policy.allowed_recipients_only = true;
+    policy.allowed_recipients = vec![Principal::anonymous()];
+    assert_eq!(check(&policy, &[], &plan(1), NOW), Err(PolicyViolation::RecipientNotAllowed));
+    policy.allowed_recipients.push(p(2));
+    assert!(check(&policy, &[], &plan(1), NOW).is_ok());
+}
+
+#[test]
+fn test_quiet_hours() {
+    // 23:00 UTC is 06:00 WIB (UTC+7)
+    let now = NOW + 23 * 3_600 * 1_000_000_000;
+    assert_eq!(local_hour(now, 420), 6);
+    let p = SpendingPolicy {
+        quiet_hours: Some(QuietHours { start_hour: 22, end_hour: 7, utc_offset_minutes: 420 }),
+        ..Default::default()
+    };
+    assert_eq!(check(&p, &[], &plan(1), now).unwrap_err().code(), "PolicyQuietHours");
+    assert!(check(&p, &[], &plan(1), NOW + 3 * 3_600 * 1_000_000_000).is_ok());
+}
+
+#[test]
+fn test_validate() {
+    assert!(validate(&SpendingPolicy::default()).is_ok());
+    let p = SpendingPolicy { allowed_recipients_only: true, ..Default::default() };
+    assert!(validate(&p).is_err());
+    let p = SpendingPolicy {
+        quiet_hours: Some(QuietHours { start_hour: 24, end_hour: 7, utc_offset_minutes: 0 }),
+        ..Default::default()
+    };
+    assert!(validate(&p).is_err());
+}
+
+#[test]
+fn test_pending_spends_count_toward_caps() {
+    set(Principal::anonymous(), limits(None, Some(100), None)).unwrap();
+    let mut in_flight = receipt(80, NOW);
+    in_flight.checksum = "abc".into();
+    reserve(in_flight);
+    assert_eq!(check_owner(Principal::anonymous(), &plan(30), NOW),
+        Err(PolicyViolation::DailyCap { limit: Nat::from(100u32), spent: Nat::from(80u32) }));
+    release("abc");
+    assert!(check_owner(Principal::anonymous(), &plan(30), NOW).is_ok());
+}
+
+#[test]
+fn test_snapshot_survives_upgrade_round_trip() {
+    set(p(2), limits(Some(20), Some(50), None)).unwrap();
+    let bytes = candid::encode_one(snapshot()).unwrap();
+
+    // a fresh canister after the upgrade
+    restore(Vec::new());
+    assert!(check_owner(p(2), &plan(25), NOW).is_ok());
+    restore(candid::decode_one(&bytes).unwrap());
+    assert_eq!(check_owner(p(2), &plan(25), NOW), Err(PolicyViolation::PerTxMax { limit: Nat::from(20u32) }));
+}
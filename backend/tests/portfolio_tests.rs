@@ -0,0 +1,36 @@
+use candid::{Nat, Principal};
+use icrc_ledger_types::icrc2::allowance::Allowance;
+
+use backend::portfolio::*;
+use backend::types::TOKENS;
+
+fn allowance(amount: u64, expires_at: Option<u64>) -> Result<Allowance, String> {
+    Ok(Allowance { allowance: Nat::from(amount), expires_at })
+}
+
+#[test]
+fn test_entry_formats_balance_and_allowance() {
+    let icp = &TOKENS[0];
+    let ledger = Some(Principal::from_slice(&[1; 10]));
+    let e = entry(icp, ledger, Ok(Nat::from(150_000_000u64)), allowance(10_000, Some(100)), 50);
+    assert_eq!(e.balance_dec.as_deref(), Some("1.5"));
+    assert_eq!(e.allowance, Some(Nat::from(10_000u32)));
+    assert_eq!(e.allowance_expires_at, Some(100));
+    assert!(e.errors.is_empty());
+
+    let expired = entry(icp, ledger, Ok(Nat::from(1u32)), allowance(10_000, Some(100)), 100);
+    assert_eq!(expired.allowance, Some(Nat::from(0u32)));
+    assert_eq!(expired.allowance_expires_at, None);
+}
+
+#[test]
+fn test_entry_tolerates_ledger_failures() {
+    let cfxn = &TOKENS[1];
+    let e = entry(cfxn, None, Err("canister stopped".into()), allowance(5, None), 0);
+    assert_eq!(e.balance, None);
+    assert_eq!(e.allowance, Some(Nat::from(5u32)));
+    assert_eq!(e.errors, vec!["balance: canister stopped".to_string()]);
+
+    let ok = entry(&TOKENS[0], None, Ok(Nat::from(250_000_000u64)), Err("x".into()), 0);
+    assert_eq!(describe(&[ok, e]), "Balances: ICP 2.5, CFXN unavailable.");
+}
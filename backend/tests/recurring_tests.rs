@@ -0,0 +1,96 @@
+use candid::Principal;
+
+use backend::recurring::*;
+use backend::types::{Frequency, RecurringStatus, RecurringTransfer};
+use backend::utils::{format_date_ns, parse_date_ns, NANOS_PER_DAY};
+
+mod common;
+use common::plan;
+
+fn sample_series(max_count: Option<u32>, end_at: Option<u64>) -> RecurringTransfer {
+    RecurringTransfer {
+        id: 1,
+        owner: Principal::anonymous(),
+        plan: plan(10),
+        frequency: Frequency::Monthly,
+        anchor_at: 0,
+        slot: 0,
+        next_run_at: 0,
+        end_at,
+        max_count,
+        executed_count: 2,
+        status: RecurringStatus::Active,
+        last_block_index: None,
+        last_error: None,
+        failure_reported: true,
+    }
+}
+
+#[test]
+fn test_frequency_parse() {
+    assert_eq!(Frequency::parse("Monthly"), Some(Frequency::Monthly));
+    assert_eq!(Frequency::parse("mingguan"), Some(Frequency::Weekly));
+    assert_eq!(Frequency::parse("yearly"), None);
+}
+
+#[test]
+fn test_occurrence_at_keeps_month_anchor() {
+    let anchor = parse_date_ns("2025-01-31").unwrap();
+    assert_eq!(format_date_ns(occurrence_at(anchor, Frequency::Monthly, 1)), "2025-02-28");
+    assert_eq!(format_date_ns(occurrence_at(anchor, Frequency::Monthly, 2)), "2025-03-31");
+    assert_eq!(occurrence_at(anchor, Frequency::Weekly, 2), anchor + 14 * NANOS_PER_DAY);
+}
+
+#[test]
+fn test_next_slot_after_skips_missed_slots() {
+    let anchor = parse_date_ns("2025-01-01").unwrap();
+    let now = parse_date_ns("2025-01-10").unwrap();
+    let (slot, at) = next_slot_after(anchor, Frequency::Daily, 0, now);
+    assert_eq!(slot, 10);
+    assert_eq!(format_date_ns(at), "2025-01-11");
+}
+
+#[test]
+fn test_is_finished() {
+    assert!(is_finished(&sample_series(Some(2), None), 5));
+    assert!(!is_finished(&sample_series(Some(3), None), 5));
+    assert!(is_finished(&sample_series(None, Some(4)), 5));
+    assert!(!is_finished(&sample_series(None, None), 5));
+}
+
+#[test]
+fn test_failure_notice() {
+    assert!(failure_notice(&[]).is_none());
+    let mut s = sample_series(None, None);
+    s.status = RecurringStatus::Paused;
+    s.last_error = Some("ExecError: ICRC2::InsufficientAllowance".into());
+    match failure_notice(&[s]) {
+        Some(ic_llm::ChatMessage::System { content }) => {
+            assert!(content.contains("PAUSED"));
+            assert!(content.contains("InsufficientAllowance"));
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn test_snapshot_survives_upgrade_round_trip() {
+    let mut paused = sample_series(Some(12), None);
+    paused.id = 2;
+    paused.status = RecurringStatus::Paused;
+    paused.last_error = Some("InsufficientAllowance: 0".into());
+    restore((vec![sample_series(Some(12), None), paused], 2));
+    let bytes = candid::encode_one(snapshot()).unwrap();
+
+    // a fresh canister after the upgrade
+    restore((Vec::new(), 0));
+    assert!(list(Principal::anonymous()).is_empty());
+    restore(candid::decode_one(&bytes).unwrap());
+    let back = list(Principal::anonymous());
+    assert_eq!(back.len(), 2);
+    assert_eq!(back[0].executed_count, 2);
+    assert_eq!(back[0].status, RecurringStatus::Active);
+    assert_eq!(back[1].status, RecurringStatus::Paused);
+    assert_eq!(back[1].last_error.as_deref(), Some("InsufficientAllowance: 0"));
+    assert_eq!(snapshot().1, 2);
+}
@@ -0,0 +1,77 @@
+use backend::session::*;
+use candid::Principal;
+use ic_llm::ChatMessage;
+
+const NOW: u64 = 1_700_000_000_000_000_000;
+
+fn user(n: usize) -> ChatMessage {
+    ChatMessage::User { content: format!("m{}", n) }
+}
+
+#[test]
+fn test_start_and_get_checks_owner_and_expiry() {
+    let alice = Principal::from_slice(&[1; 29]);
+    let bob = Principal::from_slice(&[2; 29]);
+    let s = start(alice, NOW);
+    assert_eq!(get(alice, s.id, NOW).unwrap().owner, alice);
+    assert!(get(bob, s.id, NOW).unwrap_err().contains("unknown session"));
+    assert!(get(alice, s.id + 100, NOW).is_err());
+
+    append(s.id, vec![user(1)], Some("abc".into()), true, NOW + 10);
+    let got = get(alice, s.id, NOW + 10).unwrap();
+    assert_eq!(got.messages.len(), 1);
+    assert_eq!(got.pending_checksum.as_deref(), Some("abc"));
+    assert!(got.pending_in_last_turn);
+    assert!(get(alice, s.id, NOW + 11 + SESSION_TTL_NS).unwrap_err().contains("expired"));
+
+    // expired sessions are purged on the next start
+    start(bob, NOW + 11 + SESSION_TTL_NS);
+    assert!(get(alice, s.id, NOW).unwrap_err().contains("unknown session"));
+}
+
+#[test]
+fn test_forget_pending_clears_only_that_checksum() {
+    let dave = Principal::from_slice(&[4; 29]);
+    let a = start(dave, NOW);
+    let b = start(dave, NOW);
+    append(a.id, vec![], Some("abc".into()), true, NOW);
+    append(b.id, vec![], Some("def".into()), false, NOW);
+    forget_pending(dave, "abc");
+    assert_eq!(get(dave, a.id, NOW).unwrap().pending_checksum, None);
+    assert!(!get(dave, a.id, NOW).unwrap().pending_in_last_turn);
+    assert_eq!(get(dave, b.id, NOW).unwrap().pending_checksum.as_deref(), Some("def"));
+    forget_pending(Principal::from_slice(&[5; 29]), "def");
+    assert_eq!(get(dave, b.id, NOW).unwrap().pending_checksum.as_deref(), Some("def"));
+}
+
+#[test]
+fn test_start_evicts_least_recently_used() {
+    let carol = Principal::from_slice(&[3; 29]);
+    let ids: Vec<u64> = (0..MAX_SESSIONS_PER_CALLER as u64).map(|i| start(carol, NOW + i).id).collect();
+    // touching the oldest keeps it alive
+    append(ids[0], vec![user(0)], None, false, NOW + 100);
+    let newest = start(carol, NOW + 200);
+    assert!(get(carol, ids[0], NOW + 200).is_ok());
+    assert!(get(carol, ids[1], NOW + 200).is_err());
+    assert!(get(carol, newest.id, NOW + 200).is_ok());
+}
+
+#[test]
+fn test_trim_keeps_latest_and_drops_orphan_tool_results() {
+    let mut msgs: Vec<ChatMessage> = (0..MAX_SESSION_MESSAGES + 3).map(user).collect();
+    trim(&mut msgs);
+    assert_eq!(msgs.len(), MAX_SESSION_MESSAGES);
+    assert!(matches!(&msgs[0], ChatMessage::User { content } if content == "m3"));
+
+    let mut msgs: Vec<ChatMessage> = (0..MAX_SESSION_MESSAGES).map(user).collect();
+    msgs.insert(1, ChatMessage::Tool { tool_call_id: "t".into(), content: "{}".into() });
+    trim(&mut msgs);
+    assert!(matches!(&msgs[0], ChatMessage::User { content } if content == "m1"));
+}
+
+#[test]
+fn test_validate_text() {
+    assert!(validate_text("hello").is_ok());
+    assert!(validate_text("   ").is_err());
+    assert!(validate_text(&"a".repeat(MAX_USER_TEXT_BYTES + 1)).is_err());
+}
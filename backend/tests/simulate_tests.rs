@@ -0,0 +1,71 @@
+use candid::{Nat, Principal};
+use icrc_ledger_types::icrc2::allowance::Allowance;
+
+use backend::simulate::*;
+use backend::types::{CheckStatus, SimulationReport, TransferPlan};
+use backend::utils::PLAN_TTL_NS;
+
+mod common;
+use common::with_checksum;
+
+fn plan() -> TransferPlan {
+    with_checksum(TransferPlan { created_at_time: 1_000, ..common::plan(100) })
+}
+
+fn view(balance: u64, allowance: u64) -> LedgerView {
+    LedgerView {
+        fee: Ok(Nat::from(1u32)),
+        balance: Ok(Nat::from(balance)),
+        allowance: Ok(Allowance { allowance: Nat::from(allowance), expires_at: None }),
+    }
+}
+
+fn status(r: &SimulationReport, name: &str) -> CheckStatus {
+    r.checks.iter().find(|c| c.name == name).unwrap().status
+}
+
+#[test]
+fn test_all_checks_pass() {
+    let r = evaluate(&plan(), &view(101, 101), 2_000);
+    assert!(r.ok, "{:?}", r.checks);
+}
+
+#[test]
+fn test_fee_counts_towards_balance_and_allowance() {
+    let r = evaluate(&plan(), &view(100, 100), 2_000);
+    assert!(!r.ok);
+    assert_eq!(status(&r, "balance"), CheckStatus::Fail);
+    assert_eq!(status(&r, "allowance"), CheckStatus::Fail);
+    assert_eq!(status(&r, "fee"), CheckStatus::Pass);
+}
+
+#[test]
+fn test_batch_legs_must_fit_together() {
+    let mut second = plan();
+    second.to_principal = Principal::from_slice(&[3; 29]);
+    let legs = [plan(), second];
+    // each leg needs 101, which fits on its own, but together they need 202
+    let views = [view(150, 150), view(150, 150)];
+    let reports = evaluate_all(&legs, &views, 2_000);
+    assert_eq!(reports.len(), 2);
+    for r in &reports {
+        assert_eq!(status(r, "balance"), CheckStatus::Fail);
+        assert_eq!(status(r, "allowance"), CheckStatus::Fail);
+    }
+    assert!(reports[0].checks.iter().any(|c| c.detail == "balance 150 needs 202 for all transfers"));
+    assert!(evaluate_all(&legs, &[view(202, 202), view(202, 202)], 2_000).iter().all(|r| r.ok));
+}
+
+#[test]
+fn test_expiry_allowlist_and_unreachable_ledger() {
+    let mut p = plan();
+    let r = evaluate(&p, &view(500, 500), 1_000 + PLAN_TTL_NS + 1);
+    assert_eq!(status(&r, "expiry"), CheckStatus::Fail);
+
+    p.ledger = Principal::management_canister();
+    let down = LedgerView { fee: Err("down".into()), balance: Err("down".into()), allowance: Err("down".into()) };
+    let r = evaluate(&p, &down, 2_000);
+    assert_eq!(status(&r, "allowlist"), CheckStatus::Fail);
+    assert_eq!(status(&r, "balance"), CheckStatus::Unknown);
+    assert!(!r.ok);
+}
@@ -0,0 +1,66 @@
+use candid::Nat;
+
+use backend::split::*;
+use backend::types::SplitArgs;
+
+mod common;
+use common::p;
+
+fn nats(v: &[u64]) -> Vec<Nat> {
+    v.iter().map(|n| Nat::from(*n)).collect()
+}
+
+#[test]
+fn test_allocate_equal_distributes_remainder_in_order() {
+    assert_eq!(allocate(&Nat::from(100u32), &nats(&[1, 1, 1])), nats(&[34, 33, 33]));
+    assert_eq!(allocate(&Nat::from(101u32), &nats(&[1, 1, 1])), nats(&[34, 34, 33]));
+    assert_eq!(allocate(&Nat::from(2u32), &nats(&[1, 1, 1])), nats(&[1, 1, 0]));
+}
+
+#[test]
+fn test_allocate_weights_largest_fraction_first() {
+    // 10 * 1/6 = 1.67, 10 * 2/6 = 3.33, 10 * 3/6 = 5
+    assert_eq!(allocate(&Nat::from(10u32), &nats(&[1, 2, 3])), nats(&[2, 3, 5]));
+    let shares = allocate(&Nat::from(1_000_000u32), &nats(&[1, 1, 1, 1, 1, 1, 1]));
+    let sum = shares.iter().fold(Nat::from(0u32), |a, s| a + s.clone());
+    assert_eq!(sum, Nat::from(1_000_000u32));
+}
+
+#[test]
+fn test_parse_weights() {
+    assert_eq!(parse_weights("2, 1,1", 3, false).unwrap().len(), 3);
+    assert!(parse_weights("2,1", 3, false).is_err());
+    assert!(parse_weights("50%,25%,25%", 3, true).is_ok());
+    assert!(parse_weights("33.3,33.3,33.4", 3, true).is_ok());
+    assert!(parse_weights("50,30,30", 3, true).is_err());
+    assert!(parse_weights("0,0", 2, false).is_err());
+}
+
+#[test]
+fn test_is_self() {
+    assert!(is_self(" Me ", p(1)));
+    assert!(is_self("saya", p(1)));
+    assert!(is_self(&p(1).to_text(), p(1)));
+    assert!(!is_self(&p(2).to_text(), p(1)));
+}
+
+#[test]
+fn test_plan_excludes_own_share() {
+    let args = SplitArgs {
+        total_dec: "100".into(),
+        recipients: format!("{}, {}, me", p(2).to_text(), p(3).to_text()),
+        symbol: Some("CFXN".into()),
+        mode: None,
+        weights: None,
+        memo: None,
+    };
+    let batch = plan(p(1), &args, 1_000).unwrap();
+    assert_eq!(batch.legs.len(), 2);
+    assert_eq!(batch.legs[0].amount, Nat::from(34u32));
+    assert_eq!(batch.legs[1].amount, Nat::from(33u32));
+    assert_ne!(batch.legs[0].checksum, batch.legs[1].checksum);
+    assert!(batch.human_readable.contains("Your share 33"));
+
+    let only_me = SplitArgs { recipients: "me".into(), ..args };
+    assert!(plan(p(1), &only_me, 1_000).is_err());
+}
@@ -0,0 +1,40 @@
+use candid::{Nat, Principal};
+
+use backend::swap::*;
+use backend::types::{DexQuote, SwapPlanArgs};
+
+fn args(symbol_in: &str, symbol_out: &str, slippage: Option<&str>) -> SwapPlanArgs {
+    SwapPlanArgs {
+        amount_in_dec: "10".into(),
+        symbol_in: symbol_in.into(),
+        symbol_out: symbol_out.into(),
+        slippage_pct: slippage.map(str::to_string),
+    }
+}
+
+#[test]
+fn test_parse_slippage() {
+    assert_eq!(parse_slippage(None), Ok(DEFAULT_SLIPPAGE_BPS));
+    assert_eq!(parse_slippage(Some("1%")), Ok(100));
+    assert_eq!(parse_slippage(Some("0.25")), Ok(25));
+    assert!(parse_slippage(Some("0")).is_err());
+    assert!(parse_slippage(Some("60")).is_err());
+    assert!(parse_slippage(Some("abc")).is_err());
+}
+
+#[test]
+fn test_min_out_rounds_down() {
+    assert_eq!(min_out(&Nat::from(1_000u32), 50), Nat::from(995u32));
+    assert_eq!(min_out(&Nat::from(999u32), 100), Nat::from(989u32));
+}
+
+#[test]
+fn test_build_rejects_bad_pairs_and_empty_quotes() {
+    let quote = DexQuote { amount_out: Nat::from(0u32), price_impact_bps: 0, fee: Nat::from(0u32) };
+    let dex = Principal::from_slice(&[3; 10]);
+    let owner = Principal::from_slice(&[1; 29]);
+    let err = build(owner, dex, &args("CFXN", "cfxn", None), &quote, 1).unwrap_err();
+    assert_eq!(err["code"], "BadToken");
+    let err = build(owner, dex, &args("CFXN", "DOGE", None), &quote, 1).unwrap_err();
+    assert_eq!(err["field"], "symbol_out");
+}
@@ -0,0 +1,158 @@
+use candid::Principal;
+use futures::executor::block_on;
+use futures::future::LocalBoxFuture;
+use ic_llm::{FunctionCall, ToolCall};
+use serde_json::{json, Value};
+
+use backend::tools::*;
+use backend::types::{PlanArgs, RecurringArgs};
+
+struct EchoTool;
+
+impl Tool for EchoTool {
+    fn name(&self) -> &'static str { "echo" }
+    fn description(&self) -> Option<&'static str> { Some("Echo the text") }
+    fn params(&self) -> Vec<Param> {
+        vec![Param::string("text").required(), Param::string("mode").one_of(&["loud", "quiet"])]
+    }
+    fn permission(&self) -> Permission { Permission::Read }
+    fn guidance(&self) -> Option<&'static str> { Some("Call echo to repeat something.") }
+    fn call<'a>(&'a self, args: Value, _ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move {
+            let text = args.get("text").and_then(|v| v.as_str())
+                .ok_or_else(|| json!({"status":"err","code":"BadArgs","field":"text"}))?;
+            Ok(json!({"status":"ok","text":text}))
+        })
+    }
+}
+
+struct SpendTool;
+
+impl Tool for SpendTool {
+    fn name(&self) -> &'static str { "spend" }
+    fn permission(&self) -> Permission { Permission::Execute }
+    fn call<'a>(&'a self, _args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>> {
+        Box::pin(async move { Ok(json!({"status":"ok","checksum":ctx.confirmed})) })
+    }
+}
+
+fn call(name: &str, args: &[(&str, &str)]) -> ToolCall {
+    let arguments = args.iter()
+        .map(|(n, v)| serde_json::from_value(json!({"name": n, "value": v})).unwrap())
+        .collect();
+    ToolCall { id: "1".into(), function: FunctionCall { name: name.into(), arguments } }
+}
+
+fn ctx(confirmed: Option<&str>) -> ToolCtx<'_> {
+    ToolCtx { caller: Principal::from_slice(&[1; 29]), now: 1, confirmed }
+}
+
+#[test]
+fn test_registry_definitions_and_guidance() {
+    let r = Registry::default().with(EchoTool).with(SpendTool);
+    let defs = serde_json::to_value(r.definitions()).unwrap();
+    assert_eq!(defs.as_array().unwrap().len(), 2);
+    assert_eq!(defs[0]["function"]["name"], "echo");
+    assert_eq!(defs[0]["function"]["description"], "Echo the text");
+    assert!(defs.to_string().contains("\"loud\""));
+    assert_eq!(r.get("spend").unwrap().permission(), Permission::Execute);
+    assert!(r.get("nope").is_none());
+    assert_eq!(r.guidance(), "\nTOOLS\n- Call echo to repeat something.\n");
+    assert_eq!(Registry::default().with(SpendTool).guidance(), "");
+}
+
+#[test]
+fn test_dispatch_runs_tools_and_gates_execute() {
+    let r = Registry::default().with(EchoTool).with(SpendTool);
+    let out: Value = serde_json::from_str(&block_on(r.dispatch(&call("echo", &[("text", "hi")]), &ctx(None)))).unwrap();
+    assert_eq!(out["text"], "hi");
+    let out: Value = serde_json::from_str(&block_on(r.dispatch(&call("echo", &[]), &ctx(None)))).unwrap();
+    assert_eq!(out["field"], "text");
+    let out: Value = serde_json::from_str(&block_on(r.dispatch(&call("other", &[]), &ctx(None)))).unwrap();
+    assert_eq!(out["error"], "unknown tool");
+
+    let out: Value = serde_json::from_str(&block_on(r.dispatch(&call("spend", &[]), &ctx(None)))).unwrap();
+    assert_eq!(out["code"], "ConfirmationRequired");
+    let out: Value = serde_json::from_str(&block_on(r.dispatch(&call("spend", &[]), &ctx(Some("ab"))))).unwrap();
+    assert_eq!(out["checksum"], "ab");
+}
+
+#[test]
+fn test_arg_helpers() {
+    assert_eq!(id_arg(&json!({"id": 3})).unwrap(), 3);
+    assert_eq!(id_arg(&json!({"id": "#12"})).unwrap(), 12);
+    assert_eq!(id_arg(&json!({})).unwrap_err()["field"], "id");
+
+    #[derive(serde::Deserialize)]
+    struct A { n: u32 }
+    assert_eq!(parse_args::<A>(json!({"n": 2})).unwrap().n, 2);
+    assert_eq!(parse_args::<A>(json!({"n": "x"})).err().unwrap()["code"], "BadArgs");
+}
+
+#[test]
+fn test_coerce_args_reports_every_invalid_field() {
+    let params = vec![
+        Param::string("to").required(),
+        Param::number("top"),
+        Param::string("mode").one_of(&["equal", "percent"]),
+        Param::string("recipients"),
+    ];
+    let ok = coerce_args(&params, json!({"to": 7, "top": " 3 ", "mode": "Percent", "recipients": ["alice", "me"], "extra": true})).unwrap();
+    assert_eq!(ok, json!({"to": "7", "top": 3, "mode": "percent", "recipients": "alice,me", "extra": true}));
+    // empty optional strings count as missing
+    assert_eq!(coerce_args(&params, json!({"to": "a", "mode": ""})).unwrap(), json!({"to": "a"}));
+
+    let err = coerce_args(&params, json!({"top": "many", "mode": "weights"})).unwrap_err();
+    assert_eq!(err["code"], "BadArgs");
+    assert_eq!(err["field"], "to");
+    let fields: Vec<&str> = err["fields"].as_array().unwrap().iter().map(|f| f["field"].as_str().unwrap()).collect();
+    assert_eq!(fields, ["to", "top", "mode"]);
+    assert_eq!(err["error"], "to: missing; top: expected a number; mode: expected one of equal, percent");
+}
+
+#[test]
+fn test_plan_args_schema_types_decimals() {
+    let args = coerce_args(&PlanArgs::params(), json!({"to": "alice", "amount_dec": 10, "decimals": "8"})).unwrap();
+    let a: PlanArgs = parse_args(args).unwrap();
+    assert_eq!((a.amount_dec.as_str(), a.decimals), ("10", Some(8)));
+    let err = coerce_args(&PlanArgs::params(), json!({"decimals": "eight"})).unwrap_err();
+    assert_eq!(err["fields"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn test_recurring_args_schema_types_count() {
+    let params = RecurringArgs::params();
+    let frequency = params.iter().find(|p| p.name == "frequency").unwrap();
+    assert!(frequency.required);
+    assert_eq!(frequency.enum_values, ["daily", "weekly", "monthly"]);
+    let args = coerce_args(&params, json!({"to": "bob", "amount_dec": "5", "frequency": "weekly", "count": "3"})).unwrap();
+    let a: RecurringArgs = parse_args(args).unwrap();
+    assert_eq!((a.frequency.as_str(), a.count), ("weekly", Some(3)));
+}
+
+#[test]
+fn test_fund_moving_tools_require_confirmation() {
+    use backend::escrow::SettleEscrowTool;
+    use backend::recurring::{ChangeRecurringTool, ScheduleRecurringTool};
+    let r = Registry::default()
+        .with(ScheduleRecurringTool)
+        .with(ChangeRecurringTool { resume: true })
+        .with(SettleEscrowTool { refund: false })
+        .with(SettleEscrowTool { refund: true });
+    let schedule = call("schedule_recurring", &[("to", "alice"), ("amount_dec", "5"), ("frequency", "weekly")]);
+    for c in [schedule.clone(), call("resume_recurring", &[("id", "1")]), call("release_escrow", &[("id", "2")]), call("refund_escrow", &[("id", "2")])] {
+        let out: Value = serde_json::from_str(&block_on(r.dispatch(&c, &ctx(None)))).unwrap();
+        assert_eq!(out["code"], "ConfirmationRequired", "{}", c.function.name);
+        assert!(out["summary"].is_string());
+    }
+
+    // confirming another plan does not run it; the same call gets the same checksum
+    let first: Value = serde_json::from_str(&block_on(r.dispatch(&schedule, &ctx(Some("0123456789abcdef"))))).unwrap();
+    assert_eq!(first["code"], "ConfirmationRequired");
+    assert_eq!(first["summary"], "Send 5 to alice weekly.");
+    let again: Value = serde_json::from_str(&block_on(r.dispatch(&schedule, &ctx(None)))).unwrap();
+    assert_eq!(again["checksum"], first["checksum"]);
+    let other = call("schedule_recurring", &[("to", "alice"), ("amount_dec", "6"), ("frequency", "weekly")]);
+    let other: Value = serde_json::from_str(&block_on(r.dispatch(&other, &ctx(None)))).unwrap();
+    assert_ne!(other["checksum"], first["checksum"]);
+}
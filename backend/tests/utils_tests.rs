@@ -2,9 +2,11 @@ use candid::{Nat, Principal};
 use ic_llm::{ChatMessage, ToolCall};
 
 use backend::utils::*;
-use backend::types::TransferPlan;
 use backend::save_account;
 
+mod common;
+use common::{plan, with_checksum};
+
 #[test]
 fn test_token_symbols() {
     let syms = token_symbols();
@@ -24,30 +26,12 @@ fn test_resolve_to_alias_and_principal() {
 
 #[test]
 fn test_decode_plan_value() {
-    let plan = sample_plan();
+    let plan = with_checksum(plan(10));
     let v = serde_json::to_value(&plan).unwrap();
     let decoded = decode_plan_value(&v).unwrap();
     assert_eq!(decoded.checksum, plan.checksum);
 }
 
-fn sample_plan() -> TransferPlan {
-    let mut plan = TransferPlan {
-        from_owner: Some(Principal::anonymous()),
-        from_sub: None,
-        to_principal: Principal::anonymous(),
-        to_sub: None,
-        amount: Nat::from(10u32),
-        symbol: "CFXN".to_string(),
-        ledger: Principal::management_canister(),
-        memo: None,
-        created_at_time: 1,
-        human_readable: "Send".into(),
-        checksum: String::new(),
-    };
-    plan.checksum = plan_checksum(&plan);
-    plan
-}
-
 #[test]
 fn test_resolve_token() {
     let (p, d, s) = resolve_token(Some("CFXN"), None).unwrap();
@@ -70,7 +54,7 @@ fn test_scale_amount() {
 
 #[test]
 fn test_plan_checksum() {
-    let plan = sample_plan();
+    let plan = with_checksum(plan(10));
     let cs = plan_checksum(&plan);
     assert_eq!(cs.len(), 16);
 }
@@ -81,13 +65,6 @@ fn test_is_placeholder() {
     assert!(!is_placeholder("value"));
 }
 
-#[test]
-fn test_to_number_and_string() {
-    use serde_json::Value;
-    assert_eq!(to_number(&Value::String("7".into())), Some(Value::Number(7.into())));
-    assert_eq!(to_string("raw", &Value::Bool(true)), Value::String("true".into()));
-}
-
 #[test]
 fn test_tool_args_json() {
     let json = r#"{
@@ -108,16 +85,180 @@ fn test_tool_args_json() {
 }
 
 #[test]
-fn test_cap_messages_in_place_and_lang() {
+fn test_trim_to_token_budget_and_lang() {
     let mut msgs: Vec<ChatMessage> = vec![ChatMessage::System { content: "sys".into() }];
     for _ in 0..12 {
-        msgs.push(ChatMessage::User { content: "hi".into() });
+        msgs.push(ChatMessage::User { content: "hi ".repeat(40) });
     }
-    cap_messages_in_place(&mut msgs);
-    assert!(msgs.len() <= MAX_MESSAGES);
+    let budget = 200;
+    trim_to_token_budget(&mut msgs, budget, None);
+    assert!(msgs.iter().map(approx_tokens).sum::<usize>() <= budget);
+    assert!(matches!(msgs[0], ChatMessage::System { .. }));
+    assert!(msgs.len() < 13);
 
     let lang = detect_lang_last_user(&msgs);
     assert_eq!(lang, "en");
     let guard = lang_guard(lang);
     if let ChatMessage::System { content } = guard { assert!(content.contains("ENGLISH")); } else { panic!(); }
 }
+
+#[test]
+fn test_trim_keeps_tool_pairs_and_adds_note() {
+    let call: ToolCall = serde_json::from_str(r#"{"id":"c1","function":{"name":"plan_transfer","arguments":[]}}"#).unwrap();
+    let assistant: ChatMessage = serde_json::from_value(serde_json::json!({"assistant": {"content": null, "tool_calls": [call]}})).unwrap();
+    let mut msgs = vec![
+        ChatMessage::System { content: "sys".into() },
+        ChatMessage::User { content: "x".repeat(400) },
+        assistant,
+        ChatMessage::Tool { tool_call_id: "c1".into(), content: "y".repeat(400) },
+        ChatMessage::User { content: "send it".into() },
+    ];
+    // the user turn fits but the pair does not: both call and result are dropped
+    let note = ChatMessage::System { content: "PENDING PLAN".into() };
+    trim_to_token_budget(&mut msgs, 60, Some(note.clone()));
+    assert_eq!(msgs.len(), 3);
+    assert!(matches!(&msgs[1], ChatMessage::System { content } if content == "PENDING PLAN"));
+    assert!(matches!(&msgs[2], ChatMessage::User { content } if content == "send it"));
+
+    // trimming again does not repeat the note
+    msgs.insert(2, ChatMessage::User { content: "z".repeat(400) });
+    trim_to_token_budget(&mut msgs, 60, Some(note));
+    assert_eq!(msgs.len(), 3);
+
+    // an orphan tool result is never kept
+    let mut msgs = vec![
+        ChatMessage::System { content: "sys".into() },
+        ChatMessage::Tool { tool_call_id: "c0".into(), content: "r".repeat(400) },
+        ChatMessage::User { content: "hi".into() },
+    ];
+    trim_to_token_budget(&mut msgs, 50, None);
+    assert!(msgs.iter().all(|m| !matches!(m, ChatMessage::Tool { .. })));
+}
+
+#[test]
+fn test_trim_always_keeps_last_user_message() {
+    let call: ToolCall = serde_json::from_str(r#"{"id":"c1","function":{"name":"get_balance","arguments":[]}}"#).unwrap();
+    let assistant: ChatMessage = serde_json::from_value(serde_json::json!({"assistant": {"content": null, "tool_calls": [call]}})).unwrap();
+    let mut msgs = vec![
+        ChatMessage::System { content: "sys".into() },
+        ChatMessage::User { content: "old ".repeat(100) },
+        ChatMessage::User { content: "send 5 CFXN to alice".into() },
+        assistant,
+        ChatMessage::Tool { tool_call_id: "c1".into(), content: "y".repeat(200) },
+    ];
+    // a later tool round no longer hides the request it belongs to
+    trim_to_token_budget(&mut msgs, 80, None);
+    assert_eq!(msgs.len(), 4);
+    assert!(matches!(&msgs[1], ChatMessage::User { content } if content == "send 5 CFXN to alice"));
+    assert!(matches!(msgs[3], ChatMessage::Tool { .. }));
+
+    // an oversized request is truncated rather than dropped
+    let mut msgs = vec![
+        ChatMessage::System { content: "sys".into() },
+        ChatMessage::User { content: format!("send 5 CFXN to alice {}", "é".repeat(1_000)) },
+    ];
+    trim_to_token_budget(&mut msgs, 100, None);
+    assert_eq!(msgs.len(), 2);
+    assert!(matches!(&msgs[1], ChatMessage::User { content } if content.starts_with("send 5 CFXN to alice")));
+    assert!(msgs.iter().map(approx_tokens).sum::<usize>() <= 100);
+}
+
+#[test]
+fn test_date_helpers() {
+    let ts = parse_date_ns("2024-01-31").unwrap();
+    assert_eq!(format_date_ns(ts), "2024-01-31");
+    assert_eq!(format_date_ns(add_months_ns(ts, 1)), "2024-02-29");
+    assert_eq!(format_date_ns(add_months_ns(ts, 13)), "2025-02-28");
+    assert!(parse_date_ns("2023-02-29").is_err());
+    assert!(parse_date_ns("31/01/2024").is_err());
+}
+
+#[test]
+fn test_format_amount() {
+    assert_eq!(format_amount(&Nat::from(150_000_000u64), 8), "1.5");
+    assert_eq!(format_amount(&Nat::from(5u32), 8), "0.00000005");
+    assert_eq!(format_amount(&Nat::from(42u32), 0), "42");
+    assert_eq!(format_amount(&Nat::from(100u32), 2), "1");
+}
+
+#[test]
+fn test_memo_roundtrip_and_limits() {
+    let caller = Principal::from_slice(&[9; 29]);
+    let bytes = encode_memo(Some("dinner"), Some(12), caller);
+    assert!(bytes.len() <= 32);
+    let d = decode_memo(&bytes).unwrap();
+    assert_eq!(d.invoice_id, Some(12));
+    assert_eq!(d.text.as_deref(), Some("dinner"));
+    assert_eq!(d.caller_tag, hex::encode(caller_tag(caller)));
+    assert_eq!(describe_memo(&bytes), format!("invoice #12, \"dinner\", caller {}", d.caller_tag));
+
+    assert!(plan_memo(Some("rent"), None, caller, "CFXN").unwrap().is_some());
+    assert!(plan_memo(None, None, caller, "CFXN").unwrap().is_none());
+    let err = plan_memo(Some(&"x".repeat(40)), None, caller, "CFXN").unwrap_err();
+    assert_eq!(err["code"], "BadMemo");
+    assert_eq!(err["max_text_bytes"], 25);
+
+    assert_eq!(describe_memo(b"legacy"), "legacy");
+    assert!(decode_memo(b"legacy").is_none());
+}
+
+#[test]
+fn test_detect_and_normalize_languages() {
+    assert_eq!(detect_lang("berapa saldo saya sekarang?"), Some("id"));
+    assert_eq!(detect_lang("saya nak hantar duit kepada Ali"), Some("ms"));
+    assert_eq!(detect_lang("aku arep ngirim duwit menyang Budi"), Some("jv"));
+    assert_eq!(detect_lang("what is my balance"), Some("en"));
+    assert_eq!(detect_lang("tolong jawab in English please"), Some("en"));
+    assert_eq!(detect_lang("10"), None);
+
+    assert_eq!(normalize_lang("Bahasa Melayu"), Some("ms"));
+    assert_eq!(normalize_lang("jawa"), Some("jv"));
+    assert_eq!(normalize_lang("klingon"), None);
+
+    // short replies keep the language of the conversation
+    let msgs = vec![
+        ChatMessage::User { content: "kirim ke alice dong".into() },
+        ChatMessage::User { content: "10".into() },
+    ];
+    assert_eq!(detect_lang_last_user(&msgs), "id");
+}
+
+#[test]
+fn test_detect_lang_ignores_single_keywords() {
+    // naming a place or a single typical word does not switch the language
+    assert_eq!(detect_lang("kirim ke teman di malaysia"), Some("id"));
+    assert_eq!(detect_lang("oke jawa"), None);
+    assert_eq!(detect_lang("saldo?"), None);
+    assert_eq!(detect_lang("pakai bahasa jawa"), Some("jv"));
+    assert_eq!(detect_lang("send 10 ICP to my friend in malaysia"), Some("en"));
+    assert_eq!(detect_lang("kirim 10 ICP ke teman saya in indonesia"), Some("id"));
+    assert_eq!(detect_lang("reply in malay"), Some("ms"));
+    assert_eq!(detect_lang("switch to english"), Some("en"));
+    let msgs = vec![
+        ChatMessage::User { content: "berapa saldo saya sekarang?".into() },
+        ChatMessage::User { content: "malaysia".into() },
+    ];
+    assert_eq!(detect_lang_last_user(&msgs), "id");
+}
+
+#[test]
+fn test_set_lang_guard_replaces_existing_guard() {
+    let mut convo = vec![ChatMessage::System { content: "sys".into() }, ChatMessage::User { content: "hi".into() }];
+    set_lang_guard(&mut convo, "en");
+    set_lang_guard(&mut convo, "jv");
+    set_lang_guard(&mut convo, "jv");
+    assert_eq!(convo.len(), 3);
+    assert!(matches!(&convo[1], ChatMessage::System { content } if content.contains("BASA JAWA")));
+}
+
+#[test]
+fn test_resolve_plan_token_conflicts() {
+    let cfxn = "mxzaz-hqaaa-aaaar-qaada-cai";
+    assert_eq!(resolve_plan_token(Some("cfxn"), Some(cfxn), Some(0)).unwrap().2, "CFXN");
+    let e = resolve_plan_token(Some("CFXN"), None, Some(8)).unwrap_err();
+    assert_eq!((e["code"].as_str(), e["field"].as_str(), e["expected"].as_u64()), (Some("Conflict"), Some("decimals"), Some(0)));
+    let e = resolve_plan_token(Some("CFXN"), Some("aaaaa-aa"), None).unwrap_err();
+    assert_eq!((e["code"].as_str(), e["field"].as_str(), e["expected"].as_str()), (Some("Conflict"), Some("ledger"), Some(cfxn)));
+    assert_eq!(resolve_plan_token(None, Some("aaaaa-aa"), None).unwrap_err()["field"], "ledger");
+    assert_eq!(resolve_plan_token(Some("DOGE"), None, None).unwrap_err()["code"], "BadToken");
+}
@@ -0,0 +1,274 @@
+use candid::Principal;
+use futures::future::LocalBoxFuture;
+use ic_llm::{ParameterType, ToolCall};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+
+use crate::{log, PENDING_ACTIONS};
+use crate::types::PendingAction;
+use crate::utils::{action_checksum, tool_args_json};
+
+/// What a tool may do on the user's behalf.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    /// Reads state only.
+    Read,
+    /// Changes the user's saved data or settings.
+    Write,
+    /// Creates a plan that still has to be confirmed.
+    Plan,
+    /// Moves funds; only runs in a turn where the user confirmed the plan or the call itself.
+    Execute,
+}
+
+/// Parameter of a tool as shown to the model.
+#[derive(Clone, Debug)]
+pub struct Param {
+    pub name: &'static str,
+    pub kind: ParameterType,
+    pub description: Option<&'static str>,
+    pub enum_values: &'static [&'static str],
+    pub required: bool,
+}
+
+impl Param {
+    /// Optional string parameter.
+    pub fn string(name: &'static str) -> Self {
+        Param { name, kind: ParameterType::String, description: None, enum_values: &[], required: false }
+    }
+
+    /// Optional number parameter.
+    pub fn number(name: &'static str) -> Self {
+        Param { kind: ParameterType::Number, ..Param::string(name) }
+    }
+
+    pub fn required(self) -> Self {
+        Param { required: true, ..self }
+    }
+
+    pub fn describe(self, description: &'static str) -> Self {
+        Param { description: Some(description), ..self }
+    }
+
+    pub fn one_of(self, values: &'static [&'static str]) -> Self {
+        Param { enum_values: values, ..self }
+    }
+
+    /// Coerce `v` to this parameter's type; the error says what was expected.
+    pub fn coerce(&self, v: &Value) -> Result<Value, String> {
+        let out = match (&self.kind, v) {
+            (ParameterType::String, Value::String(_)) => v.clone(),
+            (ParameterType::String, Value::Number(_) | Value::Bool(_)) => Value::String(v.to_string()),
+            // lists of names or weights become "a,b,c"
+            (ParameterType::String, Value::Array(items)) if items.iter().all(|i| !i.is_array() && !i.is_object()) => {
+                Value::String(items.iter().map(|i| i.as_str().map(str::to_string).unwrap_or_else(|| i.to_string())).collect::<Vec<_>>().join(","))
+            }
+            (ParameterType::String, _) => Value::String(v.to_string()),
+            (ParameterType::Number, Value::Number(_)) => v.clone(),
+            (ParameterType::Number, Value::String(s)) => {
+                let s = s.trim();
+                s.parse::<u64>().ok().map(Value::from)
+                    .or_else(|| s.parse::<f64>().ok().filter(|f| f.is_finite()).map(Value::from))
+                    .ok_or("expected a number")?
+            }
+            (ParameterType::Boolean, Value::Bool(_)) => v.clone(),
+            (ParameterType::Boolean, Value::String(s)) => match s.trim().to_ascii_lowercase().as_str() {
+                "true" | "yes" => Value::Bool(true),
+                "false" | "no" => Value::Bool(false),
+                _ => return Err("expected true or false".into()),
+            },
+            (ParameterType::Number, _) => return Err("expected a number".into()),
+            (ParameterType::Boolean, _) => return Err("expected true or false".into()),
+        };
+        if self.enum_values.is_empty() {
+            return Ok(out);
+        }
+        let s = out.as_str().unwrap_or_default().trim();
+        self.enum_values.iter().find(|e| e.eq_ignore_ascii_case(s))
+            .map(|e| Value::String(e.to_string()))
+            .ok_or_else(|| format!("expected one of {}", self.enum_values.join(", ")))
+    }
+
+    fn build(&self) -> ic_llm::ParameterBuilder {
+        let mut p = ic_llm::parameter(self.name, self.kind.clone());
+        if let Some(d) = self.description { p = p.with_description(d); }
+        if !self.enum_values.is_empty() { p = p.with_enum_values(self.enum_values.iter().copied()); }
+        if self.required { p = p.is_required(); }
+        p
+    }
+}
+
+/// Argument type of a tool; its schema is both the tool definition and the validation of incoming arguments.
+pub trait ToolArgs: DeserializeOwned {
+    fn params() -> Vec<Param>;
+}
+
+/// Declare a tool argument struct and its [`ToolArgs`] schema from one field list.
+///
+/// After `=>` each field names its [`Param`] constructor and modifiers; the parameter takes the field's name.
+#[macro_export]
+macro_rules! tool_args {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident {
+            $(
+                $(#[$fmeta:meta])*
+                pub $field:ident: $ty:ty => $kind:ident() $(.$modifier:ident($($arg:expr),*))*
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        pub struct $name {
+            $($(#[$fmeta])* pub $field: $ty,)*
+        }
+
+        impl $crate::tools::ToolArgs for $name {
+            fn params() -> Vec<$crate::tools::Param> {
+                vec![$($crate::tools::Param::$kind(stringify!($field))$(.$modifier($($arg),*))*),*]
+            }
+        }
+    };
+}
+
+/// Who is calling a tool and what they confirmed.
+pub struct ToolCtx<'a> {
+    pub caller: Principal,
+    pub now: u64,
+    /// Checksum of the plan the user explicitly confirmed in this turn, if any.
+    pub confirmed: Option<&'a str>,
+}
+
+/// A tool the model can call.
+pub trait Tool {
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> Option<&'static str> { None }
+
+    fn params(&self) -> Vec<Param> { Vec::new() }
+
+    fn permission(&self) -> Permission;
+
+    /// What an `Execute` tool is about to do with `args`.
+    ///
+    /// Such tools run only once the user confirmed the returned summary in a later turn; `None` means the
+    /// tool executes a plan and checks the confirmed checksum itself.
+    fn confirmation(&self, _args: &Value) -> Option<String> { None }
+
+    /// Extra system prompt line about when to call the tool.
+    fn guidance(&self) -> Option<&'static str> { None }
+
+    /// Run the tool with arguments already parsed into a JSON object; errors are `{"status":"err",...}` values.
+    fn call<'a>(&'a self, args: Value, ctx: &'a ToolCtx<'a>) -> LocalBoxFuture<'a, Result<Value, Value>>;
+}
+
+/// Tools offered to the model, in the order they are listed.
+#[derive(Default)]
+pub struct Registry {
+    tools: Vec<Box<dyn Tool>>,
+}
+
+impl Registry {
+    pub fn with(mut self, tool: impl Tool + 'static) -> Self {
+        debug_assert!(self.get(tool.name()).is_none(), "duplicate tool {}", tool.name());
+        self.tools.push(Box::new(tool));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Tool> {
+        self.tools.iter().find(|t| t.name() == name).map(|t| t.as_ref())
+    }
+
+    /// Tool definitions sent with every chat request.
+    pub fn definitions(&self) -> Vec<ic_llm::Tool> {
+        self.tools.iter().map(|t| {
+            let mut b = ic_llm::tool(t.name());
+            if let Some(d) = t.description() { b = b.with_description(d); }
+            t.params().iter().fold(b, |b, p| b.with_parameter(p.build())).build()
+        }).collect()
+    }
+
+    /// System prompt section with the tools' own guidance.
+    pub fn guidance(&self) -> String {
+        let lines: Vec<String> = self.tools.iter().filter_map(|t| t.guidance()).map(|g| format!("- {}", g)).collect();
+        if lines.is_empty() { String::new() } else { format!("\nTOOLS\n{}\n", lines.join("\n")) }
+    }
+
+    /// Run `call`; returns the JSON result sent back to the model.
+    pub async fn dispatch(&self, call: &ToolCall, ctx: &ToolCtx<'_>) -> String {
+        let name = call.function.name.as_str();
+        let Some(tool) = self.get(name) else {
+            return json!({"status":"err","error":"unknown tool"}).to_string();
+        };
+        let raw = tool_args_json(call);
+        log!("[{}] raw_args={}", name, serde_json::to_string(&raw).unwrap_or_default());
+        let args = match coerce_args(&tool.params(), raw) {
+            Ok(a) => a,
+            Err(e) => {
+                log!("[{}] BadArgs: {}", name, e["error"]);
+                return e.to_string();
+            }
+        };
+        if tool.permission() == Permission::Execute {
+            match tool.confirmation(&args) {
+                Some(summary) => {
+                    let checksum = action_checksum(ctx.caller, name, &args);
+                    if ctx.confirmed != Some(checksum.as_str()) {
+                        log!("[{}] ConfirmationRequired checksum={}", name, checksum);
+                        let action = PendingAction { tool: name.to_string(), checksum: checksum.clone(), summary: summary.clone(), created_at: ctx.now };
+                        PENDING_ACTIONS.with(|m| { m.borrow_mut().insert(ctx.caller, action); });
+                        return json!({"status":"err","code":"ConfirmationRequired","checksum":checksum,"summary":summary,
+                            "error":"show the summary and call again with the same arguments once the user confirms"}).to_string();
+                    }
+                    PENDING_ACTIONS.with(|m| { m.borrow_mut().remove(&ctx.caller); });
+                }
+                None if ctx.confirmed.is_none() => {
+                    log!("[{}] ConfirmationRequired", name);
+                    return json!({"status":"err","code":"ConfirmationRequired","error":"show the plan and wait for the user to confirm it"}).to_string();
+                }
+                None => {}
+            }
+        }
+        tool.call(args, ctx).await.unwrap_or_else(|e| e).to_string()
+    }
+}
+
+/// Coerce `args` to the types of `params`, reporting every missing or invalid field at once.
+///
+/// Arguments without a parameter are passed through; nulls, and empty strings of optional parameters, count as missing.
+pub fn coerce_args(params: &[Param], args: Value) -> Result<Value, Value> {
+    let mut m = match args {
+        Value::Object(m) => m,
+        _ => Map::new(),
+    };
+    m.retain(|k, v| !v.is_null() && !(v.as_str().is_some_and(|s| s.trim().is_empty()) && params.iter().any(|p| p.name == k && !p.required)));
+    let mut errors = Vec::new();
+    for p in params {
+        match m.get(p.name).map(|v| p.coerce(v)) {
+            Some(Ok(v)) => { m.insert(p.name.to_string(), v); }
+            Some(Err(e)) => errors.push((p.name, e)),
+            None if p.required => errors.push((p.name, "missing".to_string())),
+            None => {}
+        }
+    }
+    if errors.is_empty() {
+        return Ok(Value::Object(m));
+    }
+    Err(json!({
+        "status": "err",
+        "code": "BadArgs",
+        "field": errors[0].0,
+        "fields": errors.iter().map(|(f, e)| json!({"field": f, "error": e})).collect::<Vec<_>>(),
+        "error": errors.iter().map(|(f, e)| format!("{}: {}", f, e)).collect::<Vec<_>>().join("; "),
+    }))
+}
+
+/// Deserialize tool arguments, reporting failures as `BadArgs`.
+pub fn parse_args<T: DeserializeOwned>(args: Value) -> Result<T, Value> {
+    serde_json::from_value(args).map_err(|e| json!({"status":"err","code":"BadArgs","error":e.to_string()}))
+}
+
+/// Numeric `id` argument; accepts "#3" and "3".
+pub fn id_arg(args: &Value) -> Result<u64, Value> {
+    args.get("id").and_then(|v| v.as_u64().or_else(|| v.as_str()?.trim_start_matches('#').parse().ok()))
+        .ok_or_else(|| json!({"status":"err","code":"BadArgs","field":"id"}))
+}
@@ -1,7 +1,13 @@
 use candid::{CandidType, Nat, Principal};
+use ic_llm::ChatMessage;
+use icrc_ledger_types::icrc1::account::Account as IcrcAccount;
 use icrc_ledger_types::icrc1::transfer::Memo;
+use icrc_ledger_types::icrc2::approve::ApproveArgs;
+use icrc_ledger_types::icrc2::transfer_from::TransferFromError;
 use serde::{Deserialize, Serialize};
 
+use crate::tool_args;
+
 /// Allowlisted token configuration.
 #[derive(Clone, Debug)]
 pub struct TokenEntry {
@@ -11,12 +17,16 @@ pub struct TokenEntry {
     pub ledger: &'static str,
     /// Number of decimal places used by the token.
     pub decimals: u8,
+    /// Largest memo in bytes the ledger accepts.
+    pub max_memo: u8,
+    /// Principal text of the ledger's ICRC index canister, if one is deployed.
+    pub index: Option<&'static str>,
 }
 
 /// Tokens permitted for transfers.
 pub const TOKENS: &[TokenEntry] = &[
-    TokenEntry { symbol: "ICP",  ledger: "<LEDGER_ICP_ID>",      decimals: 8 },
-    TokenEntry { symbol: "CFXN", ledger: "mxzaz-hqaaa-aaaar-qaada-cai",     decimals: 0 },
+    TokenEntry { symbol: "ICP",  ledger: "<LEDGER_ICP_ID>",      decimals: 8, max_memo: 32, index: None },
+    TokenEntry { symbol: "CFXN", ledger: "mxzaz-hqaaa-aaaar-qaada-cai",     decimals: 0, max_memo: 32, index: None },
 ];
 
 /// Stored reference to a user account alias.
@@ -28,23 +38,60 @@ pub struct SavedAccount {
     pub owner: Principal,
     /// Optional ICRC subaccount.
     pub subaccount: Option<[u8; 32]>,
+    /// Lowercase labels such as "vendor" used to group spending.
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
-/// Arguments required to build a [`TransferPlan`].
-#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
-pub struct PlanArgs {
-    /// Recipient principal text or saved alias.
-    pub to: String,
-    /// Amount represented as a decimal string (e.g. "0.5").
-    pub amount_dec: String,
-    /// Optional token symbol override.
-    pub symbol: Option<String>,
-    /// Optional ledger canister id override.
-    pub ledger: Option<String>,
-    /// Optional explicit decimals for the token.
-    pub decimals: Option<u8>,
-    /// Optional memo string.
-    pub memo: Option<String>,
+tool_args! {
+    /// Arguments required to build a [`TransferPlan`].
+    #[derive(Clone, Debug, PartialEq, CandidType, Deserialize, Serialize)]
+    pub struct PlanArgs {
+        /// Recipient principal text or saved alias.
+        pub to: String => string().required(),
+        /// Amount represented as a decimal string (e.g. "0.5").
+        pub amount_dec: String => string().required().describe("Token amount, or a fiat value such as \"Rp 50.000\" or \"$5\""),
+        /// Optional token symbol override.
+        pub symbol: Option<String> => string(),
+        /// Optional ledger canister id; must be the allowlisted ledger of `symbol`.
+        pub ledger: Option<String> => string(),
+        /// Optional decimals; must match the allowlisted token.
+        pub decimals: Option<u8> => number(),
+        /// Optional memo string.
+        pub memo: Option<String> => string(),
+    }
+}
+
+tool_args! {
+    /// Arguments of the `save_account` tool.
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct SaveAccountArgs {
+        /// Alias to save the account under.
+        pub alias: String => string().required(),
+        /// Account owner principal text.
+        pub owner: String => string().required(),
+        /// Optional 32-byte subaccount as hex.
+        pub sub: Option<String> => string().describe("32-byte subaccount as hex"),
+        /// Optional comma separated tags.
+        pub tags: Option<String> => string().describe("Comma separated, e.g. \"vendor,office\""),
+    }
+}
+
+tool_args! {
+    /// Changes applied to a pending plan by `amend_plan`; unset fields are kept.
+    #[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize)]
+    pub struct AmendArgs {
+        /// Plan to amend; the caller's last plan if unset.
+        pub checksum: Option<String> => string(),
+        /// New recipient principal text or alias.
+        pub to: Option<String> => string(),
+        /// New amount as a decimal string.
+        pub amount_dec: Option<String> => string().describe("New token amount, or a fiat value such as \"Rp 50.000\" or \"$5\""),
+        /// New token symbol.
+        pub symbol: Option<String> => string(),
+        /// New memo.
+        pub memo: Option<String> => string(),
+    }
 }
 
 /// Planned transfer returned from `plan_transfer`.
@@ -70,7 +117,1116 @@ pub struct TransferPlan {
     pub created_at_time: u64,
     /// Human friendly description of the transfer.
     pub human_readable: String,
+    /// Co-signer approvals needed before execution (0 = none).
+    #[serde(default)]
+    pub approvals_required: u8,
+    /// Invoice settled by this transfer, if any.
+    #[serde(default)]
+    pub invoice_id: Option<u64>,
+    /// Escrow funded by this transfer, if any.
+    #[serde(default)]
+    pub escrow_id: Option<u64>,
+    /// Checksum of the plan this one amends, if any.
+    #[serde(default)]
+    pub supersedes: Option<String>,
     /// Checksum for replay protection.
     pub checksum: String,
 }
 
+
+/// Reasons a [`TransferPlan`] could not be executed.
+#[derive(Clone, Debug)]
+pub enum ExecError {
+    /// The plan checksum was already executed.
+    Duplicate,
+    /// The plan has no source owner.
+    MissingOwner,
+    /// The ledger rejected `icrc2_transfer_from`.
+    Ledger(TransferFromError),
+    /// The owner's spending policy forbids the transfer.
+    Policy(PolicyViolation),
+    /// Co-signer quorum not reached yet.
+    AwaitingApproval { approved: u8, required: u8 },
+    /// A co-signer rejected the plan.
+    Rejected { by: Principal, reason: Option<String> },
+    /// The invoice the plan settles is no longer open.
+    InvoiceClosed { id: u64 },
+    /// The escrow the plan funds is no longer awaiting funds.
+    EscrowClosed { id: u64 },
+    /// The plan was amended; only the newer version can be confirmed.
+    Superseded { by: String },
+    /// The user cancelled the plan.
+    Cancelled,
+    /// The DEX swap failed.
+    Swap { detail: String },
+}
+
+impl ExecError {
+    /// Error code reported to the model.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ExecError::Duplicate => "Duplicate",
+            ExecError::MissingOwner | ExecError::Ledger(_) => "ExecError",
+            ExecError::Policy(v) => v.code(),
+            ExecError::AwaitingApproval { .. } => "AwaitingApproval",
+            ExecError::Rejected { .. } => "Rejected",
+            ExecError::InvoiceClosed { .. } => "InvoiceClosed",
+            ExecError::EscrowClosed { .. } => "EscrowClosed",
+            ExecError::Superseded { .. } => "Superseded",
+            ExecError::Cancelled => "Cancelled",
+            ExecError::Swap { .. } => "SwapFailed",
+        }
+    }
+
+    /// Human readable detail of the failure.
+    pub fn detail(&self) -> String {
+        match self {
+            ExecError::Duplicate => "plan already executed".into(),
+            ExecError::MissingOwner => "missing from_owner".into(),
+            ExecError::Ledger(e) => format!("ICRC2::{:?}", e),
+            ExecError::Policy(v) => v.detail(),
+            ExecError::AwaitingApproval { approved, required } => format!("{} of {} co-signer approvals", approved, required),
+            ExecError::Rejected { by, reason } => format!("rejected by {}{}", by.to_text(),
+                reason.as_deref().map(|r| format!(": {}", r)).unwrap_or_default()),
+            ExecError::InvoiceClosed { id } => format!("invoice #{} is already paid or cancelled", id),
+            ExecError::EscrowClosed { id } => format!("escrow #{} is already funded or settled", id),
+            ExecError::Superseded { by } => format!("plan was amended; confirm {} instead", by),
+            ExecError::Cancelled => "plan was cancelled".into(),
+            ExecError::Swap { detail } => detail.clone(),
+        }
+    }
+}
+
+/// How often a recurring transfer repeats.
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Frequency {
+    /// Parse a frequency name (English or Indonesian).
+    pub fn parse(s: &str) -> Option<Frequency> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "daily" | "day" | "harian" => Some(Frequency::Daily),
+            "weekly" | "week" | "mingguan" => Some(Frequency::Weekly),
+            "monthly" | "month" | "bulanan" => Some(Frequency::Monthly),
+            _ => None,
+        }
+    }
+}
+
+/// Lifecycle state of a [`RecurringTransfer`].
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum RecurringStatus {
+    /// Occurrences are executed on schedule.
+    Active,
+    /// Stopped after a failed occurrence; resumable by the owner.
+    Paused,
+    /// End date or occurrence count reached.
+    Completed,
+    /// Cancelled by the owner.
+    Cancelled,
+}
+
+tool_args! {
+    /// Arguments required to schedule a [`RecurringTransfer`].
+    #[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+    pub struct RecurringArgs {
+        /// Recipient principal text or saved alias.
+        pub to: String => string().required(),
+        /// Amount per occurrence as a decimal string.
+        pub amount_dec: String => string().required(),
+        /// `daily`, `weekly` or `monthly`.
+        pub frequency: String => string().one_of(&["daily", "weekly", "monthly"]).required(),
+        /// Optional token symbol.
+        pub symbol: Option<String> => string(),
+        /// Optional memo string.
+        pub memo: Option<String> => string(),
+        /// First occurrence as `YYYY-MM-DD`; defaults to now.
+        pub start_date: Option<String> => string(),
+        /// Last allowed occurrence date as `YYYY-MM-DD`.
+        pub end_date: Option<String> => string(),
+        /// Maximum number of successful occurrences.
+        pub count: Option<u32> => number(),
+    }
+}
+
+/// Transfer repeated on a fixed schedule (rent, payroll, ...).
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct RecurringTransfer {
+    /// Series identifier.
+    pub id: u64,
+    /// Principal paying each occurrence.
+    pub owner: Principal,
+    /// Template plan; every occurrence gets a fresh time and checksum.
+    pub plan: TransferPlan,
+    /// Repetition period.
+    pub frequency: Frequency,
+    /// Time of the first occurrence in nanoseconds.
+    pub anchor_at: u64,
+    /// Index of the next occurrence counted from `anchor_at`.
+    pub slot: u32,
+    /// Time of the next occurrence in nanoseconds.
+    pub next_run_at: u64,
+    /// No occurrence runs after this time.
+    pub end_at: Option<u64>,
+    /// Stop after this many successful occurrences.
+    pub max_count: Option<u32>,
+    /// Successful occurrences so far.
+    pub executed_count: u32,
+    /// Current lifecycle state.
+    pub status: RecurringStatus,
+    /// Block index of the last successful occurrence.
+    pub last_block_index: Option<Nat>,
+    /// Error of the last failed occurrence.
+    pub last_error: Option<String>,
+    /// Whether the last failure was already shown in chat.
+    pub failure_reported: bool,
+}
+
+/// How a chat turn ended.
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ChatStatus {
+    /// The assistant answered.
+    Done,
+    /// A plan is waiting for the user's confirmation.
+    AwaitingConfirmation,
+    /// The assistant kept calling tools until the round limit.
+    RoundLimit,
+    /// Neither the model nor its fallback answered.
+    ModelUnavailable,
+}
+
+/// Tool called during a chat turn.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ToolTrace {
+    /// Tool name.
+    pub name: String,
+    /// "ok" or "err".
+    pub status: String,
+    /// Error code of a failed call.
+    pub code: Option<String>,
+}
+
+/// Reply of `copilot_chat`.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ChatResponse {
+    /// Text shown to the user.
+    pub reply: String,
+    /// Plan awaiting confirmation, if any.
+    pub pending_plan: Option<PendingPlan>,
+    /// Transfers executed during this turn.
+    pub receipts: Vec<Receipt>,
+    /// Tools called during this turn, in order.
+    pub tools: Vec<ToolTrace>,
+    /// How the turn ended: on `AwaitingConfirmation` show `pending_plan` with confirm and cancel
+    /// buttons, on `RoundLimit` or `ModelUnavailable` offer to retry; `Done` needs no action.
+    pub status: ChatStatus,
+}
+
+/// Record of a transfer executed by the backend.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Receipt {
+    /// Checksum of the executed plan.
+    pub checksum: String,
+    /// Principal whose funds were moved.
+    pub owner: Principal,
+    /// Destination principal.
+    pub to_principal: Principal,
+    /// Destination subaccount if any.
+    pub to_sub: Option<[u8; 32]>,
+    /// Amount in minimal units.
+    pub amount: Nat,
+    /// Canonical token symbol.
+    pub symbol: String,
+    /// Ledger canister handling the token.
+    pub ledger: Principal,
+    /// Memo attached to the transfer.
+    pub memo: Option<Memo>,
+    /// Ledger block index of the transfer.
+    pub block_index: Nat,
+    /// Execution timestamp in nanoseconds.
+    pub executed_at: u64,
+    /// Invoice settled by the transfer, if any.
+    pub invoice_id: Option<u64>,
+    /// Readable form of the memo.
+    pub memo_text: Option<String>,
+    /// Output of the swap this transfer funded, if any.
+    pub swap: Option<SwapFill>,
+    /// Fiat value at execution time (e.g. "Rp 50.000 / $3.10").
+    pub fiat_value: Option<String>,
+}
+
+/// Spending caps for one token, in minimal units.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct TokenLimit {
+    /// Token symbol the caps apply to.
+    pub symbol: String,
+    /// Maximum amount of a single transfer.
+    pub per_tx_max: Option<Nat>,
+    /// Maximum total over the last 24 hours.
+    pub daily_cap: Option<Nat>,
+    /// Maximum total over the last 7 days.
+    pub weekly_cap: Option<Nat>,
+}
+
+/// Cap on transfers to one recipient over the last 24 hours, in minimal units.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct RecipientCap {
+    /// Recipient principal.
+    pub recipient: Principal,
+    /// Token symbol the cap applies to.
+    pub symbol: String,
+    /// Maximum total over the last 24 hours.
+    pub daily_cap: Nat,
+}
+
+/// Local time window during which transfers are refused.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct QuietHours {
+    /// First quiet hour (0-23, inclusive).
+    pub start_hour: u8,
+    /// Hour at which transfers are allowed again (0-23, exclusive).
+    pub end_hour: u8,
+    /// Offset of the user's local time from UTC in minutes (e.g. 420 for WIB).
+    pub utc_offset_minutes: i16,
+}
+
+/// User-configurable safeguards checked before every execution.
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize)]
+pub struct SpendingPolicy {
+    /// Per-token caps.
+    pub token_limits: Vec<TokenLimit>,
+    /// Per-recipient caps.
+    pub recipient_caps: Vec<RecipientCap>,
+    /// Only transfers to `allowed_recipients` are permitted.
+    pub allowed_recipients_only: bool,
+    /// Recipients permitted in allowed-recipient-only mode.
+    pub allowed_recipients: Vec<Principal>,
+    /// Optional quiet hours.
+    pub quiet_hours: Option<QuietHours>,
+}
+
+/// Rule of a [`SpendingPolicy`] that a transfer breaks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PolicyViolation {
+    /// Amount exceeds the per-transaction maximum.
+    PerTxMax { limit: Nat },
+    /// Total over the last 24 hours would exceed the daily cap.
+    DailyCap { limit: Nat, spent: Nat },
+    /// Total over the last 7 days would exceed the weekly cap.
+    WeeklyCap { limit: Nat, spent: Nat },
+    /// Total to the recipient over the last 24 hours would exceed its cap.
+    RecipientCap { limit: Nat, spent: Nat },
+    /// Recipient is not on the allowed list.
+    RecipientNotAllowed,
+    /// Current local time is inside quiet hours.
+    QuietHours { start_hour: u8, end_hour: u8 },
+}
+
+impl PolicyViolation {
+    /// Error code reported to the model.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PolicyViolation::PerTxMax { .. } => "PolicyPerTxMax",
+            PolicyViolation::DailyCap { .. } => "PolicyDailyCap",
+            PolicyViolation::WeeklyCap { .. } => "PolicyWeeklyCap",
+            PolicyViolation::RecipientCap { .. } => "PolicyRecipientCap",
+            PolicyViolation::RecipientNotAllowed => "PolicyRecipientNotAllowed",
+            PolicyViolation::QuietHours { .. } => "PolicyQuietHours",
+        }
+    }
+
+    /// Human readable detail of the violation.
+    pub fn detail(&self) -> String {
+        match self {
+            PolicyViolation::PerTxMax { limit } => format!("per-transaction maximum is {}", limit),
+            PolicyViolation::DailyCap { limit, spent } => format!("daily cap {} (already sent {})", limit, spent),
+            PolicyViolation::WeeklyCap { limit, spent } => format!("weekly cap {} (already sent {})", limit, spent),
+            PolicyViolation::RecipientCap { limit, spent } => format!("recipient daily cap {} (already sent {})", limit, spent),
+            PolicyViolation::RecipientNotAllowed => "recipient is not on the allowed list".into(),
+            PolicyViolation::QuietHours { start_hour, end_hour } => format!("quiet hours {:02}:00-{:02}:00", start_hour, end_hour),
+        }
+    }
+}
+
+/// Amount of a token from which co-signer approval is needed, in minimal units.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct TokenThreshold {
+    /// Token symbol.
+    pub symbol: String,
+    /// Transfers of at least this amount need approval.
+    pub amount: Nat,
+}
+
+/// M-of-N co-signer setup of an owner (e.g. a team treasury).
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize)]
+pub struct CosignConfig {
+    /// Principals allowed to approve.
+    pub cosigners: Vec<Principal>,
+    /// Approvals needed (M).
+    pub required: u8,
+    /// Per-token amounts that trigger the approval requirement.
+    pub thresholds: Vec<TokenThreshold>,
+}
+
+/// Approval state of a plan that needs co-signers.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ApprovalRequest {
+    /// Checksum of the plan awaiting approval.
+    pub checksum: String,
+    /// Plan as shown to co-signers.
+    pub plan: TransferPlan,
+    /// Co-signers at the time the plan was made.
+    pub cosigners: Vec<Principal>,
+    /// Approvals needed.
+    pub required: u8,
+    /// Co-signers who approved so far.
+    pub approved_by: Vec<Principal>,
+    /// Co-signer who rejected the plan, if any.
+    pub rejected_by: Option<Principal>,
+    /// Reason given with the rejection.
+    pub rejection_reason: Option<String>,
+    /// Request is dropped after this time (plans are only valid for the ledger's window).
+    pub expires_at: Option<u64>,
+}
+
+/// Several transfers confirmed and executed together (e.g. a split bill).
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct BatchPlan {
+    /// Principal paying every leg.
+    pub owner: Principal,
+    /// Individual transfers; each keeps its own checksum for anti-replay.
+    pub legs: Vec<TransferPlan>,
+    /// Canonical token symbol.
+    pub symbol: String,
+    /// Total being split in minimal units, including the owner's own share.
+    pub total: Nat,
+    /// Human friendly description of the batch.
+    pub human_readable: String,
+    /// Creation timestamp in nanoseconds.
+    pub created_at_time: u64,
+    /// Checksum over the legs' checksums.
+    pub checksum: String,
+}
+
+/// Plan awaiting the user's confirmation.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub enum PendingPlan {
+    Transfer(TransferPlan),
+    Batch(BatchPlan),
+    Swap(Box<SwapPlan>),
+}
+
+impl PendingPlan {
+    /// Checksum used to confirm the plan.
+    pub fn checksum(&self) -> &str {
+        match self {
+            PendingPlan::Transfer(p) => &p.checksum,
+            PendingPlan::Batch(b) => &b.checksum,
+            PendingPlan::Swap(s) => &s.checksum,
+        }
+    }
+
+    /// Principal whose funds the plan moves.
+    pub fn owner(&self) -> Option<Principal> {
+        match self {
+            PendingPlan::Transfer(p) => p.from_owner,
+            PendingPlan::Batch(b) => Some(b.owner),
+            PendingPlan::Swap(s) => Some(s.owner),
+        }
+    }
+
+    /// One-line summary shown to the user.
+    pub fn human_readable(&self) -> &str {
+        match self {
+            PendingPlan::Transfer(p) => &p.human_readable,
+            PendingPlan::Batch(b) => &b.human_readable,
+            PendingPlan::Swap(s) => &s.human_readable,
+        }
+    }
+
+    /// Ledger transfers pulled from the owner when the plan executes.
+    pub fn legs(&self) -> &[TransferPlan] {
+        match self {
+            PendingPlan::Transfer(p) => std::slice::from_ref(p),
+            PendingPlan::Batch(b) => &b.legs,
+            PendingPlan::Swap(s) => std::slice::from_ref(&s.deposit),
+        }
+    }
+}
+
+tool_args! {
+    /// Arguments of the `plan_split` tool.
+    #[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+    pub struct SplitArgs {
+        /// Total amount to split as a decimal string.
+        pub total_dec: String => string().required(),
+        /// Comma separated recipients (aliases, principals or "me").
+        pub recipients: String => string().describe("Comma separated aliases/principals; use \"me\" for the user").required(),
+        /// Optional token symbol.
+        pub symbol: Option<String> => string(),
+        /// `equal` (default), `weights` or `percent`.
+        pub mode: Option<String> => string().one_of(&["equal", "weights", "percent"]),
+        /// Comma separated weights or percentages, one per recipient.
+        pub weights: Option<String> => string().describe("Comma separated, one per recipient"),
+        /// Optional memo string.
+        pub memo: Option<String> => string(),
+    }
+}
+
+/// Lifecycle state of an [`Invoice`].
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum InvoiceStatus {
+    Open,
+    Paid,
+    Cancelled,
+}
+
+tool_args! {
+    /// Arguments required to create an [`Invoice`].
+    #[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+    pub struct InvoiceArgs {
+        /// Requested amount as a decimal string.
+        pub amount_dec: String => string().required(),
+        /// Optional token symbol.
+        pub symbol: Option<String> => string(),
+        /// What the payment is for.
+        pub memo: Option<String> => string(),
+        /// Due date as `YYYY-MM-DD`.
+        pub due_date: Option<String> => string(),
+        /// Only this principal (text or alias) may pay, if set.
+        pub payer: Option<String> => string().describe("Alias/principal allowed to pay; omit for anyone"),
+    }
+}
+
+/// Payment request from one principal to another.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Invoice {
+    /// Invoice identifier shared with the payer.
+    pub id: u64,
+    /// Principal requesting (and receiving) the payment.
+    pub creator: Principal,
+    /// Principal expected to pay, if restricted.
+    pub payer: Option<Principal>,
+    /// Requested amount in minimal units.
+    pub amount: Nat,
+    /// Canonical token symbol.
+    pub symbol: String,
+    /// Ledger canister handling the token.
+    pub ledger: Principal,
+    /// What the payment is for.
+    pub memo: Option<String>,
+    /// Due time in nanoseconds (end of the due date).
+    pub due_at: Option<u64>,
+    /// Creation timestamp in nanoseconds.
+    pub created_at: u64,
+    /// Current lifecycle state.
+    pub status: InvoiceStatus,
+    /// Principal who paid the invoice.
+    pub paid_by: Option<Principal>,
+    /// Block index of the paying transfer.
+    pub paid_block_index: Option<Nat>,
+    /// Payment timestamp in nanoseconds.
+    pub paid_at: Option<u64>,
+}
+
+/// Lifecycle state of an [`Escrow`].
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum EscrowStatus {
+    AwaitingFunds,
+    Funded,
+    Released,
+    Refunded,
+}
+
+tool_args! {
+    /// Arguments required to open an [`Escrow`].
+    #[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+    pub struct EscrowArgs {
+        /// Recipient principal text or alias.
+        pub to: String => string().required(),
+        /// Amount to lock as a decimal string.
+        pub amount_dec: String => string().required(),
+        /// Refund deadline as `YYYY-MM-DD`.
+        pub deadline: String => string().describe("YYYY-MM-DD after which the payer may refund").required(),
+        /// Optional token symbol.
+        pub symbol: Option<String> => string(),
+        /// Optional arbiter principal text or alias.
+        pub arbiter: Option<String> => string(),
+        /// Optional memo.
+        pub memo: Option<String> => string(),
+    }
+}
+
+/// Funds locked in a canister-owned subaccount until released or refunded.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Escrow {
+    /// Escrow identifier.
+    pub id: u64,
+    /// Principal funding the escrow.
+    pub payer: Principal,
+    /// Principal receiving the funds on release.
+    pub recipient: Principal,
+    /// Optional recipient subaccount.
+    pub recipient_sub: Option<[u8; 32]>,
+    /// Principal allowed to release or refund besides the payer.
+    pub arbiter: Option<Principal>,
+    /// Locked amount in minimal units.
+    pub amount: Nat,
+    /// Canonical token symbol.
+    pub symbol: String,
+    /// Ledger canister handling the token.
+    pub ledger: Principal,
+    /// Canister subaccount holding the funds.
+    pub subaccount: [u8; 32],
+    /// Optional memo.
+    pub memo: Option<String>,
+    /// Time after which the payer may refund, in nanoseconds.
+    pub deadline_at: u64,
+    /// Creation timestamp in nanoseconds.
+    pub created_at: u64,
+    /// Current lifecycle state.
+    pub status: EscrowStatus,
+    /// Block index of the funding transfer.
+    pub fund_block_index: Option<Nat>,
+    /// Block index of the release or refund transfer.
+    pub settle_block_index: Option<Nat>,
+}
+
+/// Outcome of one simulation check.
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// The check could not be evaluated (e.g. ledger unreachable).
+    Unknown,
+}
+
+/// Single check of a [`SimulationReport`].
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct SimCheck {
+    /// Check name (allowlist, balance, allowance, fee, policy, ...).
+    pub name: String,
+    /// Outcome of the check.
+    pub status: CheckStatus,
+    /// Short explanation.
+    pub detail: String,
+}
+
+/// Dry-run result for a plan; nothing is sent to the ledger.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct SimulationReport {
+    /// Checksum of the simulated plan.
+    pub checksum: String,
+    /// Whether every check passed.
+    pub ok: bool,
+    /// Individual checks in evaluation order.
+    pub checks: Vec<SimCheck>,
+}
+
+/// Fields of a compact memo written by this canister.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DecodedMemo {
+    /// Layout version.
+    pub version: u8,
+    /// Invoice settled by the transfer, if any.
+    pub invoice_id: Option<u64>,
+    /// Hex of the short hash identifying the caller.
+    pub caller_tag: String,
+    /// Free text supplied by the user.
+    pub text: Option<String>,
+}
+
+tool_args! {
+    /// What `prepare_approval` should cover: a stored plan or a spending budget.
+    #[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize)]
+    pub struct ApprovalPrepArgs {
+        /// Plan or batch to cover; the caller's last plan if neither this nor a budget is set.
+        pub checksum: Option<String> => string(),
+        /// Budget as a decimal string, instead of a plan.
+        pub budget_dec: Option<String> => string(),
+        /// Token of the budget.
+        pub symbol: Option<String> => string(),
+        /// Days the allowance stays valid.
+        pub valid_days: Option<u32> => number(),
+    }
+}
+
+/// Ready-to-sign `icrc2_approve` call toward this canister.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct PreparedApproval {
+    /// Ledger canister to call `icrc2_approve` on.
+    pub ledger: Principal,
+    /// Canonical token symbol.
+    pub symbol: String,
+    /// Arguments for `icrc2_approve`.
+    pub args: ApproveArgs,
+    /// Allowance currently granted to this canister.
+    pub current_allowance: Nat,
+    /// Whether the current allowance already covers the request.
+    pub sufficient: bool,
+    /// One-line summary shown to the user.
+    pub human_readable: String,
+}
+
+tool_args! {
+    /// Arguments of the `plan_swap` tool.
+    #[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+    pub struct SwapPlanArgs {
+        /// Amount to sell as a decimal string.
+        pub amount_in_dec: String => string().required(),
+        /// Token to sell.
+        pub symbol_in: String => string().required(),
+        /// Token to buy.
+        pub symbol_out: String => string().required(),
+        /// Maximum accepted slippage in percent (e.g. "0.5").
+        pub slippage_pct: Option<String> => string().describe("Max slippage in percent, default 0.5"),
+    }
+}
+
+/// `quote` request of the DEX interface.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct DexQuoteArgs {
+    pub token_in: Principal,
+    pub token_out: Principal,
+    pub amount_in: Nat,
+}
+
+/// `quote` reply of the DEX interface.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct DexQuote {
+    /// Output for the quoted input, after DEX fees.
+    pub amount_out: Nat,
+    /// Price impact in basis points.
+    pub price_impact_bps: u32,
+    /// DEX fee in units of the input token.
+    pub fee: Nat,
+}
+
+/// `swap` request of the DEX interface; the input must already sit in the deposit subaccount.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct DexSwapArgs {
+    pub token_in: Principal,
+    pub token_out: Principal,
+    pub amount_in: Nat,
+    /// The DEX must fail below this output, leaving the deposit for `withdraw`.
+    pub min_amount_out: Nat,
+    /// DEX subaccount holding the deposited input.
+    pub deposit_subaccount: [u8; 32],
+    /// Account receiving the output.
+    pub recipient: IcrcAccount,
+}
+
+/// `swap` reply of the DEX interface.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct DexSwapResult {
+    pub amount_out: Nat,
+}
+
+/// `withdraw` request of the DEX interface: return an unswapped deposit.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct DexWithdrawArgs {
+    pub token: Principal,
+    pub amount: Nat,
+    /// DEX subaccount holding the deposit.
+    pub deposit_subaccount: [u8; 32],
+    /// Account receiving the refund.
+    pub recipient: IcrcAccount,
+}
+
+/// Output of an executed swap.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct SwapFill {
+    /// DEX canister that executed the swap.
+    pub dex: Principal,
+    /// Token bought.
+    pub symbol_out: String,
+    /// Amount bought in minimal units.
+    pub amount_out: Nat,
+}
+
+/// Planned swap: a deposit into the DEX followed by its `swap` call.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct SwapPlan {
+    /// Principal selling the input.
+    pub owner: Principal,
+    /// DEX canister quoting and executing the swap.
+    pub dex: Principal,
+    /// Transfer of the input into the DEX deposit subaccount.
+    pub deposit: TransferPlan,
+    /// Token bought.
+    pub symbol_out: String,
+    /// Ledger of the token bought.
+    pub ledger_out: Principal,
+    /// Quoted output in minimal units.
+    pub expected_out: Nat,
+    /// Minimum accepted output in minimal units.
+    pub min_out: Nat,
+    /// Quoted price impact in basis points.
+    pub price_impact_bps: u32,
+    /// Slippage bound in basis points.
+    pub slippage_bps: u32,
+    /// One-line summary shown to the user.
+    pub human_readable: String,
+    /// Checksum used to confirm the swap.
+    pub checksum: String,
+}
+
+/// Canister-wide settings managed by controllers.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Config {
+    /// DEX canister used for swaps.
+    pub dex_canister: Option<Principal>,
+    /// Price oracle canister used for fiat values.
+    pub oracle_canister: Option<Principal>,
+    /// Index canisters overriding the token registry, by symbol.
+    pub index_canisters: Vec<(String, Principal)>,
+    /// Model used for chats.
+    pub model: LlmModel,
+    /// Model tried when the primary call fails or returns malformed tool calls.
+    pub fallback_model: Option<LlmModel>,
+}
+
+impl Config {
+    /// Configuration of a freshly installed canister.
+    pub const fn new() -> Self {
+        Config {
+            dex_canister: None,
+            oracle_canister: None,
+            index_canisters: Vec::new(),
+            model: LlmModel::Llama3_1_8B,
+            fallback_model: Some(LlmModel::Qwen3_32B),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::new()
+    }
+}
+
+/// `get_price` request of the price oracle interface.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct PriceRequest {
+    /// Token symbol (e.g. "ICP").
+    pub symbol: String,
+    /// Fiat currency code (e.g. "IDR").
+    pub currency: String,
+}
+
+/// `get_price` reply of the price oracle interface.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Price {
+    /// Fiat units per whole token, scaled by 1e8.
+    pub rate_e8: u64,
+    /// Time the oracle observed the price, in nanoseconds.
+    pub timestamp: u64,
+}
+
+/// Balance of one allowlisted token in a portfolio.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct TokenBalance {
+    /// Token symbol.
+    pub symbol: String,
+    /// Ledger canister, `None` when the configured id is invalid.
+    pub ledger: Option<Principal>,
+    /// Number of decimal places used by the token.
+    pub decimals: u8,
+    /// Balance in minimal units, `None` when the ledger could not be queried.
+    pub balance: Option<Nat>,
+    /// Balance formatted with decimals.
+    pub balance_dec: Option<String>,
+    /// Live allowance granted to this canister, in minimal units.
+    pub allowance: Option<Nat>,
+    /// Expiry of the allowance in nanoseconds, if any.
+    pub allowance_expires_at: Option<u64>,
+    /// Fiat value of the balance (e.g. "≈ Rp 50.000 / $3.10").
+    pub fiat_value: Option<String>,
+    /// Errors from the ledger queries.
+    pub errors: Vec<String>,
+}
+
+/// Balances of the caller across all allowlisted tokens.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Portfolio {
+    /// Account the balances belong to.
+    pub owner: Principal,
+    /// One entry per allowlisted token.
+    pub tokens: Vec<TokenBalance>,
+    /// One-line summary for the chat.
+    pub human_readable: String,
+}
+
+/// `get_account_transactions` request of the ICRC index canister.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct GetAccountTransactionsArgs {
+    pub account: IcrcAccount,
+    /// Id of the last transaction seen; results start after it. `None` starts at the newest.
+    pub start: Option<Nat>,
+    pub max_results: Nat,
+}
+
+/// Transfer part of an index transaction.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct IndexTransfer {
+    pub from: IcrcAccount,
+    pub to: IcrcAccount,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub spender: Option<IcrcAccount>,
+}
+
+/// Mint part of an index transaction.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct IndexMint {
+    pub to: IcrcAccount,
+    pub amount: Nat,
+    pub memo: Option<Vec<u8>>,
+}
+
+/// Burn part of an index transaction.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct IndexBurn {
+    pub from: IcrcAccount,
+    pub amount: Nat,
+    pub memo: Option<Vec<u8>>,
+}
+
+/// Approve part of an index transaction.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct IndexApprove {
+    pub from: IcrcAccount,
+    pub spender: IcrcAccount,
+    pub amount: Nat,
+}
+
+/// Ledger transaction as returned by the index; only the fields the copilot reads.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct IndexTransaction {
+    /// "transfer", "mint", "burn" or "approve".
+    pub kind: String,
+    pub timestamp: u64,
+    pub transfer: Option<IndexTransfer>,
+    pub mint: Option<IndexMint>,
+    pub burn: Option<IndexBurn>,
+    pub approve: Option<IndexApprove>,
+}
+
+/// Index transaction with its ledger id.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct TransactionWithId {
+    pub id: Nat,
+    pub transaction: IndexTransaction,
+}
+
+/// Page of account transactions, newest first.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct GetTransactions {
+    pub balance: Nat,
+    pub transactions: Vec<TransactionWithId>,
+    /// Id of the oldest transaction of the account.
+    pub oldest_tx_id: Option<Nat>,
+}
+
+/// Error reply of the index canister.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct GetTransactionsErr {
+    pub message: String,
+}
+
+tool_args! {
+    /// Arguments of the `get_account_transactions` tool.
+    #[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize)]
+    pub struct HistoryArgs {
+        /// Token symbol; defaults to the default token.
+        pub symbol: Option<String> => string(),
+        /// "in", "out" or "all" (default).
+        pub direction: Option<String> => string().one_of(&["in", "out", "all"]),
+        /// Cursor from a previous page.
+        pub start: Option<String> => string().describe("next_start of the previous page"),
+        /// Page size; defaults to 10.
+        pub max_results: Option<u32> => number(),
+    }
+}
+
+/// Ledger transaction of the caller's account, seen from the caller.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct AccountTx {
+    /// Ledger transaction id.
+    pub id: Nat,
+    /// "transfer", "mint", "burn" or "approve".
+    pub kind: String,
+    /// "in", "out" or "self".
+    pub direction: String,
+    /// Other party of the transaction, if any.
+    pub counterparty: Option<IcrcAccount>,
+    /// Amount in minimal units.
+    pub amount: Nat,
+    /// Amount formatted with decimals.
+    pub amount_dec: String,
+    /// Decoded memo, if any.
+    pub memo_text: Option<String>,
+    /// Ledger timestamp in nanoseconds.
+    pub timestamp: u64,
+}
+
+/// Page of the caller's ledger history.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct TxPage {
+    pub symbol: String,
+    /// Current balance formatted with decimals.
+    pub balance_dec: String,
+    pub transactions: Vec<AccountTx>,
+    /// Cursor for the next (older) page; `None` on the last page.
+    pub next_start: Option<String>,
+}
+
+tool_args! {
+    /// Arguments of the `spending_summary` tool.
+    #[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize)]
+    pub struct SummaryArgs {
+        /// Only this token.
+        pub symbol: Option<String> => string(),
+        /// Only this alias or principal.
+        pub recipient: Option<String> => string().describe("Alias or principal"),
+        /// Only recipients with this tag.
+        pub tag: Option<String> => string().describe("Recipient tag, e.g. \"vendor\""),
+        /// Bucket size: "day", "week" or "month" (default).
+        pub period: Option<String> => string().one_of(&["day", "week", "month"]),
+        /// First day included, `YYYY-MM-DD` or a whole month `YYYY-MM`.
+        pub from: Option<String> => string().describe("YYYY-MM-DD or YYYY-MM"),
+        /// Last day included, `YYYY-MM-DD` or a whole month `YYYY-MM`.
+        pub to: Option<String> => string().describe("YYYY-MM-DD or YYYY-MM, inclusive"),
+        /// Number of top recipients returned (default 5).
+        pub top: Option<u32> => number(),
+    }
+}
+
+/// Total sent in one token for one group of receipts.
+#[derive(Clone, Debug, PartialEq, CandidType, Deserialize, Serialize)]
+pub struct SummaryGroup {
+    /// Group key: token symbol, period label, recipient or tag.
+    pub key: String,
+    /// Token symbol of the total.
+    pub symbol: String,
+    /// Total in minimal units.
+    pub total: Nat,
+    /// Total formatted with decimals.
+    pub total_dec: String,
+    /// Number of transfers.
+    pub count: u32,
+}
+
+/// Aggregated spending of the caller.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct SpendingSummary {
+    /// Start of the range in nanoseconds, inclusive.
+    pub from: Option<u64>,
+    /// End of the range in nanoseconds, exclusive.
+    pub to: Option<u64>,
+    /// Totals per token.
+    pub totals: Vec<SummaryGroup>,
+    /// Totals per period and token, oldest first.
+    pub by_period: Vec<SummaryGroup>,
+    /// Largest recipients per token.
+    pub top_recipients: Vec<SummaryGroup>,
+    /// Totals per recipient tag and token.
+    pub by_tag: Vec<SummaryGroup>,
+    /// One-line summary for the chat.
+    pub human_readable: String,
+}
+
+/// LLM models the copilot can run on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CandidType, Deserialize, Serialize)]
+pub enum LlmModel {
+    Llama3_1_8B,
+    Qwen3_32B,
+    Llama4Scout,
+}
+
+impl LlmModel {
+    /// Corresponding [`ic_llm::Model`].
+    pub fn model(self) -> ic_llm::Model {
+        match self {
+            LlmModel::Llama3_1_8B => ic_llm::Model::Llama3_1_8B,
+            LlmModel::Qwen3_32B => ic_llm::Model::Qwen3_32B,
+            LlmModel::Llama4Scout => ic_llm::Model::Llama4Scout,
+        }
+    }
+}
+
+/// Canister init arguments.
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize)]
+pub struct InitArgs {
+    /// Model used for chats; defaults to Llama 3.1 8B.
+    pub model: Option<LlmModel>,
+    /// Model tried when the primary fails.
+    pub fallback_model: Option<LlmModel>,
+}
+
+/// Model used for one LLM round of a chat.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct ModelTrace {
+    /// Round of the chat, starting at 1.
+    pub round: u32,
+    /// Model whose reply was used, `None` when every model failed.
+    pub model: Option<LlmModel>,
+    /// Whether the fallback model was needed.
+    pub fallback: bool,
+    /// Why the primary model's reply was rejected.
+    pub error: Option<String>,
+}
+
+/// Fund-moving tool call waiting for the user's confirmation in a later turn.
+#[derive(Clone, Debug)]
+pub struct PendingAction {
+    /// Tool that runs once confirmed.
+    pub tool: String,
+    /// Checksum the user confirms; covers the caller, tool and arguments.
+    pub checksum: String,
+    /// One-line summary shown to the user.
+    pub summary: String,
+    /// Creation time in nanoseconds.
+    pub created_at: u64,
+}
+
+/// Server-side conversation of one caller.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+pub struct Session {
+    /// Session identifier.
+    pub id: u64,
+    /// Caller the session belongs to.
+    pub owner: Principal,
+    /// Stored conversation without the system prompt, oldest first.
+    pub messages: Vec<ChatMessage>,
+    /// Checksum of the plan or tool call awaiting confirmation in this session, if any.
+    pub pending_checksum: Option<String>,
+    /// Whether the last assistant turn presented `pending_checksum`; a bare "yes" confirms only then.
+    pub pending_in_last_turn: bool,
+    /// Creation time in nanoseconds.
+    pub created_at: u64,
+    /// Time of the last message in nanoseconds.
+    pub updated_at: u64,
+}
+
+/// State saved to stable memory across upgrades.
+#[derive(Clone, Debug, Default, CandidType, Deserialize)]
+pub struct StableState {
+    /// Escrows and the last issued escrow id.
+    pub escrows: (Vec<Escrow>, u64),
+    /// Recurring series and the last issued series id.
+    pub recurring: (Vec<RecurringTransfer>, u64),
+    /// Spending policy of each owner.
+    pub policies: Vec<(Principal, SpendingPolicy)>,
+    /// Invoices and the last issued invoice id.
+    pub invoices: (Vec<Invoice>, u64),
+    /// Co-signer setup of each owner.
+    pub cosign_configs: Vec<(Principal, CosignConfig)>,
+    /// Approval requests of plans that need co-signers, including recurring series.
+    pub approvals: Vec<ApprovalRequest>,
+}
@@ -1,11 +1,12 @@
 use candid::{Nat, Principal};
 use ic_llm::{ChatMessage, ToolCall};
-use serde_json::{Map, Number, Value};
+use icrc_ledger_types::icrc1::transfer::Memo;
+use serde_json::{json, Map, Value};
 use num_bigint::BigUint;
 use sha2::{Digest, Sha256};
 
 use crate::{ACCOUNTS, log};
-use crate::types::{TransferPlan, TOKENS};
+use crate::types::{DecodedMemo, PendingPlan, SwapPlan, TransferPlan, TOKENS};
 
 /// Returns all allowlisted token symbols.
 pub fn token_symbols() -> Vec<&'static str> {
@@ -66,6 +67,32 @@ pub fn resolve_token(symbol_opt: Option<&str>, ledger_opt: Option<&str>)
     Ok((p, t.decimals, t.symbol.to_string()))
 }
 
+/// Resolve the token of a plan; a `ledger` or `decimals` contradicting the allowlist is a `Conflict`.
+pub fn resolve_plan_token(symbol: Option<&str>, ledger: Option<&str>, decimals: Option<u8>)
+    -> Result<(Principal, u8, String), Value>
+{
+    let bad_token = |field: &str| json!({"status":"err","code":"BadToken","field":field,"options": token_symbols()});
+    let by_symbol = match symbol {
+        Some(s) => Some(resolve_token(Some(s), None).map_err(|_| bad_token("symbol"))?),
+        None => None,
+    };
+    let token = match (ledger, by_symbol) {
+        (Some(l), Some(s)) => {
+            if resolve_token(None, Some(l)).map_or(true, |t| t.2 != s.2) {
+                return Err(json!({"status":"err","code":"Conflict","field":"ledger","got":l,"expected":s.0.to_text(),"symbol":s.2}));
+            }
+            s
+        }
+        (Some(l), None) => resolve_token(None, Some(l)).map_err(|_| bad_token("ledger"))?,
+        (None, Some(s)) => s,
+        (None, None) => resolve_token(None, None).map_err(|_| bad_token("symbol"))?,
+    };
+    if let Some(d) = decimals.filter(|d| *d != token.1) {
+        return Err(json!({"status":"err","code":"Conflict","field":"decimals","got":d,"expected":token.1,"symbol":token.2}));
+    }
+    Ok(token)
+}
+
 /// Provide an example amount string for a given decimal precision.
 pub fn example_for_decimals(decimals: u8) -> &'static str {
     if decimals == 0 { "10" } else { "0.5" }
@@ -97,30 +124,230 @@ pub fn plan_checksum(p: &TransferPlan) -> String {
     h.update(p.ledger.as_slice());
     if let Some(m) = &p.memo { h.update(&m.0); }
     h.update(p.created_at_time.to_le_bytes());
+    if p.approvals_required > 0 { h.update([p.approvals_required]); }
+    if let Some(id) = p.invoice_id { h.update(id.to_le_bytes()); }
+    if let Some(id) = p.escrow_id { h.update(b"escrow"); h.update(id.to_le_bytes()); }
+    if let Some(prev) = &p.supersedes { h.update(prev.as_bytes()); }
     hex::encode(&h.finalize()[..8])
 }
 
-/// Determine if a string is considered a placeholder value.
-pub fn is_placeholder(s: &str) -> bool {
-    let t = s.trim().to_ascii_lowercase();
-    t.is_empty() || matches!(t.as_str(), "unknown" | "tbd" | "-" | "null" | "?" | "n/a")
+/// User supplied memo text of a plan, without the caller tag.
+pub fn plan_memo_text(p: &TransferPlan) -> Option<String> {
+    let bytes = &p.memo.as_ref()?.0;
+    if let Some(d) = decode_memo(bytes) { return d.text; }
+    let raw = String::from_utf8(bytes.to_vec()).ok()?;
+    Some(raw.rsplit_once("|caller:").map(|(m, _)| m.to_string()).unwrap_or(raw))
 }
 
-/// Coerce a JSON value to a number when possible.
-pub fn to_number(v: &Value) -> Option<Value> {
-    match v {
-        Value::Number(_) => Some(v.clone()),
-        Value::String(s) => s.parse::<u64>().ok().map(Number::from).map(Value::Number),
-        _ => None,
+/// Leading byte of memos written by this canister.
+pub const MEMO_MAGIC: u8 = 0xCF;
+/// Current memo layout version.
+pub const MEMO_VERSION: u8 = 1;
+const MEMO_FLAG_INVOICE: u8 = 0b0000_0001;
+const MEMO_TAG_LEN: usize = 4;
+
+/// Largest memo an allowlisted token accepts (32 bytes if unknown).
+pub fn token_max_memo(symbol: &str) -> usize {
+    TOKENS.iter().find(|t| t.symbol.eq_ignore_ascii_case(symbol)).map(|t| t.max_memo as usize).unwrap_or(32)
+}
+
+/// Short hash identifying `caller` inside memos.
+pub fn caller_tag(caller: Principal) -> [u8; MEMO_TAG_LEN] {
+    let h = Sha256::digest(caller.as_slice());
+    [h[0], h[1], h[2], h[3]]
+}
+
+/// Encode a compact memo: magic, version, flags, optional invoice id, caller tag, then text.
+pub fn encode_memo(text: Option<&str>, invoice_id: Option<u64>, caller: Principal) -> Vec<u8> {
+    let mut out = vec![MEMO_MAGIC, MEMO_VERSION, if invoice_id.is_some() { MEMO_FLAG_INVOICE } else { 0 }];
+    if let Some(id) = invoice_id { out.extend_from_slice(&id.to_be_bytes()); }
+    out.extend_from_slice(&caller_tag(caller));
+    if let Some(t) = text { out.extend_from_slice(t.as_bytes()); }
+    out
+}
+
+/// Decode a memo written by [`encode_memo`].
+pub fn decode_memo(bytes: &[u8]) -> Option<DecodedMemo> {
+    let (&[magic, version, flags], rest) = bytes.split_first_chunk::<3>()?;
+    if magic != MEMO_MAGIC || version != MEMO_VERSION { return None; }
+    let (invoice_id, rest) = if flags & MEMO_FLAG_INVOICE != 0 {
+        let (id, rest) = rest.split_first_chunk::<8>()?;
+        (Some(u64::from_be_bytes(*id)), rest)
+    } else {
+        (None, rest)
+    };
+    let (tag, text) = rest.split_first_chunk::<MEMO_TAG_LEN>()?;
+    let text = std::str::from_utf8(text).ok()?;
+    Some(DecodedMemo {
+        version,
+        invoice_id,
+        caller_tag: hex::encode(tag),
+        text: (!text.is_empty()).then(|| text.to_string()),
+    })
+}
+
+/// Readable form of any memo: decoded fields, plain text, or hex.
+pub fn describe_memo(bytes: &[u8]) -> String {
+    if let Some(d) = decode_memo(bytes) {
+        let mut parts = Vec::new();
+        if let Some(id) = d.invoice_id { parts.push(format!("invoice #{}", id)); }
+        if let Some(t) = d.text { parts.push(format!("\"{}\"", t)); }
+        parts.push(format!("caller {}", d.caller_tag));
+        return parts.join(", ");
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(t) => t.to_string(),
+        Err(_) => format!("0x{}", hex::encode(bytes)),
+    }
+}
+
+/// Build the memo of a plan, rejecting it if it exceeds the token's limit.
+pub fn plan_memo(text: Option<&str>, invoice_id: Option<u64>, caller: Principal, symbol: &str) -> Result<Option<Memo>, Value> {
+    let text = text.filter(|t| !is_placeholder(t));
+    if text.is_none() && invoice_id.is_none() { return Ok(None); }
+    let bytes = encode_memo(text, invoice_id, caller);
+    let max = token_max_memo(symbol);
+    log!("[plan_memo] memo_len={} max={}", bytes.len(), max);
+    if bytes.len() > max {
+        let overhead = bytes.len() - text.map_or(0, str::len);
+        return Err(json!({
+            "status":"err","code":"BadMemo","field":"memo",
+            "error": format!("memo too long for {}: {} bytes, max {}", symbol, bytes.len(), max),
+            "max_text_bytes": max.saturating_sub(overhead),
+        }));
     }
+    Ok(Some(Memo(bytes.into())))
+}
+
+/// Format an amount in minimal units as a decimal string without trailing zeros.
+pub fn format_amount(n: &Nat, decimals: u8) -> String {
+    let s = n.0.to_str_radix(10);
+    if decimals == 0 { return s; }
+    let d = decimals as usize;
+    let padded = format!("{:0>width$}", s, width = d + 1);
+    let (int_part, frac_part) = padded.split_at(padded.len() - d);
+    let frac = frac_part.trim_end_matches('0');
+    if frac.is_empty() { int_part.to_string() } else { format!("{}.{}", int_part, frac) }
+}
+
+/// Compute a checksum for a batch from its legs' checksums.
+pub fn batch_checksum(legs: &[TransferPlan]) -> String {
+    let mut h = Sha256::new();
+    h.update(b"batch");
+    for l in legs { h.update(l.checksum.as_bytes()); }
+    hex::encode(&h.finalize()[..8])
 }
 
-/// Convert any JSON value to a string representation.
-pub fn to_string(_raw: &str, v: &Value) -> Value {
-    match v { Value::String(s) => Value::String(s.clone()), other => Value::String(other.to_string()) }
+/// Checksum of a fund-moving tool call; the same arguments give the same checksum in a later turn.
+pub fn action_checksum(caller: Principal, tool: &str, args: &Value) -> String {
+    let mut h = Sha256::new();
+    h.update(b"action");
+    h.update(caller.as_slice());
+    h.update(tool.as_bytes());
+    h.update(args.to_string().as_bytes());
+    hex::encode(&h.finalize()[..8])
 }
 
-/// Parse tool call arguments into a JSON object for easier handling.
+/// Canister subaccount holding the funds of escrow `id`.
+pub fn escrow_subaccount(id: u64) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(b"escrow");
+    h.update(id.to_be_bytes());
+    h.finalize().into()
+}
+
+/// DEX subaccount receiving the input of a swap by `owner` planned at `created`.
+pub fn swap_deposit_subaccount(owner: Principal, created: u64) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(b"swap");
+    h.update(owner.as_slice());
+    h.update(created.to_be_bytes());
+    h.finalize().into()
+}
+
+/// Compute a checksum for a swap from its deposit leg and execution bounds.
+pub fn swap_checksum(p: &SwapPlan) -> String {
+    let mut h = Sha256::new();
+    h.update(b"swap");
+    h.update(p.deposit.checksum.as_bytes());
+    h.update(p.dex.as_slice());
+    h.update(p.ledger_out.as_slice());
+    h.update(p.min_out.to_string().as_bytes());
+    hex::encode(&h.finalize()[..8])
+}
+
+/// Nanoseconds in one day.
+pub const NANOS_PER_DAY: u64 = 86_400 * 1_000_000_000;
+
+/// How long a plan stays executable; ICRC ledgers reject `created_at_time` older than their 24h window.
+pub const PLAN_TTL_NS: u64 = NANOS_PER_DAY;
+
+/// Convert a civil date to days since the Unix epoch.
+pub fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let mp = (m as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Convert days since the Unix epoch to a civil `(year, month, day)`.
+pub fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719_468;
+    let era = (if z >= 0 { z } else { z - 146_096 }) / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    (if m <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 }, m, d)
+}
+
+/// Number of days in the given month.
+pub fn days_in_month(y: i64, m: u32) -> u32 {
+    let next = if m == 12 { days_from_civil(y + 1, 1, 1) } else { days_from_civil(y, m + 1, 1) };
+    (next - days_from_civil(y, m, 1)) as u32
+}
+
+/// Parse a `YYYY-MM-DD` date into nanoseconds since the epoch (00:00 UTC).
+pub fn parse_date_ns(s: &str) -> Result<u64, String> {
+    let parts: Vec<&str> = s.trim().split('-').collect();
+    if parts.len() != 3 { return Err("format tanggal harus YYYY-MM-DD".into()); }
+    let y: i64 = parts[0].parse().map_err(|_| "tahun tidak valid")?;
+    let m: u32 = parts[1].parse().map_err(|_| "bulan tidak valid")?;
+    let d: u32 = parts[2].parse().map_err(|_| "tanggal tidak valid")?;
+    if !(1..=12).contains(&m) || d == 0 || d > days_in_month(y, m) || y < 1970 {
+        return Err("tanggal tidak valid".into());
+    }
+    Ok(days_from_civil(y, m, d) as u64 * NANOS_PER_DAY)
+}
+
+/// Format a nanosecond timestamp as `YYYY-MM-DD` (UTC).
+pub fn format_date_ns(ts: u64) -> String {
+    let (y, m, d) = civil_from_days((ts / NANOS_PER_DAY) as i64);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// Add calendar months to a timestamp, clamping to the last day of the target month.
+pub fn add_months_ns(ts: u64, months: u32) -> u64 {
+    let day_ns = ts % NANOS_PER_DAY;
+    let (y, m, d) = civil_from_days((ts / NANOS_PER_DAY) as i64);
+    let total = (m - 1) as i64 + months as i64;
+    let (ny, nm) = (y + total.div_euclid(12), total.rem_euclid(12) as u32 + 1);
+    let nd = d.min(days_in_month(ny, nm));
+    days_from_civil(ny, nm, nd) as u64 * NANOS_PER_DAY + day_ns
+}
+
+/// Determine if a string is considered a placeholder value.
+pub fn is_placeholder(s: &str) -> bool {
+    let t = s.trim().to_ascii_lowercase();
+    t.is_empty() || matches!(t.as_str(), "unknown" | "tbd" | "-" | "null" | "?" | "n/a")
+}
+
+/// Parse tool call arguments into a JSON object; values are typed by the tool's schema later.
 pub fn tool_args_json(call: &ToolCall) -> Value {
     let mut m: Map<String, Value> = Map::new();
     for arg in &call.function.arguments {
@@ -133,50 +360,201 @@ pub fn tool_args_json(call: &ToolCall) -> Value {
             Ok(v) => v,
             Err(_) => Value::String(arg.value.clone()),
         };
-        let coerced = match arg.name.as_str() {
-            "to" | "symbol" | "ledger" | "memo" => to_string(&arg.value, &parsed),
-            "amount_dec" => to_string(&arg.value, &parsed),
-            "decimals" => to_number(&parsed).unwrap_or(Value::Number(Number::from(0u64))),
-            _ => parsed,
-        };
-        m.insert(arg.name.clone(), coerced);
+        m.insert(arg.name.clone(), parsed);
     }
     Value::Object(m)
 }
 
-/// Maximum number of messages kept in conversation (including system message).
-pub const MAX_MESSAGES: usize = 10;
+/// Approximate token budget of the conversation sent to the model.
+pub const MAX_CONTEXT_TOKENS: usize = 3_000;
 
-/// Trim the conversation to the most recent [`MAX_MESSAGES`] messages in place.
-pub fn cap_messages_in_place(convo: &mut Vec<ChatMessage>) {
-    if convo.len() <= MAX_MESSAGES { return; }
-    let system = convo.first().cloned();
-    let keep = convo.split_off(convo.len().saturating_sub(MAX_MESSAGES - 1));
-    convo.clear();
-    if let Some(sys) = system { convo.push(sys); }
-    convo.extend(keep);
+/// Rough token estimate of a message: ~4 characters per token plus per-message overhead.
+pub fn approx_tokens(m: &ChatMessage) -> usize {
+    let chars = match m {
+        ChatMessage::System { content } | ChatMessage::User { content } => content.len(),
+        ChatMessage::Assistant(a) => a.content.as_ref().map_or(0, String::len)
+            + a.tool_calls.iter().map(|c| serde_json::to_string(c).map_or(0, |s| s.len())).sum::<usize>(),
+        ChatMessage::Tool { content, tool_call_id } => content.len() + tool_call_id.len(),
+    };
+    chars.div_ceil(4) + 4
 }
 
-/// Heuristic to detect the language of the last user message.
-pub fn detect_lang_last_user(msgs: &[ChatMessage]) -> &'static str {
-    for m in msgs.iter().rev() {
-        if let ChatMessage::User { content } = m {
-            let lc = content.to_ascii_lowercase();
-            if lc.contains("english please") || lc.contains("english") { return "en"; }
-            if lc.contains("indonesia") || lc.contains("indonesian") { return "id"; }
-            if lc.contains("yang") || lc.contains("kamu") || lc.contains("sudah") { return "id"; }
-            return "en";
+/// System note restating the pending plan once the turns that produced it are trimmed.
+pub fn pending_plan_note(plan: &PendingPlan) -> ChatMessage {
+    let mut s = format!("PENDING PLAN (earlier turns trimmed): {} checksum={}", plan.human_readable(), plan.checksum());
+    if let PendingPlan::Transfer(p) = plan {
+        let decimals = TOKENS.iter().find(|t| t.symbol == p.symbol).map(|t| t.decimals).unwrap_or(0);
+        s.push_str(&format!(
+            " Known: to={} amount_dec={} symbol={}{}.",
+            p.to_principal, format_amount(&p.amount, decimals), p.symbol,
+            p.memo.as_ref().map(|m| format!(" memo={}", describe_memo(&m.0))).unwrap_or_default(),
+        ));
+    }
+    s.push_str(" It is not sent until the user confirms.");
+    ChatMessage::System { content: s }
+}
+
+/// Trim the conversation to `budget` approximate tokens in place.
+///
+/// Leading system messages, the latest message and the latest user message are always kept, the
+/// user message truncated if it alone exceeds the budget, so the slots of the current request survive
+/// even when no plan is pending. An assistant tool-call message and its tool results are kept or
+/// dropped together. When anything is dropped, `note` is inserted after the leading system messages.
+pub fn trim_to_token_budget(convo: &mut Vec<ChatMessage>, budget: usize, note: Option<ChatMessage>) {
+    let head = convo.iter().take_while(|m| matches!(m, ChatMessage::System { .. })).count();
+    let total: usize = convo.iter().map(approx_tokens).sum();
+    if total <= budget { return; }
+
+    // group the tail into units: a tool-call message owns the tool results after it
+    let mut units: Vec<Vec<ChatMessage>> = Vec::new();
+    for m in convo.drain(head..) {
+        match (&m, units.last_mut()) {
+            (ChatMessage::Tool { .. }, Some(u)) if matches!(u[0], ChatMessage::Assistant(ref a) if !a.tool_calls.is_empty()) => u.push(m),
+            // a tool result without its call is invalid for the model
+            (ChatMessage::Tool { .. }, _) => {}
+            _ => units.push(vec![m]),
+        }
+    }
+    let cost = |u: &Vec<ChatMessage>| u.iter().map(approx_tokens).sum::<usize>();
+    let last = units.len().checked_sub(1);
+    let last_user = units.iter().rposition(|u| matches!(u[0], ChatMessage::User { .. }));
+
+    let mut used: usize = convo.iter().map(approx_tokens).sum::<usize>() + note.as_ref().map_or(0, approx_tokens);
+    used += last.filter(|l| Some(*l) != last_user).map_or(0, |l| cost(&units[l]));
+    if let Some(ChatMessage::User { content }) = last_user.map(|i| &mut units[i][0]) {
+        let room = budget.saturating_sub(used + 4).max(16) * 4;
+        if content.len() > room {
+            let cut = (0..=room).rev().find(|i| content.is_char_boundary(*i)).unwrap_or(0);
+            content.truncate(cut);
+        }
+    }
+    used += last_user.map_or(0, |i| cost(&units[i]));
+
+    let mut kept = vec![false; units.len()];
+    let mut full = false;
+    for (i, u) in units.iter().enumerate().rev() {
+        if Some(i) == last || Some(i) == last_user {
+            kept[i] = true;
+            continue;
         }
+        // older turns are dropped from the first one that does not fit
+        if full || used + cost(u) > budget { full = true; continue; }
+        used += cost(u);
+        kept[i] = true;
     }
-    "en"
+    let dropped = kept.contains(&false);
+    let text = |m: &ChatMessage| match m { ChatMessage::System { content } => Some(content.clone()), _ => None };
+    // later rounds keep the note from earlier ones among the leading system messages
+    if dropped && !note.as_ref().is_some_and(|n| convo.iter().any(|m| text(m) == text(n))) {
+        convo.extend(note);
+    }
+    convo.extend(units.into_iter().zip(kept).filter(|(_, k)| *k).flat_map(|(u, _)| u));
+}
+
+/// Supported reply languages: English, Indonesian, Malay and Javanese.
+pub const LANGUAGES: [&str; 4] = ["en", "id", "ms", "jv"];
+
+/// Canonical code of a language given by code or name ("jawa", "Bahasa Melayu", "english").
+pub fn normalize_lang(raw: &str) -> Option<&'static str> {
+    let t = raw.trim().to_lowercase();
+    let t = t.strip_prefix("bahasa ").or_else(|| t.strip_prefix("basa ")).unwrap_or(&t);
+    match t {
+        "en" | "english" | "inggris" | "inggeris" => Some("en"),
+        "id" | "indonesia" | "indonesian" => Some("id"),
+        "ms" | "malay" | "melayu" | "malaysia" => Some("ms"),
+        "jv" | "javanese" | "jawa" | "jowo" => Some("jv"),
+        _ => None,
+    }
+}
+
+/// Words typical of each language; words shared by Indonesian and Malay appear in both.
+const LANG_WORDS: [(&str, &[&str]); 4] = [
+    ("id", &["yang", "saya", "kamu", "sudah", "tidak", "nggak", "gak", "mau", "bisa", "kirim", "kirimkan", "saldo", "berapa",
+             "tolong", "untuk", "dengan", "ke", "gimana", "bagaimana", "dong", "aja", "sekarang", "belum", "apa", "ini", "akun"]),
+    ("ms", &["saya", "awak", "anda", "tidak", "tak", "nak", "hantar", "baki", "berapa", "boleh", "sila", "mahu", "sahaja",
+             "kepada", "akaun", "wang", "macam", "sekarang", "belum", "apa", "ini", "untuk"]),
+    ("jv", &["aku", "kowe", "sampeyan", "panjenengan", "opo", "piye", "ora", "wis", "arep", "ngirim", "kirimno", "duwit",
+             "dhuwit", "saiki", "nuwun", "nggih", "mboten", "pinten", "iki", "kuwi", "karo", "tulung", "menyang"]),
+    ("en", &["the", "my", "to", "send", "what", "how", "please", "balance", "you", "and", "is", "show", "can", "me",
+             "transfer", "much", "for", "of", "pay"]),
+];
+
+/// English verbs asking for a reply language ("speak English", "reply in Malay", "switch to English").
+const LANG_REQUEST_VERBS: [&str; 7] = ["speak", "use", "reply", "answer", "respond", "write", "switch"];
+/// Words asking for a language named after "bahasa"/"basa" ("pakai bahasa jawa", "nganggo basa jawa").
+const BAHASA_REQUEST_WORDS: [&str; 7] = ["pakai", "pake", "gunakan", "guna", "dalam", "nganggo", "ganti"];
+
+/// Language explicitly asked for in `words`, if any.
+fn requested_lang(words: &[&str]) -> Option<&'static str> {
+    // a country, code or other word after "in" names no language: "my friend in malaysia", "in id 42"
+    let name = |w: &str| match w {
+        "indonesia" | "malaysia" | "id" | "en" => None,
+        _ => normalize_lang(w),
+    };
+    let at = |i: usize| words.get(i).copied().unwrap_or_default();
+    (0..words.len()).find_map(|i| {
+        let w = at(i);
+        if (BAHASA_REQUEST_WORDS.contains(&w) || LANG_REQUEST_VERBS.contains(&w)) && matches!(at(i + 1), "bahasa" | "basa") {
+            normalize_lang(at(i + 2))
+        } else if LANG_REQUEST_VERBS.contains(&w) {
+            name(if matches!(at(i + 1), "in" | "to") { at(i + 2) } else { at(i + 1) })
+        } else if w == "in" && at(i + 2) == "please" {
+            name(at(i + 1))
+        } else {
+            None
+        }
+    })
 }
 
+/// Guess the language of `text`; `None` when no language clearly wins ("10", "ok", "saldo?").
+///
+/// A language the text asks for ("in English please", "speak Malay", "pakai bahasa jawa") wins; a
+/// language or country name alone ("teman di malaysia") does not. Otherwise the language with most
+/// typical words wins if it has at least two and strictly more than any other.
+pub fn detect_lang(text: &str) -> Option<&'static str> {
+    let lc = text.to_lowercase();
+    let words: Vec<&str> = lc.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+    if let Some(lang) = requested_lang(&words) {
+        return Some(lang);
+    }
+    let mut scores: Vec<(usize, &'static str)> = LANGUAGES.iter().map(|code| {
+        let list = LANG_WORDS.iter().find(|(c, _)| c == code).map(|(_, l)| *l).unwrap_or_default();
+        (words.iter().filter(|w| list.contains(w)).count(), *code)
+    }).collect();
+    scores.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    match scores.as_slice() {
+        [(best, code), (second, _), ..] if *best >= 2 && best > second => Some(code),
+        _ => None,
+    }
+}
+
+/// Language of the most recent user message with recognizable words, defaulting to English.
+pub fn detect_lang_last_user(msgs: &[ChatMessage]) -> &'static str {
+    msgs.iter().rev()
+        .find_map(|m| match m { ChatMessage::User { content } => detect_lang(content), _ => None })
+        .unwrap_or("en")
+}
+
+const LANG_GUARDS: [(&str, &str); 4] = [
+    ("en", "LANGUAGE: Answer in ENGLISH only. Do not mix Indonesian."),
+    ("id", "BAHASA: Jawab dalam BAHASA INDONESIA saja. Jangan campur Inggris."),
+    ("ms", "BAHASA: Jawab dalam BAHASA MELAYU (Malaysia) sahaja. Jangan campur bahasa lain."),
+    ("jv", "BASA: Wangsulana nganggo BASA JAWA wae. Aja dicampur basa liya."),
+];
+
 /// Construct a language guard system message for the given language code.
 pub fn lang_guard(lang: &str) -> ChatMessage {
-    let s = match lang {
-        "id" => "BAHASA: Jawab dalam BAHASA INDONESIA saja. Jangan campur Inggris.",
-        _    => "LANGUAGE: Answer in ENGLISH only. Do not mix Indonesian.",
-    };
+    let s = LANG_GUARDS.iter().find(|(c, _)| *c == lang).unwrap_or(&LANG_GUARDS[0]).1;
     ChatMessage::System { content: s.to_string() }
 }
 
+/// Put the guard for `lang` right after the system prompt, replacing any earlier guard.
+pub fn set_lang_guard(convo: &mut Vec<ChatMessage>, lang: &str) {
+    let guard = lang_guard(lang);
+    let is_guard = |m: &ChatMessage| matches!(m, ChatMessage::System { content } if LANG_GUARDS.iter().any(|(_, g)| g == content));
+    match convo.iter().position(is_guard) {
+        Some(i) => convo[i] = guard,
+        None => convo.insert(1.min(convo.len()), guard),
+    }
+}
+
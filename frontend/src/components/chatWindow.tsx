@@ -5,6 +5,7 @@ import {
   idlFactory as backend_idlFactory,
   canisterId as backend_canisterId,
 } from '../../../src/declarations/backend';
+import type { ChatResponse } from '../../../src/declarations/backend/backend.did';
 
 // Define message types
 type SystemMessage = { system: { content: string } };
@@ -20,6 +21,8 @@ export default function ChatWindow() {
   ]);
   const [inputValue, setInputValue] = useState('');
   const [isLoading, setIsLoading] = useState(false);
+  // plan awaiting confirmation; shows the confirm/cancel buttons
+  const [pendingPlan, setPendingPlan] = useState<{ summary: string; checksum: string } | null>(null);
   const chatBoxRef = useRef<HTMLDivElement>(null);
 
   const formatDate = (date: Date) => {
@@ -28,11 +31,84 @@ export default function ChatWindow() {
     return `${h.slice(-2)}:${m.slice(-2)}`;
   };
 
-  const askAgent = async (messages: ChatMessage[]) => {
+  const SESSION_KEY = 'cofi-session-id';
+  const sessionId = useRef<bigint | null>(null);
+
+  const newSession = async () => {
+    const id = await backend.start_session();
+    sessionId.current = id;
+    localStorage.setItem(SESSION_KEY, id.toString());
+    return id;
+  };
+
+  // resume the stored session, or start one
+  useEffect(() => {
+    const stored = localStorage.getItem(SESSION_KEY);
+    if (!stored) {
+      newSession().catch(console.log);
+      return;
+    }
+    backend.get_session(BigInt(stored)).then((res) => {
+      if ('Err' in res) {
+        newSession().catch(console.log);
+        return;
+      }
+      sessionId.current = res.Ok.id;
+      const restored: ChatMessage[] = [];
+      for (const m of res.Ok.messages) {
+        if ('user' in m) restored.push({ user: { content: m.user.content } });
+        else if ('assistant' in m && m.assistant.content.length > 0 && m.assistant.tool_calls.length === 0) {
+          restored.push({ system: { content: m.assistant.content[0]! } });
+        }
+      }
+      setChat((prevChat) => [prevChat[0], ...restored]);
+    }).catch(console.log);
+  }, []);
+
+  const showStatus = (res: ChatResponse) => {
+    if ('AwaitingConfirmation' in res.status && res.pending_plan.length > 0) {
+      const plan = res.pending_plan[0]!;
+      const p = 'Transfer' in plan ? plan.Transfer : 'Batch' in plan ? plan.Batch : plan.Swap;
+      setPendingPlan({ summary: p.human_readable, checksum: p.checksum });
+    } else {
+      setPendingPlan(null);
+    }
+  };
+
+  // execute exactly the plan shown, by its checksum
+  const confirmPlan = async () => {
+    if (!pendingPlan) return;
+    const { summary, checksum } = pendingPlan;
+    setPendingPlan(null);
+    setIsLoading(true);
+    setChat((prevChat) => [...prevChat, { user: { content: `confirm ${checksum}` } }]);
     try {
-    // const p = await window.ic?.plug?.getPrincipal();
-    //   const actor = await window.ic!.plug!.createA. 
-      const response = await backend.copilot_chat(messages);
+      const res = await backend.confirm(checksum);
+      const content = 'Ok' in res ? `Done: ${summary}` : `Not executed: ${res.Err}`;
+      setChat((prevChat) => [...prevChat, { system: { content } }]);
+    } catch (e) {
+      console.log(e);
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
+  const cancelPlan = async () => {
+    setPendingPlan(null);
+    await backend.cancel_plan().catch(console.log);
+  };
+
+  const askAgent = async (text: string) => {
+    try {
+      const id = sessionId.current ?? await newSession();
+      // [] = use the model configured on the canister
+      let res = await backend.copilot_chat(id, text, []);
+      if ('Err' in res && /session/.test(res.Err)) {
+        // expired or evicted: continue in a fresh session
+        res = await backend.copilot_chat(await newSession(), text, []);
+      }
+      const response = 'Ok' in res ? res.Ok.reply : res.Err;
+      if ('Ok' in res) showStatus(res.Ok);
       setChat((prevChat) => {
         const newChat = [...prevChat];
         newChat.pop(); // Remove the "Thinking..." message
@@ -56,12 +132,9 @@ export default function ChatWindow() {
     }
   };
 
-  const handleSubmit = (e: React.FormEvent) => {
-    e.preventDefault();
-    if (!inputValue.trim() || isLoading) return;
-
+  const send = (text: string) => {
     const userMessage: UserMessage = {
-      user: { content: inputValue }
+      user: { content: text }
     };
     const thinkingMessage: SystemMessage = {
       system: { content: 'Thinking ...' }
@@ -71,8 +144,13 @@ export default function ChatWindow() {
     setInputValue('');
     setIsLoading(true);
 
-    const messagesToSend = chat.slice(1).concat([userMessage]);
-    askAgent(messagesToSend);
+    askAgent(text);
+  };
+
+  const handleSubmit = (e: React.FormEvent) => {
+    e.preventDefault();
+    if (!inputValue.trim() || isLoading) return;
+    send(inputValue);
   };
 
   useEffect(() => {
@@ -98,6 +176,26 @@ export default function ChatWindow() {
         })}
       </div>
 
+      {pendingPlan && (
+        <div className="px-4 py-3 border-t border-zinc-700 bg-zinc-900 flex items-center gap-2">
+          <span className="flex-1 text-sm text-zinc-300">{pendingPlan.summary}</span>
+          <button
+            className="bg-green-600 hover:bg-green-500 px-3 py-1 rounded text-white"
+            onClick={confirmPlan}
+            disabled={isLoading}
+          >
+            Confirm
+          </button>
+          <button
+            className="bg-zinc-700 hover:bg-zinc-600 px-3 py-1 rounded text-white"
+            onClick={cancelPlan}
+            disabled={isLoading}
+          >
+            Cancel
+          </button>
+        </div>
+      )}
+
       <div className="p-4 border-t border-zinc-700 bg-zinc-900">
         <form className="flex items-center gap-2" onSubmit={handleSubmit}>
           <input
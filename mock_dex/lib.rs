@@ -0,0 +1,142 @@
+use candid::{CandidType, Nat, Principal};
+use ic_cdk::{query, update};
+use icrc_ledger_types::icrc1::account::Account;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// DEX fee in basis points taken from the input.
+const FEE_BPS: u32 = 30;
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct QuoteArgs {
+    pub token_in: Principal,
+    pub token_out: Principal,
+    pub amount_in: Nat,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct Quote {
+    pub amount_out: Nat,
+    pub price_impact_bps: u32,
+    pub fee: Nat,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct SwapArgs {
+    pub token_in: Principal,
+    pub token_out: Principal,
+    pub amount_in: Nat,
+    pub min_amount_out: Nat,
+    pub deposit_subaccount: [u8; 32],
+    pub recipient: Account,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct SwapResult {
+    pub amount_out: Nat,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct WithdrawArgs {
+    pub token: Principal,
+    pub amount: Nat,
+    pub deposit_subaccount: [u8; 32],
+    pub recipient: Account,
+}
+
+thread_local! {
+    /// Reserves keyed by (token_in, token_out).
+    static POOLS: RefCell<BTreeMap<(Principal, Principal), (Nat, Nat)>> = const { RefCell::new(BTreeMap::new()) };
+    /// Executed swaps, for assertions in tests.
+    static SWAPS: RefCell<Vec<SwapArgs>> = const { RefCell::new(Vec::new()) };
+    /// Withdrawn deposits, for assertions in tests.
+    static WITHDRAWALS: RefCell<Vec<WithdrawArgs>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Whether the deposit in `sub` was already swapped or withdrawn.
+fn deposit_used(sub: &[u8; 32]) -> bool {
+    SWAPS.with(|s| s.borrow().iter().any(|a| &a.deposit_subaccount == sub))
+        || WITHDRAWALS.with(|w| w.borrow().iter().any(|a| &a.deposit_subaccount == sub))
+}
+
+/// Constant-product quote for `amount_in` against reserves `(r_in, r_out)`.
+pub fn compute_quote(r_in: &Nat, r_out: &Nat, amount_in: &Nat) -> Quote {
+    let fee = amount_in.clone() * Nat::from(FEE_BPS) / Nat::from(10_000u32);
+    let net_in = amount_in.clone() - fee.clone();
+    let amount_out = r_out.clone() * net_in.clone() / (r_in.clone() + net_in);
+    let spot_out = r_out.clone() * amount_in.clone() / r_in.clone();
+    let price_impact_bps = if spot_out == 0u32 {
+        0
+    } else {
+        let impact = (spot_out.clone() - amount_out.clone().min(spot_out.clone())) * Nat::from(10_000u32) / spot_out;
+        u32::try_from(impact.0).unwrap_or(10_000)
+    };
+    Quote { amount_out, price_impact_bps, fee }
+}
+
+fn pool(token_in: Principal, token_out: Principal) -> Result<(Nat, Nat), String> {
+    POOLS.with(|p| p.borrow().get(&(token_in, token_out)).cloned()).ok_or_else(|| "no pool for pair".to_string())
+}
+
+/// Seed a pool with reserves in both directions.
+#[update]
+pub fn set_pool(token_a: Principal, token_b: Principal, reserve_a: Nat, reserve_b: Nat) {
+    POOLS.with(|p| {
+        let mut p = p.borrow_mut();
+        p.insert((token_a, token_b), (reserve_a.clone(), reserve_b.clone()));
+        p.insert((token_b, token_a), (reserve_b, reserve_a));
+    });
+}
+
+/// Quote a swap.
+#[query]
+pub fn quote(args: QuoteArgs) -> Result<Quote, String> {
+    let (r_in, r_out) = pool(args.token_in, args.token_out)?;
+    if r_in == 0u32 { return Err("empty pool".into()); }
+    Ok(compute_quote(&r_in, &r_out, &args.amount_in))
+}
+
+/// Swap against the pool; fails below `min_amount_out`. Deposits and payouts are not checked on any ledger.
+#[update]
+pub fn swap(args: SwapArgs) -> Result<SwapResult, String> {
+    let (r_in, r_out) = pool(args.token_in, args.token_out)?;
+    if r_in == 0u32 { return Err("empty pool".into()); }
+    if deposit_used(&args.deposit_subaccount) { return Err("deposit already used".into()); }
+    let q = compute_quote(&r_in, &r_out, &args.amount_in);
+    if q.amount_out < args.min_amount_out {
+        return Err(format!("slippage: output {} below minimum {}", q.amount_out, args.min_amount_out));
+    }
+    let new_in = r_in + args.amount_in.clone();
+    let new_out = r_out - q.amount_out.clone();
+    POOLS.with(|p| {
+        let mut p = p.borrow_mut();
+        p.insert((args.token_in, args.token_out), (new_in.clone(), new_out.clone()));
+        p.insert((args.token_out, args.token_in), (new_out, new_in));
+    });
+    SWAPS.with(|s| s.borrow_mut().push(args));
+    Ok(SwapResult { amount_out: q.amount_out })
+}
+
+/// Return an unswapped deposit; fails once the deposit was swapped or withdrawn. No ledger transfer is made.
+#[update]
+pub fn withdraw(args: WithdrawArgs) -> Result<Nat, String> {
+    if deposit_used(&args.deposit_subaccount) { return Err("deposit already used".into()); }
+    let amount = args.amount.clone();
+    WITHDRAWALS.with(|w| w.borrow_mut().push(args));
+    Ok(amount)
+}
+
+/// Number of executed swaps.
+#[query]
+pub fn swap_count() -> u64 {
+    SWAPS.with(|s| s.borrow().len() as u64)
+}
+
+/// Number of withdrawn deposits.
+#[query]
+pub fn withdraw_count() -> u64 {
+    WITHDRAWALS.with(|w| w.borrow().len() as u64)
+}
+
+ic_cdk::export_candid!();
@@ -0,0 +1,38 @@
+use candid::CandidType;
+use ic_cdk::{query, update};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct PriceRequest {
+    pub symbol: String,
+    pub currency: String,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct Price {
+    pub rate_e8: u64,
+    pub timestamp: u64,
+}
+
+thread_local! {
+    /// Rates keyed by (symbol, currency).
+    static RATES: RefCell<BTreeMap<(String, String), u64>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Set the rate of `symbol` in `currency`, scaled by 1e8.
+#[update]
+pub fn set_price(symbol: String, currency: String, rate_e8: u64) {
+    RATES.with(|r| { r.borrow_mut().insert((symbol, currency), rate_e8); });
+}
+
+/// Current price, stamped with the canister time.
+#[query]
+pub fn get_price(req: PriceRequest) -> Result<Price, String> {
+    RATES.with(|r| r.borrow().get(&(req.symbol.clone(), req.currency.clone())).copied())
+        .map(|rate_e8| Price { rate_e8, timestamp: ic_cdk::api::time() })
+        .ok_or_else(|| format!("no price for {}/{}", req.symbol, req.currency))
+}
+
+ic_cdk::export_candid!();
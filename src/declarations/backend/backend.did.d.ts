@@ -2,36 +2,432 @@ import type { Principal } from '@dfinity/principal';
 import type { ActorMethod } from '@dfinity/agent';
 import type { IDL } from '@dfinity/candid';
 
+export interface Account {
+  'owner' : Principal,
+  'subaccount' : [] | [Uint8Array | number[]],
+}
+export interface AccountTx {
+  'id' : bigint,
+  'direction' : string,
+  'kind' : string,
+  'memo_text' : [] | [string],
+  'counterparty' : [] | [Account],
+  'amount_dec' : string,
+  'timestamp' : bigint,
+  'amount' : bigint,
+}
+export interface ApprovalPrepArgs {
+  'budget_dec' : [] | [string],
+  'checksum' : [] | [string],
+  'valid_days' : [] | [number],
+  'symbol' : [] | [string],
+}
+export interface ApprovalRequest {
+  'rejected_by' : [] | [Principal],
+  'plan' : TransferPlan,
+  'approved_by' : Array<Principal>,
+  'cosigners' : Array<Principal>,
+  'checksum' : string,
+  'required' : number,
+  'rejection_reason' : [] | [string],
+  'expires_at' : [] | [bigint],
+}
+export interface ApproveArgs {
+  'fee' : [] | [bigint],
+  'memo' : [] | [Uint8Array | number[]],
+  'from_subaccount' : [] | [Uint8Array | number[]],
+  'created_at_time' : [] | [bigint],
+  'amount' : bigint,
+  'expected_allowance' : [] | [bigint],
+  'expires_at' : [] | [bigint],
+  'spender' : Account,
+}
 export interface AssistantMessage {
   'content' : [] | [string],
   'tool_calls' : Array<ToolCall>,
 }
+export interface BatchPlan {
+  'total' : bigint,
+  'human_readable' : string,
+  'owner' : Principal,
+  'legs' : Array<TransferPlan>,
+  'checksum' : string,
+  'created_at_time' : bigint,
+  'symbol' : string,
+}
 export type ChatMessage = {
     'tool' : { 'content' : string, 'tool_call_id' : string }
   } |
   { 'user' : { 'content' : string } } |
   { 'assistant' : AssistantMessage } |
   { 'system' : { 'content' : string } };
+export interface ChatResponse {
+  'status' : ChatStatus,
+  'tools' : Array<ToolTrace>,
+  'pending_plan' : [] | [PendingPlan],
+  'receipts' : Array<Receipt>,
+  'reply' : string,
+}
+export type ChatStatus = { 'RoundLimit' : null } |
+  { 'Done' : null } |
+  { 'AwaitingConfirmation' : null } |
+  { 'ModelUnavailable' : null };
+export type CheckStatus = { 'Fail' : null } |
+  { 'Pass' : null } |
+  { 'Unknown' : null };
+export interface Config {
+  'model' : LlmModel,
+  'fallback_model' : [] | [LlmModel],
+  'oracle_canister' : [] | [Principal],
+  'index_canisters' : Array<[string, Principal]>,
+  'dex_canister' : [] | [Principal],
+}
+export interface CosignConfig {
+  'cosigners' : Array<Principal>,
+  'required' : number,
+  'thresholds' : Array<TokenThreshold>,
+}
+export interface Escrow {
+  'id' : bigint,
+  'status' : EscrowStatus,
+  'arbiter' : [] | [Principal],
+  'memo' : [] | [string],
+  'subaccount' : Uint8Array | number[],
+  'recipient' : Principal,
+  'created_at' : bigint,
+  'ledger' : Principal,
+  'deadline_at' : bigint,
+  'payer' : Principal,
+  'amount' : bigint,
+  'recipient_sub' : [] | [Uint8Array | number[]],
+  'settle_block_index' : [] | [bigint],
+  'fund_block_index' : [] | [bigint],
+  'symbol' : string,
+}
+export type EscrowStatus = { 'Refunded' : null } |
+  { 'Released' : null } |
+  { 'Funded' : null } |
+  { 'AwaitingFunds' : null };
+export type Frequency = { 'Weekly' : null } |
+  { 'Daily' : null } |
+  { 'Monthly' : null };
 export interface FunctionCall {
   'name' : string,
   'arguments' : Array<ToolCallArgument>,
 }
-export type Result = { 'Ok' : null } |
+export interface HistoryArgs {
+  'direction' : [] | [string],
+  'max_results' : [] | [number],
+  'start' : [] | [string],
+  'symbol' : [] | [string],
+}
+export interface InitArgs {
+  'model' : [] | [LlmModel],
+  'fallback_model' : [] | [LlmModel],
+}
+export interface Invoice {
+  'id' : bigint,
+  'status' : InvoiceStatus,
+  'creator' : Principal,
+  'paid_block_index' : [] | [bigint],
+  'memo' : [] | [string],
+  'created_at' : bigint,
+  'ledger' : Principal,
+  'due_at' : [] | [bigint],
+  'paid_at' : [] | [bigint],
+  'paid_by' : [] | [Principal],
+  'payer' : [] | [Principal],
+  'amount' : bigint,
+  'symbol' : string,
+}
+export interface InvoiceArgs {
+  'memo' : [] | [string],
+  'amount_dec' : string,
+  'due_date' : [] | [string],
+  'payer' : [] | [string],
+  'symbol' : [] | [string],
+}
+export type InvoiceStatus = { 'Open' : null } |
+  { 'Paid' : null } |
+  { 'Cancelled' : null };
+export type LlmModel = { 'Llama4Scout' : null } |
+  { 'Qwen3_32B' : null } |
+  { 'Llama3_1_8B' : null };
+export interface ModelTrace {
+  'model' : [] | [LlmModel],
+  'fallback' : boolean,
+  'error' : [] | [string],
+  'round' : number,
+}
+export type PendingPlan = { 'Swap' : SwapPlan } |
+  { 'Batch' : BatchPlan } |
+  { 'Transfer' : TransferPlan };
+export interface Portfolio {
+  'human_readable' : string,
+  'owner' : Principal,
+  'tokens' : Array<TokenBalance>,
+}
+export interface PreparedApproval {
+  'human_readable' : string,
+  'args' : ApproveArgs,
+  'sufficient' : boolean,
+  'ledger' : Principal,
+  'symbol' : string,
+  'current_allowance' : bigint,
+}
+export interface QuietHours {
+  'utc_offset_minutes' : number,
+  'end_hour' : number,
+  'start_hour' : number,
+}
+export interface Receipt {
+  'to_principal' : Principal,
+  'executed_at' : bigint,
+  'block_index' : bigint,
+  'invoice_id' : [] | [bigint],
+  'owner' : Principal,
+  'memo' : [] | [Uint8Array | number[]],
+  'swap' : [] | [SwapFill],
+  'memo_text' : [] | [string],
+  'to_sub' : [] | [Uint8Array | number[]],
+  'ledger' : Principal,
+  'checksum' : string,
+  'fiat_value' : [] | [string],
+  'amount' : bigint,
+  'symbol' : string,
+}
+export interface RecipientCap {
+  'daily_cap' : bigint,
+  'recipient' : Principal,
+  'symbol' : string,
+}
+export interface RecurringArgs {
+  'to' : string,
+  'memo' : [] | [string],
+  'count' : [] | [number],
+  'end_date' : [] | [string],
+  'start_date' : [] | [string],
+  'amount_dec' : string,
+  'frequency' : string,
+  'symbol' : [] | [string],
+}
+export type RecurringStatus = { 'Paused' : null } |
+  { 'Active' : null } |
+  { 'Cancelled' : null } |
+  { 'Completed' : null };
+export interface RecurringTransfer {
+  'id' : bigint,
+  'last_error' : [] | [string],
+  'status' : RecurringStatus,
+  'last_block_index' : [] | [bigint],
+  'owner' : Principal,
+  'max_count' : [] | [number],
+  'plan' : TransferPlan,
+  'slot' : number,
+  'anchor_at' : bigint,
+  'failure_reported' : boolean,
+  'next_run_at' : bigint,
+  'frequency' : Frequency,
+  'executed_count' : number,
+  'end_at' : [] | [bigint],
+}
+export type Result = { 'Ok' : ApprovalRequest } |
+  { 'Err' : string };
+export type Result_1 = { 'Ok' : Invoice } |
+  { 'Err' : string };
+export type Result_10 = { 'Ok' : null } |
+  { 'Err' : string };
+export type Result_11 = { 'Ok' : Array<SimulationReport> } |
+  { 'Err' : string };
+export type Result_12 = { 'Ok' : SpendingSummary } |
+  { 'Err' : string };
+export type Result_13 = { 'Ok' : SavedAccount } |
+  { 'Err' : string };
+export type Result_2 = { 'Ok' : RecurringTransfer } |
+  { 'Err' : string };
+export type Result_3 = { 'Ok' : string } |
+  { 'Err' : string };
+export type Result_4 = { 'Ok' : ChatResponse } |
+  { 'Err' : string };
+export type Result_5 = { 'Ok' : TxPage } |
+  { 'Err' : string };
+export type Result_6 = { 'Ok' : [] | [string] } |
+  { 'Err' : string };
+export type Result_7 = { 'Ok' : Session } |
+  { 'Err' : string };
+export type Result_8 = { 'Ok' : PreparedApproval } |
+  { 'Err' : string };
+export type Result_9 = { 'Ok' : Escrow } |
   { 'Err' : string };
 export interface SavedAccount {
   'alias' : string,
   'owner' : Principal,
+  'tags' : Array<string>,
   'subaccount' : [] | [Uint8Array | number[]],
 }
+export interface Session {
+  'id' : bigint,
+  'updated_at' : bigint,
+  'pending_in_last_turn' : boolean,
+  'messages' : Array<ChatMessage>,
+  'owner' : Principal,
+  'created_at' : bigint,
+  'pending_checksum' : [] | [string],
+}
+export interface SimCheck {
+  'status' : CheckStatus,
+  'name' : string,
+  'detail' : string,
+}
+export interface SimulationReport {
+  'ok' : boolean,
+  'checksum' : string,
+  'checks' : Array<SimCheck>,
+}
+export interface SpendingPolicy {
+  'token_limits' : Array<TokenLimit>,
+  'allowed_recipients_only' : boolean,
+  'quiet_hours' : [] | [QuietHours],
+  'allowed_recipients' : Array<Principal>,
+  'recipient_caps' : Array<RecipientCap>,
+}
+export interface SpendingSummary {
+  'to' : [] | [bigint],
+  'by_tag' : Array<SummaryGroup>,
+  'human_readable' : string,
+  'from' : [] | [bigint],
+  'by_period' : Array<SummaryGroup>,
+  'top_recipients' : Array<SummaryGroup>,
+  'totals' : Array<SummaryGroup>,
+}
+export interface SummaryArgs {
+  'to' : [] | [string],
+  'tag' : [] | [string],
+  'top' : [] | [number],
+  'period' : [] | [string],
+  'from' : [] | [string],
+  'recipient' : [] | [string],
+  'symbol' : [] | [string],
+}
+export interface SummaryGroup {
+  'key' : string,
+  'total' : bigint,
+  'count' : number,
+  'total_dec' : string,
+  'symbol' : string,
+}
+export interface SwapFill {
+  'dex' : Principal,
+  'symbol_out' : string,
+  'amount_out' : bigint,
+}
+export interface SwapPlan {
+  'dex' : Principal,
+  'price_impact_bps' : number,
+  'symbol_out' : string,
+  'human_readable' : string,
+  'owner' : Principal,
+  'min_out' : bigint,
+  'deposit' : TransferPlan,
+  'slippage_bps' : number,
+  'checksum' : string,
+  'ledger_out' : Principal,
+  'expected_out' : bigint,
+}
+export interface TokenBalance {
+  'decimals' : number,
+  'balance' : [] | [bigint],
+  'errors' : Array<string>,
+  'ledger' : [] | [Principal],
+  'balance_dec' : [] | [string],
+  'allowance' : [] | [bigint],
+  'allowance_expires_at' : [] | [bigint],
+  'fiat_value' : [] | [string],
+  'symbol' : string,
+}
+export interface TokenLimit {
+  'daily_cap' : [] | [bigint],
+  'weekly_cap' : [] | [bigint],
+  'symbol' : string,
+  'per_tx_max' : [] | [bigint],
+}
+export interface TokenThreshold { 'amount' : bigint, 'symbol' : string }
 export interface ToolCall { 'id' : string, 'function' : FunctionCall }
 export interface ToolCallArgument { 'value' : string, 'name' : string }
+export interface ToolTrace {
+  'status' : string,
+  'code' : [] | [string],
+  'name' : string,
+}
+export interface TransferPlan {
+  'to_principal' : Principal,
+  'invoice_id' : [] | [bigint],
+  'human_readable' : string,
+  'from_owner' : [] | [Principal],
+  'supersedes' : [] | [string],
+  'memo' : [] | [Uint8Array | number[]],
+  'to_sub' : [] | [Uint8Array | number[]],
+  'ledger' : Principal,
+  'checksum' : string,
+  'from_sub' : [] | [Uint8Array | number[]],
+  'created_at_time' : bigint,
+  'escrow_id' : [] | [bigint],
+  'amount' : bigint,
+  'symbol' : string,
+  'approvals_required' : number,
+}
+export interface TxPage {
+  'next_start' : [] | [string],
+  'balance_dec' : string,
+  'transactions' : Array<AccountTx>,
+  'symbol' : string,
+}
 export interface _SERVICE {
-  'copilot_chat' : ActorMethod<[Array<ChatMessage>], string>,
+  'approve_plan' : ActorMethod<[string], Result>,
+  'cancel_invoice' : ActorMethod<[bigint], Result_1>,
+  'cancel_plan' : ActorMethod<[], [] | [string]>,
+  'cancel_recurring' : ActorMethod<[bigint], Result_2>,
+  'confirm' : ActorMethod<[string], Result_3>,
+  'copilot_chat' : ActorMethod<[bigint, string, [] | [LlmModel]], Result_4>,
+  'create_invoice' : ActorMethod<[InvoiceArgs], Result_1>,
+  'get_account_transactions' : ActorMethod<[HistoryArgs], Result_5>,
+  'get_chat_trace' : ActorMethod<[], Array<ModelTrace>>,
+  'get_config' : ActorMethod<[], Config>,
+  'get_cosign_config' : ActorMethod<[], CosignConfig>,
+  'get_fiat_value' : ActorMethod<[string, string], Result_6>,
+  'get_invoice' : ActorMethod<[bigint], [] | [Invoice]>,
+  'get_language' : ActorMethod<[], [] | [string]>,
+  'get_policy' : ActorMethod<[], SpendingPolicy>,
+  'get_portfolio' : ActorMethod<[], Portfolio>,
+  'get_session' : ActorMethod<[bigint], Result_7>,
   'list_accounts' : ActorMethod<[], Array<SavedAccount>>,
+  'list_escrows' : ActorMethod<[], Array<Escrow>>,
+  'list_invoices' : ActorMethod<[], Array<Invoice>>,
+  'list_receipts' : ActorMethod<[], Array<Receipt>>,
+  'list_recurring' : ActorMethod<[], Array<RecurringTransfer>>,
+  'pending_approvals' : ActorMethod<[], Array<ApprovalRequest>>,
+  'plan_history' : ActorMethod<[string], Array<TransferPlan>>,
+  'prepare_approval' : ActorMethod<[ApprovalPrepArgs], Result_8>,
+  'refund_escrow' : ActorMethod<[bigint], Result_9>,
+  'reject_plan' : ActorMethod<[string, [] | [string]], Result>,
+  'release_escrow' : ActorMethod<[bigint], Result_9>,
+  'resume_recurring' : ActorMethod<[bigint], Result_2>,
   'save_account' : ActorMethod<
     [string, string, [] | [Uint8Array | number[]]],
-    Result
+    Result_10
   >,
+  'schedule_recurring' : ActorMethod<[RecurringArgs], Result_2>,
+  'set_cosign_config' : ActorMethod<[CosignConfig], Result_10>,
+  'set_dex_canister' : ActorMethod<[[] | [Principal]], Result_10>,
+  'set_index_canister' : ActorMethod<[string, [] | [Principal]], Result_10>,
+  'set_language' : ActorMethod<[[] | [string]], Result_6>,
+  'set_model' : ActorMethod<[LlmModel, [] | [LlmModel]], Result_10>,
+  'set_oracle_canister' : ActorMethod<[[] | [Principal]], Result_10>,
+  'set_policy' : ActorMethod<[SpendingPolicy], Result_10>,
+  'simulate_transfer' : ActorMethod<[string], Result_11>,
+  'spending_summary' : ActorMethod<[SummaryArgs], Result_12>,
+  'start_session' : ActorMethod<[], bigint>,
+  'tag_account' : ActorMethod<[string, Array<string>], Result_13>,
 }
 export declare const idlFactory: IDL.InterfaceFactory;
 export declare const init: (args: { IDL: typeof IDL }) => IDL.Type[];
@@ -1,4 +1,254 @@
 export const idlFactory = ({ IDL }) => {
+  const LlmModel = IDL.Variant({
+    'Llama4Scout' : IDL.Null,
+    'Qwen3_32B' : IDL.Null,
+    'Llama3_1_8B' : IDL.Null,
+  });
+  const InitArgs = IDL.Record({
+    'model' : IDL.Opt(LlmModel),
+    'fallback_model' : IDL.Opt(LlmModel),
+  });
+  const TransferPlan = IDL.Record({
+    'to_principal' : IDL.Principal,
+    'invoice_id' : IDL.Opt(IDL.Nat64),
+    'human_readable' : IDL.Text,
+    'from_owner' : IDL.Opt(IDL.Principal),
+    'supersedes' : IDL.Opt(IDL.Text),
+    'memo' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'to_sub' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'ledger' : IDL.Principal,
+    'checksum' : IDL.Text,
+    'from_sub' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'created_at_time' : IDL.Nat64,
+    'escrow_id' : IDL.Opt(IDL.Nat64),
+    'amount' : IDL.Nat,
+    'symbol' : IDL.Text,
+    'approvals_required' : IDL.Nat8,
+  });
+  const ApprovalRequest = IDL.Record({
+    'rejected_by' : IDL.Opt(IDL.Principal),
+    'plan' : TransferPlan,
+    'approved_by' : IDL.Vec(IDL.Principal),
+    'cosigners' : IDL.Vec(IDL.Principal),
+    'checksum' : IDL.Text,
+    'required' : IDL.Nat8,
+    'rejection_reason' : IDL.Opt(IDL.Text),
+    'expires_at' : IDL.Opt(IDL.Nat64),
+  });
+  const Result = IDL.Variant({ 'Ok' : ApprovalRequest, 'Err' : IDL.Text });
+  const InvoiceStatus = IDL.Variant({
+    'Open' : IDL.Null,
+    'Paid' : IDL.Null,
+    'Cancelled' : IDL.Null,
+  });
+  const Invoice = IDL.Record({
+    'id' : IDL.Nat64,
+    'status' : InvoiceStatus,
+    'creator' : IDL.Principal,
+    'paid_block_index' : IDL.Opt(IDL.Nat),
+    'memo' : IDL.Opt(IDL.Text),
+    'created_at' : IDL.Nat64,
+    'ledger' : IDL.Principal,
+    'due_at' : IDL.Opt(IDL.Nat64),
+    'paid_at' : IDL.Opt(IDL.Nat64),
+    'paid_by' : IDL.Opt(IDL.Principal),
+    'payer' : IDL.Opt(IDL.Principal),
+    'amount' : IDL.Nat,
+    'symbol' : IDL.Text,
+  });
+  const Result_1 = IDL.Variant({ 'Ok' : Invoice, 'Err' : IDL.Text });
+  const RecurringStatus = IDL.Variant({
+    'Paused' : IDL.Null,
+    'Active' : IDL.Null,
+    'Cancelled' : IDL.Null,
+    'Completed' : IDL.Null,
+  });
+  const Frequency = IDL.Variant({
+    'Weekly' : IDL.Null,
+    'Daily' : IDL.Null,
+    'Monthly' : IDL.Null,
+  });
+  const RecurringTransfer = IDL.Record({
+    'id' : IDL.Nat64,
+    'last_error' : IDL.Opt(IDL.Text),
+    'status' : RecurringStatus,
+    'last_block_index' : IDL.Opt(IDL.Nat),
+    'owner' : IDL.Principal,
+    'max_count' : IDL.Opt(IDL.Nat32),
+    'plan' : TransferPlan,
+    'slot' : IDL.Nat32,
+    'anchor_at' : IDL.Nat64,
+    'failure_reported' : IDL.Bool,
+    'next_run_at' : IDL.Nat64,
+    'frequency' : Frequency,
+    'executed_count' : IDL.Nat32,
+    'end_at' : IDL.Opt(IDL.Nat64),
+  });
+  const Result_2 = IDL.Variant({ 'Ok' : RecurringTransfer, 'Err' : IDL.Text });
+  const Result_3 = IDL.Variant({ 'Ok' : IDL.Text, 'Err' : IDL.Text });
+  const ChatStatus = IDL.Variant({
+    'RoundLimit' : IDL.Null,
+    'Done' : IDL.Null,
+    'AwaitingConfirmation' : IDL.Null,
+    'ModelUnavailable' : IDL.Null,
+  });
+  const ToolTrace = IDL.Record({
+    'status' : IDL.Text,
+    'code' : IDL.Opt(IDL.Text),
+    'name' : IDL.Text,
+  });
+  const SwapPlan = IDL.Record({
+    'dex' : IDL.Principal,
+    'price_impact_bps' : IDL.Nat32,
+    'symbol_out' : IDL.Text,
+    'human_readable' : IDL.Text,
+    'owner' : IDL.Principal,
+    'min_out' : IDL.Nat,
+    'deposit' : TransferPlan,
+    'slippage_bps' : IDL.Nat32,
+    'checksum' : IDL.Text,
+    'ledger_out' : IDL.Principal,
+    'expected_out' : IDL.Nat,
+  });
+  const BatchPlan = IDL.Record({
+    'total' : IDL.Nat,
+    'human_readable' : IDL.Text,
+    'owner' : IDL.Principal,
+    'legs' : IDL.Vec(TransferPlan),
+    'checksum' : IDL.Text,
+    'created_at_time' : IDL.Nat64,
+    'symbol' : IDL.Text,
+  });
+  const PendingPlan = IDL.Variant({
+    'Swap' : SwapPlan,
+    'Batch' : BatchPlan,
+    'Transfer' : TransferPlan,
+  });
+  const SwapFill = IDL.Record({
+    'dex' : IDL.Principal,
+    'symbol_out' : IDL.Text,
+    'amount_out' : IDL.Nat,
+  });
+  const Receipt = IDL.Record({
+    'to_principal' : IDL.Principal,
+    'executed_at' : IDL.Nat64,
+    'block_index' : IDL.Nat,
+    'invoice_id' : IDL.Opt(IDL.Nat64),
+    'owner' : IDL.Principal,
+    'memo' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'swap' : IDL.Opt(SwapFill),
+    'memo_text' : IDL.Opt(IDL.Text),
+    'to_sub' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'ledger' : IDL.Principal,
+    'checksum' : IDL.Text,
+    'fiat_value' : IDL.Opt(IDL.Text),
+    'amount' : IDL.Nat,
+    'symbol' : IDL.Text,
+  });
+  const ChatResponse = IDL.Record({
+    'status' : ChatStatus,
+    'tools' : IDL.Vec(ToolTrace),
+    'pending_plan' : IDL.Opt(PendingPlan),
+    'receipts' : IDL.Vec(Receipt),
+    'reply' : IDL.Text,
+  });
+  const Result_4 = IDL.Variant({ 'Ok' : ChatResponse, 'Err' : IDL.Text });
+  const InvoiceArgs = IDL.Record({
+    'memo' : IDL.Opt(IDL.Text),
+    'amount_dec' : IDL.Text,
+    'due_date' : IDL.Opt(IDL.Text),
+    'payer' : IDL.Opt(IDL.Text),
+    'symbol' : IDL.Opt(IDL.Text),
+  });
+  const HistoryArgs = IDL.Record({
+    'direction' : IDL.Opt(IDL.Text),
+    'max_results' : IDL.Opt(IDL.Nat32),
+    'start' : IDL.Opt(IDL.Text),
+    'symbol' : IDL.Opt(IDL.Text),
+  });
+  const Account = IDL.Record({
+    'owner' : IDL.Principal,
+    'subaccount' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+  });
+  const AccountTx = IDL.Record({
+    'id' : IDL.Nat,
+    'direction' : IDL.Text,
+    'kind' : IDL.Text,
+    'memo_text' : IDL.Opt(IDL.Text),
+    'counterparty' : IDL.Opt(Account),
+    'amount_dec' : IDL.Text,
+    'timestamp' : IDL.Nat64,
+    'amount' : IDL.Nat,
+  });
+  const TxPage = IDL.Record({
+    'next_start' : IDL.Opt(IDL.Text),
+    'balance_dec' : IDL.Text,
+    'transactions' : IDL.Vec(AccountTx),
+    'symbol' : IDL.Text,
+  });
+  const Result_5 = IDL.Variant({ 'Ok' : TxPage, 'Err' : IDL.Text });
+  const ModelTrace = IDL.Record({
+    'model' : IDL.Opt(LlmModel),
+    'fallback' : IDL.Bool,
+    'error' : IDL.Opt(IDL.Text),
+    'round' : IDL.Nat32,
+  });
+  const Config = IDL.Record({
+    'model' : LlmModel,
+    'fallback_model' : IDL.Opt(LlmModel),
+    'oracle_canister' : IDL.Opt(IDL.Principal),
+    'index_canisters' : IDL.Vec(IDL.Tuple(IDL.Text, IDL.Principal)),
+    'dex_canister' : IDL.Opt(IDL.Principal),
+  });
+  const TokenThreshold = IDL.Record({
+    'amount' : IDL.Nat,
+    'symbol' : IDL.Text,
+  });
+  const CosignConfig = IDL.Record({
+    'cosigners' : IDL.Vec(IDL.Principal),
+    'required' : IDL.Nat8,
+    'thresholds' : IDL.Vec(TokenThreshold),
+  });
+  const Result_6 = IDL.Variant({ 'Ok' : IDL.Opt(IDL.Text), 'Err' : IDL.Text });
+  const TokenLimit = IDL.Record({
+    'daily_cap' : IDL.Opt(IDL.Nat),
+    'weekly_cap' : IDL.Opt(IDL.Nat),
+    'symbol' : IDL.Text,
+    'per_tx_max' : IDL.Opt(IDL.Nat),
+  });
+  const QuietHours = IDL.Record({
+    'utc_offset_minutes' : IDL.Int16,
+    'end_hour' : IDL.Nat8,
+    'start_hour' : IDL.Nat8,
+  });
+  const RecipientCap = IDL.Record({
+    'daily_cap' : IDL.Nat,
+    'recipient' : IDL.Principal,
+    'symbol' : IDL.Text,
+  });
+  const SpendingPolicy = IDL.Record({
+    'token_limits' : IDL.Vec(TokenLimit),
+    'allowed_recipients_only' : IDL.Bool,
+    'quiet_hours' : IDL.Opt(QuietHours),
+    'allowed_recipients' : IDL.Vec(IDL.Principal),
+    'recipient_caps' : IDL.Vec(RecipientCap),
+  });
+  const TokenBalance = IDL.Record({
+    'decimals' : IDL.Nat8,
+    'balance' : IDL.Opt(IDL.Nat),
+    'errors' : IDL.Vec(IDL.Text),
+    'ledger' : IDL.Opt(IDL.Principal),
+    'balance_dec' : IDL.Opt(IDL.Text),
+    'allowance' : IDL.Opt(IDL.Nat),
+    'allowance_expires_at' : IDL.Opt(IDL.Nat64),
+    'fiat_value' : IDL.Opt(IDL.Text),
+    'symbol' : IDL.Text,
+  });
+  const Portfolio = IDL.Record({
+    'human_readable' : IDL.Text,
+    'owner' : IDL.Principal,
+    'tokens' : IDL.Vec(TokenBalance),
+  });
   const ToolCallArgument = IDL.Record({
     'value' : IDL.Text,
     'name' : IDL.Text,
@@ -18,20 +268,194 @@ export const idlFactory = ({ IDL }) => {
     'assistant' : AssistantMessage,
     'system' : IDL.Record({ 'content' : IDL.Text }),
   });
+  const Session = IDL.Record({
+    'id' : IDL.Nat64,
+    'updated_at' : IDL.Nat64,
+    'pending_in_last_turn' : IDL.Bool,
+    'messages' : IDL.Vec(ChatMessage),
+    'owner' : IDL.Principal,
+    'created_at' : IDL.Nat64,
+    'pending_checksum' : IDL.Opt(IDL.Text),
+  });
+  const Result_7 = IDL.Variant({ 'Ok' : Session, 'Err' : IDL.Text });
   const SavedAccount = IDL.Record({
     'alias' : IDL.Text,
     'owner' : IDL.Principal,
+    'tags' : IDL.Vec(IDL.Text),
     'subaccount' : IDL.Opt(IDL.Vec(IDL.Nat8)),
   });
-  const Result = IDL.Variant({ 'Ok' : IDL.Null, 'Err' : IDL.Text });
+  const EscrowStatus = IDL.Variant({
+    'Refunded' : IDL.Null,
+    'Released' : IDL.Null,
+    'Funded' : IDL.Null,
+    'AwaitingFunds' : IDL.Null,
+  });
+  const Escrow = IDL.Record({
+    'id' : IDL.Nat64,
+    'status' : EscrowStatus,
+    'arbiter' : IDL.Opt(IDL.Principal),
+    'memo' : IDL.Opt(IDL.Text),
+    'subaccount' : IDL.Vec(IDL.Nat8),
+    'recipient' : IDL.Principal,
+    'created_at' : IDL.Nat64,
+    'ledger' : IDL.Principal,
+    'deadline_at' : IDL.Nat64,
+    'payer' : IDL.Principal,
+    'amount' : IDL.Nat,
+    'recipient_sub' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'settle_block_index' : IDL.Opt(IDL.Nat),
+    'fund_block_index' : IDL.Opt(IDL.Nat),
+    'symbol' : IDL.Text,
+  });
+  const ApprovalPrepArgs = IDL.Record({
+    'budget_dec' : IDL.Opt(IDL.Text),
+    'checksum' : IDL.Opt(IDL.Text),
+    'valid_days' : IDL.Opt(IDL.Nat32),
+    'symbol' : IDL.Opt(IDL.Text),
+  });
+  const ApproveArgs = IDL.Record({
+    'fee' : IDL.Opt(IDL.Nat),
+    'memo' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'from_subaccount' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'created_at_time' : IDL.Opt(IDL.Nat64),
+    'amount' : IDL.Nat,
+    'expected_allowance' : IDL.Opt(IDL.Nat),
+    'expires_at' : IDL.Opt(IDL.Nat64),
+    'spender' : Account,
+  });
+  const PreparedApproval = IDL.Record({
+    'human_readable' : IDL.Text,
+    'args' : ApproveArgs,
+    'sufficient' : IDL.Bool,
+    'ledger' : IDL.Principal,
+    'symbol' : IDL.Text,
+    'current_allowance' : IDL.Nat,
+  });
+  const Result_8 = IDL.Variant({ 'Ok' : PreparedApproval, 'Err' : IDL.Text });
+  const Result_9 = IDL.Variant({ 'Ok' : Escrow, 'Err' : IDL.Text });
+  const Result_10 = IDL.Variant({ 'Ok' : IDL.Null, 'Err' : IDL.Text });
+  const RecurringArgs = IDL.Record({
+    'to' : IDL.Text,
+    'memo' : IDL.Opt(IDL.Text),
+    'count' : IDL.Opt(IDL.Nat32),
+    'end_date' : IDL.Opt(IDL.Text),
+    'start_date' : IDL.Opt(IDL.Text),
+    'amount_dec' : IDL.Text,
+    'frequency' : IDL.Text,
+    'symbol' : IDL.Opt(IDL.Text),
+  });
+  const CheckStatus = IDL.Variant({
+    'Fail' : IDL.Null,
+    'Pass' : IDL.Null,
+    'Unknown' : IDL.Null,
+  });
+  const SimCheck = IDL.Record({
+    'status' : CheckStatus,
+    'name' : IDL.Text,
+    'detail' : IDL.Text,
+  });
+  const SimulationReport = IDL.Record({
+    'ok' : IDL.Bool,
+    'checksum' : IDL.Text,
+    'checks' : IDL.Vec(SimCheck),
+  });
+  const Result_11 = IDL.Variant({
+    'Ok' : IDL.Vec(SimulationReport),
+    'Err' : IDL.Text,
+  });
+  const SummaryArgs = IDL.Record({
+    'to' : IDL.Opt(IDL.Text),
+    'tag' : IDL.Opt(IDL.Text),
+    'top' : IDL.Opt(IDL.Nat32),
+    'period' : IDL.Opt(IDL.Text),
+    'from' : IDL.Opt(IDL.Text),
+    'recipient' : IDL.Opt(IDL.Text),
+    'symbol' : IDL.Opt(IDL.Text),
+  });
+  const SummaryGroup = IDL.Record({
+    'key' : IDL.Text,
+    'total' : IDL.Nat,
+    'count' : IDL.Nat32,
+    'total_dec' : IDL.Text,
+    'symbol' : IDL.Text,
+  });
+  const SpendingSummary = IDL.Record({
+    'to' : IDL.Opt(IDL.Nat64),
+    'by_tag' : IDL.Vec(SummaryGroup),
+    'human_readable' : IDL.Text,
+    'from' : IDL.Opt(IDL.Nat64),
+    'by_period' : IDL.Vec(SummaryGroup),
+    'top_recipients' : IDL.Vec(SummaryGroup),
+    'totals' : IDL.Vec(SummaryGroup),
+  });
+  const Result_12 = IDL.Variant({ 'Ok' : SpendingSummary, 'Err' : IDL.Text });
+  const Result_13 = IDL.Variant({ 'Ok' : SavedAccount, 'Err' : IDL.Text });
   return IDL.Service({
-    'copilot_chat' : IDL.Func([IDL.Vec(ChatMessage)], [IDL.Text], []),
+    'approve_plan' : IDL.Func([IDL.Text], [Result], []),
+    'cancel_invoice' : IDL.Func([IDL.Nat64], [Result_1], []),
+    'cancel_plan' : IDL.Func([], [IDL.Opt(IDL.Text)], []),
+    'cancel_recurring' : IDL.Func([IDL.Nat64], [Result_2], []),
+    'confirm' : IDL.Func([IDL.Text], [Result_3], []),
+    'copilot_chat' : IDL.Func(
+        [IDL.Nat64, IDL.Text, IDL.Opt(LlmModel)],
+        [Result_4],
+        [],
+      ),
+    'create_invoice' : IDL.Func([InvoiceArgs], [Result_1], []),
+    'get_account_transactions' : IDL.Func([HistoryArgs], [Result_5], []),
+    'get_chat_trace' : IDL.Func([], [IDL.Vec(ModelTrace)], ['query']),
+    'get_config' : IDL.Func([], [Config], ['query']),
+    'get_cosign_config' : IDL.Func([], [CosignConfig], ['query']),
+    'get_fiat_value' : IDL.Func([IDL.Text, IDL.Text], [Result_6], []),
+    'get_invoice' : IDL.Func([IDL.Nat64], [IDL.Opt(Invoice)], ['query']),
+    'get_language' : IDL.Func([], [IDL.Opt(IDL.Text)], ['query']),
+    'get_policy' : IDL.Func([], [SpendingPolicy], ['query']),
+    'get_portfolio' : IDL.Func([], [Portfolio], []),
+    'get_session' : IDL.Func([IDL.Nat64], [Result_7], ['query']),
     'list_accounts' : IDL.Func([], [IDL.Vec(SavedAccount)], ['query']),
+    'list_escrows' : IDL.Func([], [IDL.Vec(Escrow)], ['query']),
+    'list_invoices' : IDL.Func([], [IDL.Vec(Invoice)], ['query']),
+    'list_receipts' : IDL.Func([], [IDL.Vec(Receipt)], ['query']),
+    'list_recurring' : IDL.Func([], [IDL.Vec(RecurringTransfer)], ['query']),
+    'pending_approvals' : IDL.Func([], [IDL.Vec(ApprovalRequest)], ['query']),
+    'plan_history' : IDL.Func([IDL.Text], [IDL.Vec(TransferPlan)], ['query']),
+    'prepare_approval' : IDL.Func([ApprovalPrepArgs], [Result_8], []),
+    'refund_escrow' : IDL.Func([IDL.Nat64], [Result_9], []),
+    'reject_plan' : IDL.Func([IDL.Text, IDL.Opt(IDL.Text)], [Result], []),
+    'release_escrow' : IDL.Func([IDL.Nat64], [Result_9], []),
+    'resume_recurring' : IDL.Func([IDL.Nat64], [Result_2], []),
     'save_account' : IDL.Func(
         [IDL.Text, IDL.Text, IDL.Opt(IDL.Vec(IDL.Nat8))],
-        [Result],
+        [Result_10],
+        [],
+      ),
+    'schedule_recurring' : IDL.Func([RecurringArgs], [Result_2], []),
+    'set_cosign_config' : IDL.Func([CosignConfig], [Result_10], []),
+    'set_dex_canister' : IDL.Func([IDL.Opt(IDL.Principal)], [Result_10], []),
+    'set_index_canister' : IDL.Func(
+        [IDL.Text, IDL.Opt(IDL.Principal)],
+        [Result_10],
         [],
       ),
+    'set_language' : IDL.Func([IDL.Opt(IDL.Text)], [Result_6], []),
+    'set_model' : IDL.Func([LlmModel, IDL.Opt(LlmModel)], [Result_10], []),
+    'set_oracle_canister' : IDL.Func([IDL.Opt(IDL.Principal)], [Result_10], []),
+    'set_policy' : IDL.Func([SpendingPolicy], [Result_10], []),
+    'simulate_transfer' : IDL.Func([IDL.Text], [Result_11], []),
+    'spending_summary' : IDL.Func([SummaryArgs], [Result_12], ['query']),
+    'start_session' : IDL.Func([], [IDL.Nat64], []),
+    'tag_account' : IDL.Func([IDL.Text, IDL.Vec(IDL.Text)], [Result_13], []),
+  });
+};
+export const init = ({ IDL }) => {
+  const LlmModel = IDL.Variant({
+    'Llama4Scout' : IDL.Null,
+    'Qwen3_32B' : IDL.Null,
+    'Llama3_1_8B' : IDL.Null,
+  });
+  const InitArgs = IDL.Record({
+    'model' : IDL.Opt(LlmModel),
+    'fallback_model' : IDL.Opt(LlmModel),
   });
+  return [IDL.Opt(InitArgs)];
 };
-export const init = ({ IDL }) => { return []; };